@@ -0,0 +1,68 @@
+//! Module with a summary of a user account, joining its passwd, group and shadow entries.
+use crate::{
+    group::Group,
+    passwd::{Passwd, Result},
+    shadow::Shadow,
+};
+
+/// Everything about a user account.
+#[derive(Clone, Debug)]
+pub struct Account {
+    passwd:        Passwd,
+    primary_group: Group,
+    supplementary: Vec<Group>,
+    shadow:        Option<Shadow>,
+}
+
+impl Account {
+    /// Get the passwd entry of the user.
+    #[inline]
+    pub fn passwd(&self) -> &Passwd { &self.passwd }
+
+    /// Get the group of the group ID of the user.
+    #[inline]
+    pub fn primary_group(&self) -> &Group { &self.primary_group }
+
+    /// Get the other groups the user is a member of, without the primary group.
+    #[inline]
+    pub fn supplementary(&self) -> &[Group] { &self.supplementary }
+
+    /// Get the shadow entry of the user, `None` if the shadow password file couldn't be read
+    /// or has no entry for the user.
+    #[inline]
+    pub fn shadow(&self) -> Option<&Shadow> { self.shadow.as_ref() }
+}
+
+/// Get the account summary of the user `name`.
+///
+/// Fails if the user or its primary group don't exist, or the groups of the user can't be
+/// found. A unreadable shadow password file is not a error, since only privileged users can
+/// read it.
+pub fn summary(name: &str) -> Result<Account> {
+    let passwd = Passwd::from_name(name)?;
+    let primary_group = passwd.primary_group()?;
+    let supplementary =
+        passwd.belongs_to()?.into_iter().filter(|gr| gr.id() != primary_group.id()).collect();
+    let shadow = Shadow::from_name(name).unwrap_or(None);
+
+    Ok(Account { passwd, primary_group, supplementary, shadow })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_of_root() {
+        let root = summary("root").unwrap();
+
+        assert_eq!(root.passwd().uid(), 0);
+        assert_eq!(root.primary_group().id(), root.passwd().gid());
+        assert!(root.supplementary().iter().all(|gr| gr.id() != root.primary_group().id()));
+        if let Some(shadow) = root.shadow() {
+            assert_eq!(shadow.name(), "root");
+        }
+
+        assert!(summary("coreutils-no-such-user").is_err());
+    }
+}
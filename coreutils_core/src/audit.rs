@@ -6,12 +6,18 @@
 use std::{
     error::Error,
     fmt::{self, Display},
-    mem::MaybeUninit,
+    io,
+    mem::{self, MaybeUninit},
 };
 
+use crate::types::Uid;
+
 #[cfg(target_os = "macos")]
 use libc::dev_t;
-use libc::{c_int, c_uint, pid_t, uid_t};
+use libc::{c_int, c_uint, c_void, pid_t, uid_t};
+
+/// `auditon` command to get the state of a audit session by its ID.
+const A_GETSINFO_ADDR: c_int = 32;
 
 /// Struct for errors that happens on calls to `C` audit functions
 #[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
@@ -98,6 +104,20 @@ pub struct AuditInfo {
     pub ai_asid: AuditSessionId,
 }
 
+impl AuditInfo {
+    /// Get the audit user ID.
+    #[inline]
+    pub fn auid(&self) -> AuditUserId { self.ai_auid }
+
+    /// Get the audit session ID.
+    #[inline]
+    pub fn asid(&self) -> AuditSessionId { self.ai_asid }
+
+    /// Get the terminal ID.
+    #[inline]
+    pub fn termid(&self) -> AuditTerminalId { self.ai_termid }
+}
+
 impl Display for AuditInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "auid={}", self.ai_auid)?;
@@ -152,20 +172,67 @@ extern "C" {
     ///
     /// Returns `0` is successful, `-1` otherwise.
     pub fn getaudit_addr(auditinfo_addr: *mut AuditInfoAddr, length: c_int) -> c_int;
+
+    /// This system call manipulates various audit control operations, the `cmd` is run with
+    /// the argument pointed by `data` of `length` bytes.
+    ///
+    /// Returns `0` is successful, `-1` otherwise.
+    pub fn auditon(cmd: c_int, data: *mut c_void, length: c_uint) -> c_int;
 }
 
 /// Prints the `AuditInfo` if `getaudit()` call was successful, return a Err otherwise.
 pub fn auditid() -> Result<(), AuditError> {
+    let auditinfo = current_audit_session()?;
+
+    println!("{}", auditinfo);
+
+    Ok(())
+}
+
+/// Get the audit session state of the current process.
+pub fn current_audit_session() -> Result<AuditInfo, AuditError> {
     let mut auditinfo: MaybeUninit<AuditInfo> = MaybeUninit::zeroed();
-    let address = auditinfo.as_mut_ptr() as *mut AuditInfo;
 
-    if unsafe { getaudit(address) } < 0 {
-        return Err(AuditError { err: "getaudit: Operation not permitted".to_string() });
+    if unsafe { getaudit(auditinfo.as_mut_ptr()) } < 0 {
+        return Err(AuditError { err: format!("getaudit: {}", io::Error::last_os_error()) });
     }
 
-    let auditinfo = unsafe { auditinfo.assume_init() };
+    Ok(unsafe { auditinfo.assume_init() })
+}
 
-    println!("{}", auditinfo);
+/// Get the audit user ID owning the audit session with `session_id`.
+pub fn session_user(session_id: u32) -> Result<Uid, AuditError> {
+    let mut auditinfo_addr: AuditInfoAddr = unsafe { mem::zeroed() };
+    auditinfo_addr.ai_asid = session_id as AuditSessionId;
+
+    let res = unsafe {
+        auditon(
+            A_GETSINFO_ADDR,
+            &mut auditinfo_addr as *mut AuditInfoAddr as *mut c_void,
+            mem::size_of::<AuditInfoAddr>() as c_uint,
+        )
+    };
+
+    if res < 0 {
+        return Err(AuditError { err: format!("auditon: {}", io::Error::last_os_error()) });
+    }
 
-    Ok(())
+    Ok(auditinfo_addr.ai_auid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_current_session() {
+        let info = current_audit_session().unwrap();
+
+        // Reading the state of a session by its ID needs privileges
+        if unsafe { libc::geteuid() } == 0 {
+            if let Ok(auid) = session_user(info.asid() as u32) {
+                assert_eq!(auid, info.auid());
+            }
+        }
+    }
 }
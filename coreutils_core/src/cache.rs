@@ -0,0 +1,365 @@
+//! Module for resolving user and group names and IDs, remembering every answer.
+//!
+//! Tools resolving many IDs, like `ls -l` on a big directory, should create a single
+//! `NameResolver` and reuse it, so every ID or name is looked up only once.
+//!
+//! The resolver remembers the modification time of the passwd and group files, and forgets
+//! the answers of a database when its file changes, so long running tools don't show stale
+//! names.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    str,
+    time::SystemTime,
+};
+
+use crate::{
+    group::Group,
+    passwd::Passwd,
+    types::{Gid, Uid},
+};
+
+use bstr::{BString, ByteSlice};
+
+/// Default path of the passwd file.
+const PASSWD_PATH: &str = "/etc/passwd";
+/// Default path of the group file.
+const GROUP_PATH: &str = "/etc/group";
+
+/// The lookups done by a `NameResolver` when the answer is not known yet.
+pub trait Lookup {
+    /// Get the name of the user with `uid`.
+    fn user_name(&self, uid: Uid) -> Option<BString>;
+    /// Get the name of the group with `gid`.
+    fn group_name(&self, gid: Gid) -> Option<BString>;
+    /// Get the ID of the user named `name`.
+    fn uid_for(&self, name: &str) -> Option<Uid>;
+    /// Get the ID of the group named `name`.
+    fn gid_for(&self, name: &str) -> Option<Gid>;
+
+    /// Path of the passwd file whose changes invalidate the user answers.
+    fn passwd_path(&self) -> &Path { Path::new(PASSWD_PATH) }
+
+    /// Path of the group file whose changes invalidate the group answers.
+    fn group_path(&self) -> &Path { Path::new(GROUP_PATH) }
+}
+
+/// Lookups on the system user and group databases.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SystemLookup;
+
+impl Lookup for SystemLookup {
+    fn user_name(&self, uid: Uid) -> Option<BString> {
+        Passwd::from_uid(uid).ok().map(|pw| pw.name().to_owned())
+    }
+
+    fn group_name(&self, gid: Gid) -> Option<BString> {
+        Group::from_gid(gid).ok().map(|gr| gr.name().to_owned())
+    }
+
+    fn uid_for(&self, name: &str) -> Option<Uid> { Passwd::from_name(name).ok().map(|pw| pw.uid()) }
+
+    fn gid_for(&self, name: &str) -> Option<Gid> { Group::from_name(name).ok().map(|gr| gr.id()) }
+}
+
+/// Lookups on a passwd file and a group file, in the `/etc/passwd` and `/etc/group` formats.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileLookup {
+    passwd_path: PathBuf,
+    group_path:  PathBuf,
+}
+
+impl FileLookup {
+    /// Create lookups on the passwd file on `passwd_path` and the group file on `group_path`.
+    pub fn new(passwd_path: impl AsRef<Path>, group_path: impl AsRef<Path>) -> Self {
+        FileLookup {
+            passwd_path: passwd_path.as_ref().to_owned(),
+            group_path:  group_path.as_ref().to_owned(),
+        }
+    }
+
+    fn users(&self) -> Vec<Passwd> { Passwd::from_file(&self.passwd_path).unwrap_or_default() }
+}
+
+impl Lookup for FileLookup {
+    fn user_name(&self, uid: Uid) -> Option<BString> {
+        self.users().into_iter().find(|pw| pw.uid() == uid).map(|pw| pw.name().to_owned())
+    }
+
+    fn group_name(&self, gid: Gid) -> Option<BString> {
+        groups_in_file(&self.group_path).into_iter().find(|(_, id)| *id == gid).map(|gr| gr.0)
+    }
+
+    fn uid_for(&self, name: &str) -> Option<Uid> {
+        self.users().into_iter().find(|pw| pw.name() == name.as_bytes()).map(|pw| pw.uid())
+    }
+
+    fn gid_for(&self, name: &str) -> Option<Gid> {
+        groups_in_file(&self.group_path).into_iter().find(|(gr, _)| gr == name).map(|gr| gr.1)
+    }
+
+    fn passwd_path(&self) -> &Path { &self.passwd_path }
+
+    fn group_path(&self) -> &Path { &self.group_path }
+}
+
+/// Resolver of user and group names and IDs, remembering every answer, including the
+/// missing ones, until the file of their database is modified.
+#[derive(Debug, Clone)]
+pub struct NameResolver<L = SystemLookup> {
+    lookup:       L,
+    passwd_mtime: Option<SystemTime>,
+    group_mtime:  Option<SystemTime>,
+    user_names:   HashMap<Uid, Option<BString>>,
+    group_names:  HashMap<Gid, Option<BString>>,
+    uids:         HashMap<String, Option<Uid>>,
+    gids:         HashMap<String, Option<Gid>>,
+}
+
+impl NameResolver {
+    /// Create a resolver on the system user and group databases, invalidated by changes of
+    /// `/etc/passwd` and `/etc/group`.
+    pub fn new() -> Self { Self::with_lookup(SystemLookup) }
+}
+
+impl NameResolver<FileLookup> {
+    /// Create a resolver on the passwd file on `passwd_path` and the group file on
+    /// `group_path`, in the `/etc/passwd` and `/etc/group` formats.
+    pub fn from_files(passwd_path: impl AsRef<Path>, group_path: impl AsRef<Path>) -> Self {
+        Self::with_lookup(FileLookup::new(passwd_path, group_path))
+    }
+}
+
+impl Default for NameResolver {
+    fn default() -> Self { Self::new() }
+}
+
+impl<L: Lookup> NameResolver<L> {
+    /// Create a resolver doing its lookups with `lookup`.
+    pub fn with_lookup(lookup: L) -> Self {
+        NameResolver {
+            passwd_mtime: modified(lookup.passwd_path()),
+            group_mtime: modified(lookup.group_path()),
+            lookup,
+            user_names: HashMap::new(),
+            group_names: HashMap::new(),
+            uids: HashMap::new(),
+            gids: HashMap::new(),
+        }
+    }
+
+    /// Get the name of the user with `uid`, `None` if there is no such user.
+    pub fn user_name(&mut self, uid: Uid) -> Option<&BString> {
+        self.refresh_if_changed();
+
+        let lookup = &self.lookup;
+        self.user_names.entry(uid).or_insert_with(|| lookup.user_name(uid)).as_ref()
+    }
+
+    /// Get the name of the group with `gid`, `None` if there is no such group.
+    pub fn group_name(&mut self, gid: Gid) -> Option<&BString> {
+        self.refresh_if_changed();
+
+        let lookup = &self.lookup;
+        self.group_names.entry(gid).or_insert_with(|| lookup.group_name(gid)).as_ref()
+    }
+
+    /// Get the ID of the user named `name`, `None` if there is no such user.
+    pub fn uid_for(&mut self, name: &str) -> Option<Uid> {
+        self.refresh_if_changed();
+
+        if let Some(uid) = self.uids.get(name) {
+            return *uid;
+        }
+
+        let uid = self.lookup.uid_for(name);
+        self.uids.insert(name.to_string(), uid);
+        uid
+    }
+
+    /// Get the ID of the group named `name`, `None` if there is no such group.
+    pub fn gid_for(&mut self, name: &str) -> Option<Gid> {
+        self.refresh_if_changed();
+
+        if let Some(gid) = self.gids.get(name) {
+            return *gid;
+        }
+
+        let gid = self.lookup.gid_for(name);
+        self.gids.insert(name.to_string(), gid);
+        gid
+    }
+
+    /// Forget the answers of the databases whose file was modified since the last check,
+    /// returning `true` if any was.
+    pub fn refresh_if_changed(&mut self) -> bool {
+        let passwd_mtime = modified(self.lookup.passwd_path());
+        let group_mtime = modified(self.lookup.group_path());
+        let mut changed = false;
+
+        if passwd_mtime != self.passwd_mtime {
+            self.passwd_mtime = passwd_mtime;
+            self.user_names.clear();
+            self.uids.clear();
+            changed = true;
+        }
+        if group_mtime != self.group_mtime {
+            self.group_mtime = group_mtime;
+            self.group_names.clear();
+            self.gids.clear();
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+/// Modification time of the file on `path`, `None` if it can't be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// The names and IDs of the groups of the group file on `path`, empty if it can't be read.
+fn groups_in_file(path: &Path) -> Vec<(BString, Gid)> {
+    let contents = fs::read(path).unwrap_or_default();
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split_str(":").collect();
+            let id = str::from_utf8(fields.get(2)?).ok()?.parse().ok()?;
+            Some((BString::from(fields[0]), id))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::Cell, env, ffi::CString, os::unix::ffi::OsStrExt, process};
+
+    use libc::{timeval, utimes};
+
+    /// Lookup on a fixed database of `root` (0) and `alice` (1000) users and `wheel` (10)
+    /// group, counting every lookup.
+    #[derive(Default)]
+    struct Counting {
+        calls: Cell<usize>,
+    }
+
+    impl Counting {
+        fn count(&self) { self.calls.set(self.calls.get() + 1) }
+    }
+
+    impl Lookup for Counting {
+        fn user_name(&self, uid: Uid) -> Option<BString> {
+            self.count();
+            match uid {
+                0 => Some(BString::from("root")),
+                1000 => Some(BString::from("alice")),
+                _ => None,
+            }
+        }
+
+        fn group_name(&self, gid: Gid) -> Option<BString> {
+            self.count();
+            if gid == 10 { Some(BString::from("wheel")) } else { None }
+        }
+
+        fn uid_for(&self, name: &str) -> Option<Uid> {
+            self.count();
+            match name {
+                "root" => Some(0),
+                "alice" => Some(1000),
+                _ => None,
+            }
+        }
+
+        fn gid_for(&self, name: &str) -> Option<Gid> {
+            self.count();
+            if name == "wheel" { Some(10) } else { None }
+        }
+    }
+
+    #[test]
+    fn every_answer_is_memoized() {
+        let mut resolver = NameResolver::with_lookup(Counting::default());
+
+        for _ in 0..3 {
+            assert_eq!(resolver.user_name(1000), Some(&BString::from("alice")));
+            assert_eq!(resolver.user_name(1001), None);
+            assert_eq!(resolver.group_name(10), Some(&BString::from("wheel")));
+            assert_eq!(resolver.group_name(11), None);
+            assert_eq!(resolver.uid_for("root"), Some(0));
+            assert_eq!(resolver.uid_for("bob"), None);
+            assert_eq!(resolver.gid_for("wheel"), Some(10));
+            assert_eq!(resolver.gid_for("staff"), None);
+        }
+
+        assert_eq!(resolver.lookup.calls.get(), 8);
+    }
+
+    fn fixture_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("coreutils_cache_{}_{}", name, process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn set_mtime(path: &Path, secs: i64) {
+        let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let time = timeval { tv_sec: secs as _, tv_usec: 0 };
+        assert_eq!(unsafe { utimes(path.as_ptr(), [time, time].as_ptr()) }, 0);
+    }
+
+    #[test]
+    fn refetch_after_mtime_change() {
+        let passwd = fixture_file("passwd", "alice:x:1000:1000::/home/alice:/bin/sh\n");
+        let group = fixture_file("group", "staff:x:50:alice\n");
+        set_mtime(&passwd, 1_000_000);
+        set_mtime(&group, 1_000_000);
+
+        let mut resolver = NameResolver::from_files(&passwd, &group);
+        let first = (
+            resolver.user_name(1000).cloned(),
+            resolver.group_name(50).cloned(),
+            resolver.user_name(1001).cloned(),
+            resolver.uid_for("alice"),
+            resolver.gid_for("staff"),
+        );
+
+        // Same modification time, so the resolver still has the old answers
+        fs::write(&passwd, "bob:x:1000:1000::/home/bob:/bin/sh\n").unwrap();
+        set_mtime(&passwd, 1_000_000);
+        let cached = (resolver.user_name(1000).cloned(), resolver.uid_for("alice"));
+        let unchanged = resolver.refresh_if_changed();
+
+        set_mtime(&passwd, 2_000_000);
+        let changed = resolver.refresh_if_changed();
+        let refetched = (
+            resolver.user_name(1000).cloned(),
+            resolver.uid_for("alice"),
+            resolver.group_name(50).cloned(),
+        );
+
+        fs::remove_file(&passwd).unwrap();
+        fs::remove_file(&group).unwrap();
+
+        let (alice, staff) = (Some(BString::from("alice")), Some(BString::from("staff")));
+        assert_eq!(first, (alice.clone(), staff.clone(), None, Some(1000), Some(50)));
+        assert_eq!(cached, (alice, Some(1000)));
+        assert!(!unchanged);
+        assert!(changed);
+        assert_eq!(refetched, (Some(BString::from("bob")), None, staff));
+    }
+
+    #[test]
+    fn system_root() {
+        let mut resolver = NameResolver::new();
+
+        assert_eq!(resolver.user_name(0), Some(&BString::from("root")));
+        assert_eq!(resolver.uid_for("root"), Some(0));
+    }
+}
@@ -0,0 +1,44 @@
+//! Module to generate the shell completions of the binaries on their build scripts.
+
+use clap::{App, Shell};
+
+/// Shells that get completions generated.
+pub const SHELLS: [Shell; 5] =
+    [Shell::Zsh, Shell::Fish, Shell::Bash, Shell::PowerShell, Shell::Elvish];
+
+/// Write the Zsh, Fish, Bash, PowerShell and Elvish completions of the binary `name`, with
+/// the command line `app`, to the `out_dir` directory.
+pub fn generate(app: &mut App<'_, '_>, name: &str, out_dir: &str) {
+    for &shell in &SHELLS {
+        app.gen_completions(name, shell, out_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, fs, process};
+
+    use clap::Arg;
+
+    #[test]
+    fn generate_writes_every_shell() {
+        let dir = env::temp_dir().join(format!("coreutils_completions_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut app = App::new("tool").arg(Arg::with_name("verbose").long("verbose"));
+
+        generate(&mut app, "tool", dir.to_str().unwrap());
+
+        let mut files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|f| f.unwrap().file_name().into_string().unwrap())
+            .collect();
+        let bash = fs::read_to_string(dir.join("tool.bash")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        files.sort();
+        assert_eq!(files, ["_tool", "_tool.ps1", "tool.bash", "tool.elv", "tool.fish"]);
+        assert!(bash.contains("--verbose"));
+    }
+}
@@ -22,6 +22,27 @@ pub const HOST_OS: &str = "Fuchsia";
 #[cfg(target_os = "redox")]
 pub const HOST_OS: &str = "Redox";
 
+/// Default path of the failed login account database (btmp).
+pub const DEFAULT_BTMP_PATH: &str = "/var/log/btmp";
+
+/// If the `utmpx` entries of the platform have the remote host field (`ut_host`).
+pub const HAS_UT_HOST: bool = cfg!(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+    target_os = "solaris"
+));
+/// If the `utmpx` entries of the platform have the IPv6 address field (`ut_addr_v6`).
+pub const HAS_UT_ADDR_V6: bool = cfg!(target_os = "linux");
+/// If the `utmpx` entries of the platform have the session ID field (`ut_session`).
+pub const HAS_UT_SESSION: bool =
+    cfg!(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly"));
+/// If the `utmpx` entries of the platform have the exit status field (`ut_exit`).
+pub const HAS_UT_EXIT: bool =
+    cfg!(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly"));
+
 #[cfg(target_arch = "aarch64")]
 pub const MACHINE_ARCH: &str = "aarch64";
 #[cfg(target_arch = "arm")]
@@ -102,3 +123,19 @@ pub const MACHINE_ARCH: &str = "riscv32i";
 pub const MACHINE_ARCH: &str = "thumbv8m.base";
 #[cfg(target_arch = "thumbv8m.main")]
 pub const MACHINE_ARCH: &str = "thumbv8m.main";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utmpx_field_constants() {
+        let optional = [HAS_UT_ADDR_V6, HAS_UT_SESSION, HAS_UT_EXIT];
+
+        // No platform has the optional fields without the host
+        assert!(HAS_UT_HOST || optional.iter().all(|has| !has));
+        if cfg!(target_os = "linux") {
+            assert!(HAS_UT_HOST && optional.iter().all(|has| *has));
+        }
+    }
+}
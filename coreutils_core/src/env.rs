@@ -4,22 +4,44 @@ use std::{
     convert::From,
     env::{self, VarError},
     error::Error as StdError,
+    ffi::{CStr, OsStr},
     fmt::{self, Display},
     io::Error as IoError,
     mem::MaybeUninit,
-    os::raw::c_char,
-    path::PathBuf,
+    os::{
+        raw::c_char,
+        unix::{ffi::OsStrExt, fs::PermissionsExt},
+    },
+    path::{Path, PathBuf},
 };
 
-use libc::stat;
+use libc::{c_int, size_t, stat};
+
+#[cfg(target_os = "openbsd")]
+use crate::utmp::UtmpSet;
+#[cfg(not(any(
+    target_os = "netbsd",
+    target_os = "solaris",
+    target_os = "fuchsia",
+    target_os = "haiku",
+    target_os = "openbsd"
+)))]
+use crate::utmpx::UtmpxSet;
+use crate::{file_descriptor::FileDescriptor, tty::TTYName};
+
+use bstr::{BStr, BString, ByteSlice};
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Search path used when `PATH` is not set, the same as `execvp`.
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+
 /// Possible errors when calling this module functions
 #[derive(Debug)]
 pub enum Error {
     Var(VarError),
     Io(IoError),
+    Unset(BString),
 }
 
 impl Display for Error {
@@ -28,6 +50,7 @@ impl Display for Error {
         match self {
             Self::Var(err) => write!(f, "Failed to get var with error: {}", err),
             Self::Io(err) => write!(f, "IO error: {}", err),
+            Self::Unset(name) => write!(f, "Variable not set: {}", name),
         }
     }
 }
@@ -48,6 +71,7 @@ impl StdError for Error {
         match self {
             Self::Var(err) => Some(err),
             Self::Io(err) => Some(err),
+            Self::Unset(_) => None,
         }
     }
 }
@@ -81,3 +105,342 @@ pub fn current_dir_logical() -> Result<PathBuf> {
     }
     Err(Error::Io(IoError::last_os_error()))
 }
+
+/// Get the environment of the current process as `(name, value)` pairs, in the same order
+/// and with the same bytes they have in the process environment, even if they are not valid
+/// UTF-8.
+///
+/// Entries without a `=` are left out.
+pub fn environ() -> Vec<(BString, BString)> {
+    env::vars_os()
+        .map(|(name, value)| (BString::from(name.as_bytes()), BString::from(value.as_bytes())))
+        .collect()
+}
+
+/// Get the name the user logged in with, `None` if it can't be found.
+///
+/// The sources are tried in order until one has a name:
+/// 1. `getlogin_r`, the user of the session of the controlling terminal.
+/// 2. The user of the utmp entry of the terminal of the standard input.
+/// 3. The `LOGNAME` environment variable, then `USER`, if not empty. Those can be set to
+///    anything, so they are only used when the others fail, like without a terminal.
+pub fn login_name() -> Option<BString> { getlogin().or_else(tty_login).or_else(env_login) }
+
+/// The name `getlogin_r` reports.
+fn getlogin() -> Option<BString> {
+    extern "C" {
+        fn getlogin_r(name: *mut c_char, namesize: size_t) -> c_int;
+    }
+
+    // Larger than LOGIN_NAME_MAX everywhere
+    let mut buff = [0 as c_char; 256];
+    if unsafe { getlogin_r(buff.as_mut_ptr(), buff.len()) } != 0 {
+        return None;
+    }
+
+    let name = unsafe { CStr::from_ptr(buff.as_ptr()) }.to_bytes();
+    if name.is_empty() { None } else { Some(BString::from(name)) }
+}
+
+/// The user of the user process entry of the terminal of the standard input.
+#[cfg(not(any(
+    target_os = "netbsd",
+    target_os = "solaris",
+    target_os = "fuchsia",
+    target_os = "haiku",
+    target_os = "openbsd"
+)))]
+fn tty_login() -> Option<BString> {
+    let tty = TTYName::new(FileDescriptor::StdIn).ok()?;
+
+    UtmpxSet::system()
+        .iter()
+        .find(|u| u.is_user_process() && u.device_name() == tty.short())
+        .map(|u| u.user().to_owned())
+}
+
+/// The user of the utmp entry of the terminal of the standard input.
+#[cfg(target_os = "openbsd")]
+fn tty_login() -> Option<BString> {
+    let tty = TTYName::new(FileDescriptor::StdIn).ok()?;
+
+    UtmpSet::system()
+        .ok()?
+        .iter()
+        .find(|u| u.device_name() == tty.short() && !u.user().is_empty())
+        .map(|u| u.user().to_owned())
+}
+
+/// There is no utmp to look the terminal up on these platforms.
+#[cfg(any(
+    target_os = "netbsd",
+    target_os = "solaris",
+    target_os = "fuchsia",
+    target_os = "haiku"
+))]
+fn tty_login() -> Option<BString> { None }
+
+/// The name on `LOGNAME`, or on `USER` if `LOGNAME` is unset or empty.
+fn env_login() -> Option<BString> {
+    ["LOGNAME", "USER"]
+        .iter()
+        .filter_map(env::var_os)
+        .find(|name| !name.is_empty())
+        .map(|name| BString::from(name.as_bytes()))
+}
+
+/// Find the executable `cmd` would run, searching the directories on `PATH` in order.
+///
+/// A `cmd` containing a `/` is taken as already resolved and returned as it is. A empty
+/// element on `PATH` means the current directory.
+///
+/// Returns `None` if no executable file named `cmd` is found.
+pub fn search_path(cmd: &OsStr) -> Option<PathBuf> {
+    match env::var_os("PATH") {
+        Some(path) => search_path_in(cmd, &path),
+        None => search_path_in(cmd, OsStr::new(DEFAULT_PATH)),
+    }
+}
+
+/// Find the executable `cmd` on the directories of the search path `path`.
+fn search_path_in(cmd: &OsStr, path: &OsStr) -> Option<PathBuf> {
+    if cmd.is_empty() {
+        return None;
+    }
+
+    if cmd.as_bytes().contains(&b'/') {
+        return Some(PathBuf::from(cmd));
+    }
+
+    path_dirs(path).map(|dir| dir.join(cmd)).find(|file| is_executable(file))
+}
+
+/// The directories of the search path `path`, with empty elements as the current directory.
+fn path_dirs(path: &OsStr) -> impl Iterator<Item = PathBuf> + '_ {
+    env::split_paths(path)
+        .map(|dir| if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir })
+}
+
+/// Check if `file` is a regular file with any execute bit set.
+fn is_executable(file: &Path) -> bool {
+    match file.metadata() {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+/// Expand the `$VAR` and `${VAR}` references in `input` with the values of the current
+/// environment.
+///
+/// Names are made of ASCII letters, digits and `_`, not starting with a digit. A `$` not
+/// followed by a name, or a `${` without a matching `}`, is kept as it is, and `\$` expands
+/// to a literal `$`. Unset variables expand to nothing.
+pub fn expand_vars(input: &BStr) -> BString {
+    expand_with(input, |name| Ok(var_bytes(name).unwrap_or_default()))
+        .expect("Lookup of unset variables does not fail")
+}
+
+/// Same as [`expand_vars`], but fails with [`Error::Unset`] on the first reference to a
+/// unset variable.
+///
+/// [`expand_vars`]: ./fn.expand_vars.html
+/// [`Error::Unset`]: ./enum.Error.html#variant.Unset
+pub fn expand_vars_strict(input: &BStr) -> Result<BString> {
+    expand_with(input, |name| var_bytes(name).ok_or_else(|| Error::Unset(BString::from(name))))
+}
+
+/// The value of the environment variable `name` as bytes.
+fn var_bytes(name: &[u8]) -> Option<Vec<u8>> {
+    env::var_os(OsStr::from_bytes(name)).map(|value| value.as_bytes().to_vec())
+}
+
+/// Expand the variable references in `input`, taking their values from `lookup`.
+fn expand_with<F>(input: &BStr, mut lookup: F) -> Result<BString>
+where
+    F: FnMut(&[u8]) -> Result<Vec<u8>>,
+{
+    let input = input.as_bytes();
+    let mut expanded = Vec::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < input.len() {
+        match input[i] {
+            b'\\' if input.get(i + 1) == Some(&b'$') => {
+                expanded.push(b'$');
+                i += 2;
+            },
+            b'$' if input.get(i + 1) == Some(&b'{') => {
+                let len = name_len(&input[i + 2..]);
+                if len > 0 && input.get(i + 2 + len) == Some(&b'}') {
+                    expanded.extend(lookup(&input[i + 2..i + 2 + len])?);
+                    i += len + 3;
+                } else {
+                    expanded.extend_from_slice(b"${");
+                    i += 2;
+                }
+            },
+            b'$' => {
+                let len = name_len(&input[i + 1..]);
+                if len > 0 {
+                    expanded.extend(lookup(&input[i + 1..i + 1 + len])?);
+                } else {
+                    expanded.push(b'$');
+                }
+                i += len + 1;
+            },
+            byte => {
+                expanded.push(byte);
+                i += 1;
+            },
+        }
+    }
+
+    Ok(BString::from(expanded))
+}
+
+/// The length of the variable name at the start of `bytes`, 0 if there is none.
+fn name_len(bytes: &[u8]) -> usize {
+    match bytes.first() {
+        Some(first) if first.is_ascii_alphabetic() || *first == b'_' => bytes
+            .iter()
+            .position(|b| !b.is_ascii_alphanumeric() && *b != b'_')
+            .unwrap_or(bytes.len()),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        ffi::OsString,
+        fs::{self, File},
+        process,
+    };
+
+    #[test]
+    fn environ_keeps_non_utf8_values() {
+        let name = "COREUTILS_CORE_ENVIRON_TEST";
+        let value = b"\xff\xfe=not utf-8";
+        env::set_var(name, OsStr::from_bytes(value));
+
+        let vars = environ();
+        env::remove_var(name);
+
+        let found = vars.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_bytes());
+        assert_eq!(found, Some(&value[..]));
+    }
+
+    #[test]
+    fn search_path_finds_executables_only() {
+        let dir = env::temp_dir().join(format!("coreutils_search_path_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let exec = dir.join("fake-exec");
+        let plain = dir.join("fake-plain");
+        File::create(&exec).unwrap();
+        File::create(&plain).unwrap();
+        fs::set_permissions(&exec, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&plain, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut path = OsString::from("/coreutils-no-such-dir:");
+        path.push(&dir);
+
+        let found = search_path_in(OsStr::new("fake-exec"), &path);
+        let not_exec = search_path_in(OsStr::new("fake-plain"), &path);
+        let missing = search_path_in(OsStr::new("fake-missing"), &path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found, Some(exec));
+        assert_eq!(not_exec, None);
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn search_path_resolved_and_empty_elements() {
+        assert_eq!(
+            search_path_in(OsStr::new("./script"), OsStr::new("/bin")),
+            Some(PathBuf::from("./script"))
+        );
+        assert_eq!(search_path_in(OsStr::new(""), OsStr::new("/bin")), None);
+
+        let dirs: Vec<_> = path_dirs(OsStr::new(":/bin::/usr/bin:")).collect();
+        assert_eq!(
+            dirs,
+            [".", "/bin", ".", "/usr/bin", "."].iter().map(PathBuf::from).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn login_name_from_environment() {
+        let saved: Vec<_> = ["LOGNAME", "USER"].iter().map(env::var_os).collect();
+
+        env::set_var("LOGNAME", "alice");
+        env::set_var("USER", "bob");
+        let logname = env_login();
+
+        env::set_var("LOGNAME", "");
+        let empty_logname = env_login();
+
+        env::remove_var("LOGNAME");
+        let user = env_login();
+
+        env::remove_var("USER");
+        let none = env_login();
+
+        for (var, value) in ["LOGNAME", "USER"].iter().zip(saved) {
+            if let Some(value) = value {
+                env::set_var(var, value);
+            }
+        }
+
+        assert_eq!(logname.unwrap(), "alice");
+        assert_eq!(empty_logname.unwrap(), "bob");
+        assert_eq!(user.unwrap(), "bob");
+        assert_eq!(none, None);
+    }
+
+    #[test]
+    fn expand_vars_plain_and_braced() {
+        env::set_var("COREUTILS_EXPAND_A", "alpha");
+        env::set_var("COREUTILS_EXPAND_B", "beta");
+
+        let plain = expand_vars(b"$COREUTILS_EXPAND_A/$COREUTILS_EXPAND_B.txt".as_bstr());
+        let braced = expand_vars(b"${COREUTILS_EXPAND_A}_x${COREUTILS_EXPAND_B}".as_bstr());
+        let nested = expand_vars(b"{${COREUTILS_EXPAND_A}} ${A${COREUTILS_EXPAND_B}}".as_bstr());
+        let open = expand_vars(b"${COREUTILS_EXPAND_A $ $1".as_bstr());
+        env::remove_var("COREUTILS_EXPAND_A");
+        env::remove_var("COREUTILS_EXPAND_B");
+
+        assert_eq!(plain, "alpha/beta.txt");
+        assert_eq!(braced, "alpha_xbeta");
+        assert_eq!(nested, "{alpha} ${Abeta}");
+        assert_eq!(open, "${COREUTILS_EXPAND_A $ $1");
+    }
+
+    #[test]
+    fn expand_vars_escapes() {
+        env::set_var("COREUTILS_EXPAND_ESC", "value");
+
+        let escaped = expand_vars(br"\$COREUTILS_EXPAND_ESC \${COREUTILS_EXPAND_ESC}".as_bstr());
+        let other = expand_vars(br"a\b \\$COREUTILS_EXPAND_ESC".as_bstr());
+        env::remove_var("COREUTILS_EXPAND_ESC");
+
+        assert_eq!(escaped, "$COREUTILS_EXPAND_ESC ${COREUTILS_EXPAND_ESC}");
+        assert_eq!(other, r"a\b \$COREUTILS_EXPAND_ESC");
+    }
+
+    #[test]
+    fn expand_vars_unset() {
+        env::remove_var("COREUTILS_EXPAND_UNSET");
+
+        assert_eq!(expand_vars(b"[$COREUTILS_EXPAND_UNSET]".as_bstr()), "[]");
+        assert_eq!(expand_vars(b"[${COREUTILS_EXPAND_UNSET}]".as_bstr()), "[]");
+
+        match expand_vars_strict(b"x${COREUTILS_EXPAND_UNSET}y".as_bstr()) {
+            Err(Error::Unset(name)) => assert_eq!(name, "COREUTILS_EXPAND_UNSET"),
+            res => panic!("expected a unset variable error, got {:?}", res),
+        }
+        assert_eq!(expand_vars_strict(b"no vars".as_bstr()).unwrap(), "no vars");
+    }
+}
@@ -1,4 +1,17 @@
 //! Module for File descriptor abstractions.
+use std::{
+    fs::File,
+    io,
+    mem::MaybeUninit,
+    ops::{BitAnd, BitOr},
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+};
+
+use libc::{
+    c_int, dup2, fcntl, fstat, FD_CLOEXEC, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL,
+    O_ACCMODE, O_APPEND, O_NONBLOCK, O_RDONLY, O_RDWR, O_SYNC, O_WRONLY, S_IFCHR, S_IFIFO, S_IFMT,
+    S_IFREG, S_IFSOCK,
+};
 
 /// A `FileDescriptor` that can be `StdIn`, `StdOut` or `StdErr`
 /// Usefull when dealing with C call to `ttyname` and `ttyname_r`
@@ -8,3 +21,242 @@ pub enum FileDescriptor {
     StdOut = 1,
     StdErr = 2,
 }
+
+impl AsRawFd for FileDescriptor {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd { *self as RawFd }
+}
+
+impl FileDescriptor {
+    /// Make the file descriptor refer to the same file as `target`, with `dup2`(2).
+    pub fn redirect_to(self, target: &impl AsRawFd) -> io::Result<()> {
+        if unsafe { dup2(target.as_raw_fd(), self.as_raw_fd()) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get a new file descriptor referring to the same file, closed on `exec`, so it keeps
+    /// the file after the file descriptor is redirected.
+    pub fn duplicate(self) -> io::Result<File> {
+        let fd = unsafe { fcntl(self.as_raw_fd(), F_DUPFD_CLOEXEC, 3) };
+
+        if fd == -1 { Err(io::Error::last_os_error()) } else { Ok(unsafe { File::from_raw_fd(fd) }) }
+    }
+}
+
+/// The open file status flags of a file descriptor, as returned by `fcntl(F_GETFL)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OFlags(c_int);
+
+impl OFlags {
+    /// Writes are always done at the end of the file.
+    pub const APPEND: OFlags = OFlags(O_APPEND);
+    /// Operations never block, failing with `WouldBlock` instead.
+    pub const NONBLOCK: OFlags = OFlags(O_NONBLOCK);
+    /// Writes only return after the data is physically stored.
+    pub const SYNC: OFlags = OFlags(O_SYNC);
+
+    /// Create a `OFlags` from the raw flags.
+    #[inline]
+    pub fn from_bits(bits: c_int) -> Self { OFlags(bits) }
+
+    /// Get the raw flags.
+    #[inline]
+    pub fn bits(self) -> c_int { self.0 }
+
+    /// Check if all the flags in `other` are set.
+    #[inline]
+    pub fn contains(self, other: OFlags) -> bool { self.0 & other.0 == other.0 }
+
+    /// Check if the file descriptor was opened for reading.
+    #[inline]
+    pub fn is_readable(self) -> bool {
+        let mode = self.0 & O_ACCMODE;
+        mode == O_RDONLY || mode == O_RDWR
+    }
+
+    /// Check if the file descriptor was opened for writing.
+    #[inline]
+    pub fn is_writable(self) -> bool {
+        let mode = self.0 & O_ACCMODE;
+        mode == O_WRONLY || mode == O_RDWR
+    }
+}
+
+impl BitOr for OFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self { OFlags(self.0 | rhs.0) }
+}
+
+impl BitAnd for OFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self { OFlags(self.0 & rhs.0) }
+}
+
+/// The kind of file a file descriptor refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FdKind {
+    /// A pipe or FIFO.
+    Pipe,
+    /// A regular file.
+    Regular,
+    /// A character device, like a terminal or `/dev/null`.
+    CharDevice,
+    /// A socket.
+    Socket,
+    /// Any other kind, like a directory or block device.
+    Other,
+}
+
+/// Operations on the open file status flags and the file of any file descriptor.
+pub trait FileDescriptorExt: AsRawFd {
+    /// Get the open file status flags of the file descriptor.
+    fn flags(&self) -> io::Result<OFlags> {
+        let res = unsafe { fcntl(self.as_raw_fd(), F_GETFL) };
+
+        if res == -1 { Err(io::Error::last_os_error()) } else { Ok(OFlags(res)) }
+    }
+
+    /// Set or clear the `O_NONBLOCK` flag of the file descriptor, keeping all the others.
+    fn set_nonblocking(&self, yes: bool) -> io::Result<()> {
+        let flags = self.flags()?.bits();
+        let flags = if yes { flags | O_NONBLOCK } else { flags & !O_NONBLOCK };
+
+        let res = unsafe { fcntl(self.as_raw_fd(), F_SETFL, flags) };
+
+        if res == -1 { Err(io::Error::last_os_error()) } else { Ok(()) }
+    }
+
+    /// Check if the file descriptor is closed on `exec`, from its `FD_CLOEXEC` flag.
+    fn is_cloexec(&self) -> io::Result<bool> {
+        let res = unsafe { fcntl(self.as_raw_fd(), F_GETFD) };
+
+        if res == -1 { Err(io::Error::last_os_error()) } else { Ok(res & FD_CLOEXEC != 0) }
+    }
+
+    /// Set or clear the `FD_CLOEXEC` flag of the file descriptor, so it is closed on `exec`
+    /// or kept by the program executed, keeping the other file descriptor flags.
+    fn set_cloexec(&self, yes: bool) -> io::Result<()> {
+        let flags = unsafe { fcntl(self.as_raw_fd(), F_GETFD) };
+        if flags == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = if yes { flags | FD_CLOEXEC } else { flags & !FD_CLOEXEC };
+
+        let res = unsafe { fcntl(self.as_raw_fd(), F_SETFD, flags) };
+
+        if res == -1 { Err(io::Error::last_os_error()) } else { Ok(()) }
+    }
+
+    /// Get the kind of file the file descriptor refers to, with `fstat`(2).
+    fn kind(&self) -> io::Result<FdKind> {
+        let mut stat = MaybeUninit::uninit();
+        if unsafe { fstat(self.as_raw_fd(), stat.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let kind = match unsafe { stat.assume_init() }.st_mode & S_IFMT {
+            S_IFIFO => FdKind::Pipe,
+            S_IFREG => FdKind::Regular,
+            S_IFCHR => FdKind::CharDevice,
+            S_IFSOCK => FdKind::Socket,
+            _ => FdKind::Other,
+        };
+
+        Ok(kind)
+    }
+}
+
+impl<T: AsRawFd + ?Sized> FileDescriptorExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env,
+        fs::{self, OpenOptions},
+        process,
+    };
+
+    use libc::pipe;
+
+    fn pipe_pair() -> (File, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn set_and_clear_nonblocking() {
+        let (read, write) = pipe_pair();
+        assert!(!read.flags().unwrap().contains(OFlags::NONBLOCK));
+
+        read.set_nonblocking(true).unwrap();
+        assert!(read.flags().unwrap().contains(OFlags::NONBLOCK));
+        assert!(!write.flags().unwrap().contains(OFlags::NONBLOCK));
+
+        read.set_nonblocking(false).unwrap();
+        assert!(!read.flags().unwrap().contains(OFlags::NONBLOCK));
+        assert!(read.flags().unwrap().is_readable());
+        assert!(write.flags().unwrap().is_writable());
+    }
+
+    #[test]
+    fn set_and_clear_cloexec() {
+        let (read, write) = pipe_pair();
+        assert!(!read.is_cloexec().unwrap());
+
+        read.set_cloexec(true).unwrap();
+        assert!(read.is_cloexec().unwrap());
+        assert!(!write.is_cloexec().unwrap());
+        // Setting it twice keeps it set
+        read.set_cloexec(true).unwrap();
+        assert!(read.is_cloexec().unwrap());
+
+        read.set_cloexec(false).unwrap();
+        assert!(!read.is_cloexec().unwrap());
+        assert!(FileDescriptor::StdErr.duplicate().unwrap().is_cloexec().unwrap());
+    }
+
+    #[test]
+    fn append_flag() {
+        let path = env::temp_dir().join(format!("coreutils_fd_flags_{}", process::id()));
+        let file = OpenOptions::new().create(true).append(true).open(&path).unwrap();
+        let flags = file.flags();
+        fs::remove_file(&path).unwrap();
+
+        assert!(flags.unwrap().contains(OFlags::APPEND));
+    }
+
+    #[test]
+    fn kind_of_pipe_file_and_device() {
+        let (read, write) = pipe_pair();
+        assert_eq!(read.kind().unwrap(), FdKind::Pipe);
+        assert_eq!(write.kind().unwrap(), FdKind::Pipe);
+
+        let path = env::temp_dir().join(format!("coreutils_fd_kind_{}", process::id()));
+        let file = File::create(&path).unwrap();
+        let kind = file.kind();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(kind.unwrap(), FdKind::Regular);
+
+        let null = File::open("/dev/null").unwrap();
+        assert_eq!(null.kind().unwrap(), FdKind::CharDevice);
+        assert_eq!(File::open(env::temp_dir()).unwrap().kind().unwrap(), FdKind::Other);
+    }
+
+    #[test]
+    fn duplicate_keeps_the_file() {
+        let copy = FileDescriptor::StdErr.duplicate().unwrap();
+
+        assert!(copy.as_raw_fd() > 2);
+        assert_eq!(copy.kind().unwrap(), FileDescriptor::StdErr.kind().unwrap());
+    }
+}
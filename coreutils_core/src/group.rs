@@ -3,20 +3,26 @@
 #[cfg(target_os = "solaris")]
 use std::os::raw::c_int;
 use std::{
+    collections::{HashMap, HashSet},
     error::Error as StdError,
     ffi::CStr,
     fmt::{self, Display},
-    io::Error as IoError,
+    io::{self, Error as IoError},
     mem::MaybeUninit,
+    ops::RangeInclusive,
     os::raw::c_char,
     ptr,
     slice::Iter,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 #[cfg(target_os = "macos")]
 use std::convert::TryInto;
 
-use libc::{getegid, getgrgid_r, getgrnam_r, getgroups};
+use libc::{
+    endgrent, getegid, getgrent, getgrgid_r, getgrnam_r, getgroups, group, setgid, setgrent,
+    setgroups,
+};
 #[cfg(not(target_os = "solaris"))]
 use libc::{getgrouplist, getpwnam_r};
 #[cfg(target_os = "solaris")]
@@ -25,7 +31,10 @@ use libc::{sysconf, _SC_NGROUPS_MAX};
 use bstr::{BStr, BString, ByteSlice};
 
 use self::Error::*;
-use crate::{passwd::Error as PwError, types::Gid};
+use crate::{
+    passwd::{Error as PwError, Passwd},
+    types::{bstring_to_cstring, Gid},
+};
 
 #[cfg(target_os = "solaris")]
 extern "C" {
@@ -249,9 +258,15 @@ impl Group {
 
         let name = BString::from(name);
 
+        // No entry can have a NUL byte on its name
+        let name_null = match bstring_to_cstring(name.as_bstr()) {
+            Ok(n) => n,
+            Err(_) => return Err(GroupNotFound),
+        };
+
         let res = unsafe {
             getgrnam_r(
-                name.as_ptr() as *const c_char,
+                name_null.as_ptr(),
                 gr.as_mut_ptr(),
                 &mut buff[0],
                 buff.len(),
@@ -319,6 +334,224 @@ impl Group {
     /// Get the `Group` list of members.
     #[inline]
     pub fn mem(&self) -> &Members { &self.mem }
+
+    /// Returns `true` if the user `name` is in the `Group` list of members.
+    ///
+    /// Users that have the `Group` as primary group are usually not on the list, use
+    /// [`user_in_group`] to also check for them.
+    ///
+    /// [`user_in_group`]: ./fn.user_in_group.html
+    #[inline]
+    pub fn contains_user(&self, name: &str) -> bool { self.mem.iter().any(|m| m == name) }
+
+    /// Creates a `Group` from a `libc::group`.
+    fn from_c_group(gr: &group) -> Result<Self> {
+        let name = if gr.gr_name.is_null() {
+            return Err(NameCheckFailed);
+        } else {
+            let name_cstr = unsafe { CStr::from_ptr(gr.gr_name) };
+            BString::from(name_cstr.to_bytes())
+        };
+
+        let passwd = if gr.gr_passwd.is_null() {
+            return Err(PasswdCheckFailed);
+        } else {
+            let passwd_cstr = unsafe { CStr::from_ptr(gr.gr_passwd) };
+            BString::from(passwd_cstr.to_bytes())
+        };
+
+        let mut mem = Members::new();
+        let mut mem_list_ptr = gr.gr_mem;
+        while !mem_list_ptr.is_null() && !unsafe { *mem_list_ptr }.is_null() {
+            let mem_cstr = unsafe { CStr::from_ptr(*mem_list_ptr) };
+            mem.push(BString::from(mem_cstr.to_bytes()));
+            mem_list_ptr = unsafe { mem_list_ptr.add(1) };
+        }
+
+        Ok(Group { name, id: gr.gr_gid, passwd, mem })
+    }
+}
+
+/// Iterator over every group of the group database, in database order, using `getgrent`.
+///
+/// `getgrent` keeps a global cursor, so only one `GroupEntries` should be alive at a time.
+#[derive(Debug)]
+pub struct GroupEntries {
+    _priv: (),
+}
+
+impl GroupEntries {
+    /// Start iterating from the first entry of the group database.
+    pub fn new() -> Self {
+        unsafe { setgrent() };
+        GroupEntries { _priv: () }
+    }
+}
+
+impl Default for GroupEntries {
+    #[inline]
+    fn default() -> Self { Self::new() }
+}
+
+impl Iterator for GroupEntries {
+    type Item = Result<Group>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let gr_ptr = unsafe { getgrent() };
+
+        if gr_ptr.is_null() { None } else { Some(Group::from_c_group(unsafe { &*gr_ptr })) }
+    }
+}
+
+impl Drop for GroupEntries {
+    #[inline]
+    fn drop(&mut self) { unsafe { endgrent() } }
+}
+
+/// Get all groups with a group ID inside of `range`, like the system (usually `0..=999`) or
+/// the user groups.
+///
+/// The groups are in the same order as the group database.
+pub fn groups_in_range(range: RangeInclusive<Gid>) -> Result<Vec<Group>> {
+    filter_range(GroupEntries::new(), &range)
+}
+
+/// Keep the `entries` with a group ID inside of `range`, stopping at the first error.
+fn filter_range(
+    entries: impl IntoIterator<Item = Result<Group>>, range: &RangeInclusive<Gid>,
+) -> Result<Vec<Group>> {
+    entries
+        .into_iter()
+        .filter(|entry| entry.as_ref().map(|gr| range.contains(&gr.id)).unwrap_or(true))
+        .collect()
+}
+
+/// Get the names of the groups with the IDs `gids`, in the same order, going through the
+/// group database only once instead of looking each ID up.
+///
+/// A ID without a group is named by its number, like `id`(1) does.
+pub fn names_for_gids(gids: &[Gid]) -> Result<Vec<BString>> {
+    names_in(GroupEntries::new(), gids)
+}
+
+/// Find the names of `gids` in `entries`, stopping as soon as every one was found.
+fn names_in(
+    entries: impl IntoIterator<Item = Result<Group>>, gids: &[Gid],
+) -> Result<Vec<BString>> {
+    let mut names: HashMap<Gid, BString> = HashMap::with_capacity(gids.len());
+    let mut missing: HashSet<Gid> = gids.iter().cloned().collect();
+
+    if !missing.is_empty() {
+        for entry in entries {
+            let gr = entry?;
+            if missing.remove(&gr.id) {
+                names.insert(gr.id, gr.name);
+                if missing.is_empty() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let names = gids
+        .iter()
+        .map(|gid| names.get(gid).cloned().unwrap_or_else(|| BString::from(gid.to_string())))
+        .collect();
+    Ok(names)
+}
+
+/// Names of the group owning the terminals, in order: `tty` on most systems, `dialout` on
+/// the ones where the terminals belong to it instead.
+const TTY_GROUP_NAMES: [&str; 2] = ["tty", "dialout"];
+
+/// `TTY_GID` value before the group is resolved.
+const GID_UNRESOLVED: u64 = !0;
+/// `TTY_GID` value when there is no group owning the terminals.
+const GID_NONE: u64 = !0 - 1;
+
+/// The cached group ID of the group owning the terminals.
+static TTY_GID: AtomicU64 = AtomicU64::new(GID_UNRESOLVED);
+
+/// Get the group ID of the group owning the terminals, `None` if the system has none.
+///
+/// The group database is only searched on the first call, the later ones reuse its result,
+/// so it can be used for every terminal of a listing.
+pub fn tty_gid() -> Option<Gid> {
+    cached_tty_gid(&TTY_GID, |name| Group::from_name(name).ok().map(|gr| gr.id()))
+}
+
+/// Get the group ID of the group owning the terminals from `cache`, looking it up with
+/// `resolve` and storing it there if it wasn't yet.
+///
+/// Concurrent first calls may all look the group up, but they all store the same result.
+fn cached_tty_gid(cache: &AtomicU64, resolve: impl Fn(&str) -> Option<Gid>) -> Option<Gid> {
+    match cache.load(Ordering::Relaxed) {
+        GID_UNRESOLVED => {},
+        GID_NONE => return None,
+        gid => return Some(gid as Gid),
+    }
+
+    let gid = TTY_GROUP_NAMES.iter().filter_map(|name| resolve(name)).next();
+    cache.store(gid.map(u64::from).unwrap_or(GID_NONE), Ordering::Relaxed);
+
+    gid
+}
+
+/// Check if `user` is a member of `group`, either because `group` is its primary group or
+/// because it is on `group` list of members.
+pub fn user_in_group(user: &str, group: &str) -> Result<bool> {
+    let group = Group::from_name(group)?;
+
+    if group.contains_user(user) {
+        return Ok(true);
+    }
+
+    let passwd = Passwd::from_name(user)?;
+    Ok(passwd.gid() == group.id())
+}
+
+/// Switch the real and effective group IDs of the process to `gid`, like `newgrp`(1) does.
+///
+/// Unless `keep_supplementary`, the supplementary groups are dropped first, leaving `gid` as
+/// the only one, so the process doesn't keep the access of the groups it switched from.
+///
+/// Dropping the supplementary groups, and switching to a group other than the real or saved
+/// group ID, needs the appropriate privileges (being root, or the `CAP_SETGID` capability on
+/// Linux). Without them the error is of the `PermissionDenied` kind, and nothing is changed if
+/// the supplementary groups failed to be dropped.
+pub fn set_process_group(gid: Gid, keep_supplementary: bool) -> io::Result<()> {
+    if !keep_supplementary && unsafe { setgroups(1, &gid) } == -1 {
+        return Err(IoError::last_os_error());
+    }
+
+    if unsafe { setgid(gid) } == -1 {
+        return Err(IoError::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Get the groups of the user of `passwd` like `id -G` lists them: the primary group first,
+/// then the supplementary groups, without repeating a group ID.
+pub fn groups_of_user(passwd: &Passwd) -> Result<Groups> {
+    let groups = passwd.belongs_to()?;
+    let primary = match groups.iter().find(|gr| gr.id() == passwd.gid()) {
+        Some(gr) => gr.clone(),
+        None => Group::from_gid(passwd.gid())?,
+    };
+
+    Ok(Groups { inner: primary_first(primary, groups) })
+}
+
+/// Put `primary` before `groups`, skipping every group with an ID already seen.
+fn primary_first(primary: Group, groups: impl IntoIterator<Item = Group>) -> Vec<Group> {
+    let mut list = vec![primary];
+    for gr in groups {
+        if list.iter().all(|seen| seen.id != gr.id) {
+            list.push(gr);
+        }
+    }
+    list
 }
 
 /// A collection of `Group`.
@@ -498,3 +731,198 @@ impl IntoIterator for Groups {
     #[inline]
     fn into_iter(self) -> Self::IntoIter { self.inner.into_iter() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    use libc::{_exit, fork, geteuid, getgid, waitpid, WEXITSTATUS, WIFEXITED};
+
+    /// Get the supplementary group IDs of the process.
+    fn supplementary_gids() -> Vec<Gid> {
+        let num = unsafe { getgroups(0, ptr::null_mut()) };
+        let mut gids = vec![0; num as usize];
+        let num = unsafe { getgroups(num, gids.as_mut_ptr()) };
+        gids.truncate(num as usize);
+        gids
+    }
+
+    #[test]
+    fn set_process_group_switches_and_restores() {
+        // Only root can drop the supplementary groups and switch to any group
+        if unsafe { geteuid() } != 0 {
+            let err = set_process_group(unsafe { getgid() }, false).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            return;
+        }
+
+        let gid = unsafe { getgid() };
+        let other = if gid == 1 { 2 } else { 1 };
+
+        // The groups are switched in a child, so the other tests keep the groups of the process
+        let child = unsafe { fork() };
+        assert!(child >= 0);
+        if child == 0 {
+            let switched = set_process_group(other, false).is_ok()
+                && (unsafe { getgid() }, unsafe { getegid() }, supplementary_gids())
+                    == (other, other, vec![other]);
+            let restored = set_process_group(gid, true).is_ok() && unsafe { getgid() } == gid;
+            unsafe { _exit(if switched && restored { 0 } else { 1 }) };
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { waitpid(child, &mut status, 0) }, child);
+        assert!(WIFEXITED(status), "the child was killed: {}", status);
+        assert_eq!(WEXITSTATUS(status), 0);
+    }
+
+    #[test]
+    fn contains_user_checks_member_list() {
+        let group = Group {
+            name:   BString::from("wheel"),
+            id:     10,
+            passwd: BString::from("x"),
+            mem:    vec![BString::from("alice"), BString::from("carol")],
+        };
+
+        assert!(group.contains_user("alice"));
+        assert!(!group.contains_user("bob"));
+    }
+
+    #[test]
+    fn user_in_group_counts_primary_group() {
+        let root = Passwd::from_name("root").unwrap();
+        let primary = Group::from_gid(root.gid()).unwrap();
+
+        assert!(user_in_group("root", &primary.name().to_string()).unwrap());
+    }
+
+    #[test]
+    fn filter_range_keeps_database_order() {
+        let group = |name: &str, id| Group {
+            name:   BString::from(name),
+            id,
+            passwd: BString::from("x"),
+            mem:    Members::new(),
+        };
+        let database =
+            vec![group("users", 100), group("wheel", 10), group("root", 0), group("adm", 99)];
+
+        let system = filter_range(database.into_iter().map(Ok), &(0..=99)).unwrap();
+
+        let names: Vec<_> = system.iter().map(|gr| gr.name().to_string()).collect();
+        assert_eq!(names, ["wheel", "root", "adm"]);
+    }
+
+    #[test]
+    fn groups_in_range_contains_root() {
+        let system = groups_in_range(0..=0).unwrap();
+
+        assert!(system.iter().any(|gr| gr.id() == 0));
+        assert!(system.iter().all(|gr| gr.id() == 0));
+    }
+
+    #[test]
+    fn primary_group_listed_once_and_first() {
+        let group = |name: &str, id, mem: &[&str]| Group {
+            name:   BString::from(name),
+            id,
+            passwd: BString::from("x"),
+            mem:    mem.iter().map(|&m| BString::from(m)).collect(),
+        };
+        // The primary group, `staff`, also lists alice explicitly
+        let database = vec![
+            group("wheel", 10, &["alice"]),
+            group("staff", 50, &["alice", "bob"]),
+            group("audio", 63, &["bob"]),
+            group("wheel", 10, &["alice"]),
+        ];
+        let member = database.into_iter().filter(|gr| gr.contains_user("alice"));
+
+        let groups = primary_first(group("staff", 50, &["alice", "bob"]), member);
+
+        let ids: Vec<_> = groups.iter().map(Group::id).collect();
+        assert_eq!(ids, [50, 10]);
+    }
+
+    #[test]
+    fn groups_of_root_start_with_primary() {
+        let root = Passwd::from_name("root").unwrap();
+        let groups = groups_of_user(&root).unwrap().into_vec();
+
+        assert_eq!(groups[0].id(), root.gid());
+        assert!(groups[1..].iter().all(|gr| gr.id() != root.gid()));
+    }
+
+    #[test]
+    fn names_in_keeps_order_in_one_pass() {
+        let group = |name: &str, id| Group {
+            name:   BString::from(name),
+            id,
+            passwd: BString::from("x"),
+            mem:    Members::new(),
+        };
+        let database = [group("root", 0), group("wheel", 10), group("staff", 50)];
+        // Names of `gids` with the number of entries read
+        let counted = |gids: &[Gid]| {
+            let mut pulled = 0;
+            let entries = database.iter().cloned().inspect(|_| pulled += 1).map(Ok);
+            let names = names_in(entries, gids).unwrap();
+            (names, pulled)
+        };
+
+        let (names, pulled) = counted(&[50, 0, 7, 50]);
+        assert_eq!(names, ["staff", "root", "7", "staff"]);
+        assert_eq!(pulled, 3);
+
+        // Every ID was found on the second entry, so the third is never read
+        assert_eq!(counted(&[10, 0]), (vec![BString::from("wheel"), BString::from("root")], 2));
+        assert_eq!(counted(&[]), (vec![], 0));
+    }
+
+    #[test]
+    fn names_for_root_gid() {
+        assert_eq!(names_for_gids(&[0]).unwrap(), [Group::from_gid(0).unwrap().name()]);
+    }
+
+    #[test]
+    fn user_in_group_unknown_group() {
+        assert!(user_in_group("root", "coreutils-no-such-group").is_err());
+    }
+
+    #[test]
+    fn from_name_with_nul_is_not_found() {
+        match Group::from_name("root\0wheel") {
+            Err(GroupNotFound) => {},
+            res => panic!("expected GroupNotFound, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn tty_gid_is_resolved_once() {
+        let lookups = Cell::new(0);
+        let resolve = |name: &str| {
+            lookups.set(lookups.get() + 1);
+            if name == "dialout" { Some(20) } else { None }
+        };
+
+        let cache = AtomicU64::new(GID_UNRESOLVED);
+        assert_eq!(cached_tty_gid(&cache, resolve), Some(20));
+        assert_eq!(lookups.get(), 2);
+        assert_eq!(cached_tty_gid(&cache, resolve), Some(20));
+        assert_eq!(lookups.get(), 2);
+
+        // A missing group is remembered too
+        let cache = AtomicU64::new(GID_UNRESOLVED);
+        assert_eq!(cached_tty_gid(&cache, |_| None), None);
+        assert_eq!(cached_tty_gid(&cache, |_| panic!("resolved again")), None);
+
+        let tty = Group::from_name("tty").ok().map(|gr| gr.id());
+        if tty.is_some() {
+            assert_eq!(tty_gid(), tty);
+            assert_eq!(tty_gid(), tty);
+        }
+    }
+}
@@ -1,9 +1,10 @@
 // Re-exports
 pub use bstr::{self, BStr, BString, ByteSlice, ByteVec, B};
 pub use libc;
-pub use time;
 
 // Agnostic Modules
+pub mod account;
+pub mod cache;
 pub mod consts;
 pub mod env;
 pub mod file_descriptor;
@@ -11,6 +12,9 @@ pub mod group;
 pub mod mkfifo;
 pub mod mktemp;
 pub mod passwd;
+pub mod shadow;
+pub mod signal;
+pub mod time;
 pub mod tty;
 pub mod types;
 pub mod utsname;
@@ -35,3 +39,7 @@ pub mod audit;
 
 #[cfg(target_os = "openbsd")]
 pub mod routing_table;
+
+// Feature Modules
+#[cfg(feature = "completions")]
+pub mod completions;
@@ -1,14 +1,91 @@
-use std::{io, os::raw::c_double};
+//! Module for the system load averages.
+
+#[cfg(target_os = "linux")]
+use std::fs;
+use std::{
+    fmt::{self, Display},
+    io,
+    os::raw::c_double,
+};
 
 use libc::getloadavg;
 
-pub fn load_average() -> io::Result<[c_double; 3]> {
+/// The number of jobs in the run queue averaged over the last 1, 5 and 15 minutes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct LoadAvg {
+    /// Average over the last minute.
+    pub one:     f64,
+    /// Average over the last 5 minutes.
+    pub five:    f64,
+    /// Average over the last 15 minutes.
+    pub fifteen: f64,
+}
+
+impl Display for LoadAvg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.2}, {:.2}, {:.2}", self.one, self.five, self.fifteen)
+    }
+}
+
+/// Get the system load averages.
+///
+/// On Linux, `/proc/loadavg` is read if `getloadavg` fails.
+pub fn load_average() -> io::Result<LoadAvg> {
     let mut avg: [c_double; 3] = [0.0; 3];
     let res = unsafe { getloadavg(avg.as_mut_ptr(), 3) };
 
-    if res == -1 {
-        return Err(io::Error::last_os_error());
+    if res < 3 {
+        // getloadavg(3) doesn't set errno, so the error is the one of the fallback
+        #[cfg(target_os = "linux")]
+        {
+            let contents = fs::read_to_string("/proc/loadavg")?;
+            return parse_proc_loadavg(&contents).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "malformed /proc/loadavg")
+            });
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        return Err(io::Error::new(io::ErrorKind::Other, "load average unavailable"));
+    }
+
+    Ok(LoadAvg { one: avg[0], five: avg[1], fifteen: avg[2] })
+}
+
+/// Parse the first three fields of `/proc/loadavg`.
+#[cfg(target_os = "linux")]
+fn parse_proc_loadavg(contents: &str) -> Option<LoadAvg> {
+    let mut fields = contents.split_whitespace().map(str::parse);
+    let mut next = || fields.next()?.ok();
+
+    Some(LoadAvg { one: next()?, five: next()?, fifteen: next()? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_two_decimals() {
+        let load = LoadAvg { one: 0.0, five: 1.005, fifteen: 12.5 };
+        assert_eq!(load.to_string(), "0.00, 1.00, 12.50");
+
+        let load = LoadAvg { one: 0.126, five: 3.0, fifteen: 100.999 };
+        assert_eq!(load.to_string(), "0.13, 3.00, 101.00");
     }
 
-    Ok(avg)
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn parse_proc_loadavg_fields() {
+        let load = parse_proc_loadavg("0.52 0.58 0.59 2/1017 12345\n").unwrap();
+        assert_eq!(load, LoadAvg { one: 0.52, five: 0.58, fifteen: 0.59 });
+
+        assert_eq!(parse_proc_loadavg("0.52 0.58"), None);
+        assert_eq!(parse_proc_loadavg("a b c"), None);
+    }
+
+    #[test]
+    fn load_average_is_not_negative() {
+        let load = load_average().unwrap();
+        assert!(load.one >= 0.0 && load.five >= 0.0 && load.fifteen >= 0.0);
+    }
 }
@@ -4,9 +4,13 @@ use std::{
     error::Error as StdError,
     ffi::CStr,
     fmt::{self, Display},
+    fs,
+    io::Error as IoError,
     mem::MaybeUninit,
-    os::raw::c_char,
+    ops::RangeInclusive,
+    path::Path,
     ptr,
+    str,
 };
 
 #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
@@ -19,13 +23,13 @@ use crate::types::Fields;
 )))]
 use crate::types::Time;
 use crate::{
-    group::{Error as GrError, Groups},
-    types::{Gid, Uid},
+    group::{Error as GrError, Group, Groups},
+    types::{bstring_to_cstring, Gid, Uid},
 };
 
 use self::Error::*;
 
-use libc::{geteuid, getpwnam_r, getpwuid_r, getuid};
+use libc::{endpwent, geteuid, getpwent, getpwnam_r, getpwuid_r, getuid, passwd, setpwent};
 
 use bstr::{BStr, BString, ByteSlice};
 
@@ -56,6 +60,16 @@ pub enum Error {
     PasswdNotFound,
     /// Happens when something happens when finding what `Group` a `Passwd` belongs
     Group(Box<GrError>),
+    /// Happens when the primary group ID of a `Passwd` has no group.
+    ///
+    /// It holds the group ID.
+    PrimaryGroupNotFound(Gid),
+    /// Happens when a line of a passwd file is not in the `/etc/passwd` format.
+    ///
+    /// It holds the number of the line, starting at 1.
+    Malformed { line_number: usize },
+    /// Happens when a passwd file can't be read.
+    Io(IoError),
 }
 
 impl Display for Error {
@@ -74,6 +88,9 @@ impl Display for Error {
             ClassCheckFailed => write!(f, "Passwd class check failed, `.pw_class` is null"),
             PasswdNotFound => write!(f, "Passwd was not found in the system"),
             Group(err) => write!(f, "The following error hapenned trying to get all `Groups`: {}", err),
+            PrimaryGroupNotFound(gid) => write!(f, "Primary group {} has no group entry", gid),
+            Malformed { line_number } => write!(f, "Malformed passwd entry at line {}", line_number),
+            Io(err) => write!(f, "Failed to read passwd file: {}", err),
         }
     }
 }
@@ -83,6 +100,7 @@ impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
             Group(err) => Some(err),
+            Io(err) => Some(err),
             _ => None,
         }
     }
@@ -95,6 +113,40 @@ impl From<GrError> for Error {
     }
 }
 
+impl From<IoError> for Error {
+    #[inline]
+    fn from(err: IoError) -> Error {
+        Io(err)
+    }
+}
+
+/// A NIS compat line of a passwd file, starting with `+` or `-`.
+///
+/// Both hold the first field of the line without its sign: a user name, a netgroup name
+/// starting with `@`, or nothing, meaning every NIS user.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PasswdLine {
+    /// A `+` line, including the NIS users it names (e.g. `+@netgroup`, `+user` or `+`).
+    Netgroup(BString),
+    /// A `-` line, excluding the NIS users it names (e.g. `-@netgroup` or `-user`).
+    Exclude(BString),
+}
+
+impl PasswdLine {
+    /// Parse a line of a passwd file, `None` if it is not a NIS compat line.
+    fn parse(line: &[u8]) -> Option<Self> {
+        let end = line.find_byte(b':').unwrap_or(line.len());
+        match line.first() {
+            Some(b'+') => Some(PasswdLine::Netgroup(BString::from(&line[1..end]))),
+            Some(b'-') => Some(PasswdLine::Exclude(BString::from(&line[1..end]))),
+            _ => None,
+        }
+    }
+}
+
+/// Shell used for the users with an empty shell field.
+pub const DEFAULT_SHELL: &str = "/bin/sh";
+
 /// This struct holds the information of a user in UNIX/UNIX-like systems.
 ///
 /// Contains `sys/types.h` `passwd` struct attributes as Rust more common types.
@@ -546,17 +598,17 @@ impl Passwd {
         let mut pw_ptr = ptr::null_mut();
         let mut buff = [0; 16384]; // Got this size from manual page about getpwuid_r
 
-        let name_null = {
-            let mut n = BString::from(name);
-            n.push(b'\0');
-            n
-        };
-
         let name = BString::from(name);
 
+        // No entry can have a NUL byte on its name
+        let name_null = match bstring_to_cstring(name.as_bstr()) {
+            Ok(n) => n,
+            Err(_) => return Err(PasswdNotFound),
+        };
+
         let res = unsafe {
             getpwnam_r(
-                name_null.as_ptr() as *const c_char,
+                name_null.as_ptr(),
                 pw.as_mut_ptr(),
                 &mut buff[0],
                 buff.len(),
@@ -678,6 +730,227 @@ impl Passwd {
         })
     }
 
+    /// Get every `Passwd` of the passwd file at `path`, in the `/etc/passwd` format, instead
+    /// of the system database. Useful inside of a chroot or for testing.
+    ///
+    /// Empty lines and NIS compat lines (starting with `+` or `-`) are ignored and the shell
+    /// field may be missing, in which case it is empty. Fields not present in that format are
+    /// left empty or zeroed.
+    ///
+    /// It may fail, so return a `Result`, either the `Vec` of `Passwd` wrapped in a `Ok`, or
+    /// a `Error` wrapped in a `Err`, with `Malformed` for the first invalid line.
+    pub fn from_file(path: &Path) -> Result<Vec<Self>> {
+        Self::from_file_with_nis(path).map(|(list, _)| list)
+    }
+
+    /// Same as [`from_file`], but also get the NIS compat lines of the file, in the order they
+    /// appear on it.
+    ///
+    /// [`from_file`]: #method.from_file
+    pub fn from_file_with_nis(path: &Path) -> Result<(Vec<Self>, Vec<PasswdLine>)> {
+        let contents = fs::read(path)?;
+        let mut list = Vec::new();
+        let mut nis_lines = Vec::new();
+
+        for (num, line) in contents.lines().enumerate().filter(|(_, line)| !line.is_empty()) {
+            match PasswdLine::parse(line) {
+                Some(nis_line) => nis_lines.push(nis_line),
+                None => list.push(Self::from_line(line).ok_or(Malformed { line_number: num + 1 })?),
+            }
+        }
+
+        Ok((list, nis_lines))
+    }
+
+    /// Create a new `Passwd` using a `name` to find it on the passwd file at `path`.
+    ///
+    /// It may fail, so return a `Result`, either the `Passwd` struct wrapped in a `Ok`, or
+    /// a `Error` wrapped in a `Err`.
+    pub fn from_name_in_file(path: &Path, name: &str) -> Result<Self> {
+        Self::from_file(path)?.into_iter().find(|pw| pw.name == name).ok_or(PasswdNotFound)
+    }
+
+    /// Parse a line in the `/etc/passwd` format, `None` if it is not valid.
+    fn from_line(line: &[u8]) -> Option<Self> {
+        let mut fields: Vec<_> = line.split_str(":").collect();
+        match fields.len() {
+            6 => fields.push(b""),
+            7 => {},
+            _ => return None,
+        }
+
+        let parse_id = |field: &[u8]| str::from_utf8(field).ok()?.parse().ok();
+
+        if fields[0].is_empty() {
+            return None;
+        }
+
+        Some(Passwd {
+            name: BString::from(fields[0]),
+            passwd: BString::from(fields[1]),
+            user_id: parse_id(fields[2])?,
+            group_id: parse_id(fields[3])?,
+            gecos: BString::from(fields[4]),
+            dir: BString::from(fields[5]),
+            shell: BString::from(fields[6]),
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "haiku",
+                target_os = "fuchsia",
+                target_os = "solaris"
+            )))]
+            change: 0,
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "haiku",
+                target_os = "fuchsia",
+                target_os = "solaris"
+            )))]
+            class: BString::from(""),
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "haiku",
+                target_os = "fuchsia",
+                target_os = "solaris"
+            )))]
+            expire: 0,
+            #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+            fields: 0,
+        })
+    }
+
+    /// Get every `Passwd` that has the user ID `uid`, in the order they appear on the passwd
+    /// database.
+    ///
+    /// Some systems have more than one user sharing a user ID (e.g. `root` and `toor`), where
+    /// [`from_uid`] only gets the first one.
+    ///
+    /// It may fail, so return a `Result`, either the `Vec` of `Passwd` wrapped in a `Ok`, or
+    /// a `Error` wrapped in a `Err`.
+    ///
+    /// [`from_uid`]: #method.from_uid
+    pub fn all_for_uid(uid: Uid) -> Result<Vec<Self>> {
+        unsafe { setpwent() };
+        let res = Self::collect_uid(|| unsafe { getpwent() }, uid);
+        unsafe { endpwent() };
+
+        res
+    }
+
+    /// Collect every entry with the user ID `uid` given by `next_entry`, until it returns a
+    /// null pointer.
+    fn collect_uid(next_entry: impl FnMut() -> *mut passwd, uid: Uid) -> Result<Vec<Self>> {
+        let list = Self::collect_where(next_entry, |id| id == uid)?;
+
+        if list.is_empty() { Err(PasswdNotFound) } else { Ok(list) }
+    }
+
+    /// Collect every entry whose user ID passes `keep` given by `next_entry`, until it
+    /// returns a null pointer.
+    fn collect_where(
+        mut next_entry: impl FnMut() -> *mut passwd, keep: impl Fn(Uid) -> bool,
+    ) -> Result<Vec<Self>> {
+        let mut list = Vec::new();
+
+        loop {
+            let pw_ptr = next_entry();
+            if pw_ptr.is_null() {
+                break;
+            }
+
+            let pw = unsafe { &*pw_ptr };
+            if keep(pw.pw_uid) {
+                list.push(Self::from_c_passwd(pw)?);
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// Create a new `Passwd` from a `libc::passwd`.
+    fn from_c_passwd(pw: &passwd) -> Result<Self> {
+        let name = if pw.pw_name.is_null() {
+            return Err(NameCheckFailed);
+        } else {
+            let name_cstr = unsafe { CStr::from_ptr(pw.pw_name) };
+            BString::from(name_cstr.to_bytes())
+        };
+
+        let passwd = if pw.pw_passwd.is_null() {
+            return Err(PasswdCheckFailed);
+        } else {
+            let passwd_cstr = unsafe { CStr::from_ptr(pw.pw_passwd) };
+            BString::from(passwd_cstr.to_bytes())
+        };
+
+        let gecos = if pw.pw_gecos.is_null() {
+            return Err(GecosCheckFailed);
+        } else {
+            let gecos_cstr = unsafe { CStr::from_ptr(pw.pw_gecos) };
+            BString::from(gecos_cstr.to_bytes())
+        };
+
+        let dir = if pw.pw_dir.is_null() {
+            return Err(DirCheckFailed);
+        } else {
+            let dir_cstr = unsafe { CStr::from_ptr(pw.pw_dir) };
+            BString::from(dir_cstr.to_bytes())
+        };
+
+        let shell = if pw.pw_shell.is_null() {
+            return Err(ShellCheckFailed);
+        } else {
+            let shell_cstr = unsafe { CStr::from_ptr(pw.pw_shell) };
+            BString::from(shell_cstr.to_bytes())
+        };
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "haiku",
+            target_os = "fuchsia",
+            target_os = "solaris"
+        )))]
+        let class = if pw.pw_class.is_null() {
+            return Err(ClassCheckFailed);
+        } else {
+            let class_cstr = unsafe { CStr::from_ptr(pw.pw_class) };
+            BString::from(class_cstr.to_bytes())
+        };
+
+        Ok(Passwd {
+            name,
+            passwd,
+            user_id: pw.pw_uid,
+            group_id: pw.pw_gid,
+            gecos,
+            dir,
+            shell,
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "haiku",
+                target_os = "fuchsia",
+                target_os = "solaris"
+            )))]
+            change: pw.pw_change,
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "haiku",
+                target_os = "fuchsia",
+                target_os = "solaris"
+            )))]
+            class,
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "haiku",
+                target_os = "fuchsia",
+                target_os = "solaris"
+            )))]
+            expire: pw.pw_expire,
+            #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+            fields: pw.pw_fields,
+        })
+    }
+
     /// Get `Passwd` login name.
     #[inline]
     pub fn name(&self) -> &BStr {
@@ -720,6 +993,17 @@ impl Passwd {
         self.shell.as_bstr()
     }
 
+    /// Get the shell to run for the user: `Passwd` shell, or `/bin/sh` if it's empty, as POSIX
+    /// says for login.
+    #[inline]
+    pub fn login_shell(&self) -> BString {
+        if self.shell.is_empty() {
+            BString::from(DEFAULT_SHELL)
+        } else {
+            self.shell.clone()
+        }
+    }
+
     /// Get `Passwd` access class.
     #[inline]
     #[cfg(not(any(
@@ -773,6 +1057,17 @@ impl Passwd {
         let gr = Groups::from_username(&name)?;
         Ok(gr)
     }
+
+    /// Get the primary group of `Passwd`, the group of its group ID.
+    ///
+    /// Fails with `PrimaryGroupNotFound` if no group has that ID.
+    pub fn primary_group(&self) -> Result<Group> {
+        match Group::from_gid(self.gid()) {
+            Ok(group) => Ok(group),
+            Err(GrError::GroupNotFound) => Err(PrimaryGroupNotFound(self.gid())),
+            Err(err) => Err(err.into()),
+        }
+    }
 }
 
 impl Display for Passwd {
@@ -813,3 +1108,455 @@ impl Display for Passwd {
         )
     }
 }
+
+/// The comma separated sub-fields of the passwd GECOS field, as used by `finger`(1) and
+/// `chfn`(1).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Gecos {
+    /// User full name, a `&` stands for the capitalized login name.
+    full_name: BString,
+    /// User office location.
+    office: BString,
+    /// User office phone.
+    office_phone: BString,
+    /// User home phone.
+    home_phone: BString,
+    /// Anything after the home phone.
+    other: BString,
+}
+
+impl Gecos {
+    /// Split the `gecos` field into its sub-fields. Missing sub-fields are left empty.
+    pub fn parse(gecos: &BStr) -> Self {
+        let mut fields = gecos.splitn_str(5, ",").map(BString::from);
+        let mut next = || fields.next().unwrap_or_default();
+
+        Gecos {
+            full_name: next(),
+            office: next(),
+            office_phone: next(),
+            home_phone: next(),
+            other: next(),
+        }
+    }
+
+    /// Get the full name, with every `&` replaced by `login` with its first letter
+    /// capitalized.
+    pub fn full_name(&self, login: &BStr) -> BString {
+        let mut login = BString::from(login.as_bytes());
+        if let Some(first) = login.first_mut() {
+            first.make_ascii_uppercase();
+        }
+
+        self.full_name.replace("&", &login).into()
+    }
+
+    /// Get the office location.
+    #[inline]
+    pub fn office(&self) -> &BStr {
+        self.office.as_bstr()
+    }
+
+    /// Get the office phone.
+    #[inline]
+    pub fn office_phone(&self) -> &BStr {
+        self.office_phone.as_bstr()
+    }
+
+    /// Get the home phone.
+    #[inline]
+    pub fn home_phone(&self) -> &BStr {
+        self.home_phone.as_bstr()
+    }
+
+    /// Get whatever comes after the home phone.
+    #[inline]
+    pub fn other(&self) -> &BStr {
+        self.other.as_bstr()
+    }
+}
+
+/// Get the `Passwd` of the user `spec`, a login name or, if there is no user with that name
+/// and it is all digits, a user ID, with the same precedence as `chown`(1) and `id`(1).
+pub fn resolve(spec: &str) -> Result<Passwd> {
+    resolve_with(spec, Passwd::from_name, Passwd::from_uid)
+}
+
+/// Resolve `spec` like [`resolve`], looking the users up with `by_name` and `by_uid`.
+///
+/// [`resolve`]: fn.resolve.html
+fn resolve_with(
+    spec: &str, by_name: impl Fn(&str) -> Result<Passwd>, by_uid: impl Fn(Uid) -> Result<Passwd>,
+) -> Result<Passwd> {
+    match by_name(spec) {
+        Err(PasswdNotFound) if !spec.is_empty() && spec.bytes().all(|b| b.is_ascii_digit()) => {
+            match spec.parse() {
+                Ok(uid) => by_uid(uid),
+                // Too big to be a user ID
+                Err(_) => Err(PasswdNotFound),
+            }
+        },
+        res => res,
+    }
+}
+
+/// Get every `Passwd` with a user ID in `range`, in the order they appear on the passwd
+/// database, like the human users with `1000..=Uid::max_value()`.
+///
+/// A range without users is not an error, the `Vec` is just empty.
+pub fn in_uid_range(range: RangeInclusive<Uid>) -> Result<Vec<Passwd>> {
+    unsafe { setpwent() };
+    let res = Passwd::collect_where(|| unsafe { getpwent() }, |uid| range.contains(&uid));
+    unsafe { endpwent() };
+
+    res
+}
+
+/// Render the information of `p` the way `finger -l` does, one line per pair of fields.
+///
+/// The office and phone lines are only present if the GECOS field has them.
+pub fn format_finger(p: &Passwd) -> String {
+    let gecos = Gecos::parse(p.gecos());
+
+    let mut lines = vec![
+        format!("{:<40}Name: {}", format!("Login: {}", p.name()), gecos.full_name(p.name())),
+        format!("{:<40}Shell: {}", format!("Directory: {}", p.dir()), p.shell()),
+    ];
+
+    let office = match (gecos.office().is_empty(), gecos.office_phone().is_empty()) {
+        (true, true) => String::new(),
+        (false, true) => format!("Office: {}", gecos.office()),
+        (true, false) => format!("Office Phone: {}", gecos.office_phone()),
+        (false, false) => format!("Office: {}, {}", gecos.office(), gecos.office_phone()),
+    };
+
+    if gecos.home_phone().is_empty() {
+        if !office.is_empty() {
+            lines.push(office);
+        }
+    } else {
+        lines.push(format!("{:<40}Home Phone: {}", office, gecos.home_phone()));
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Expand a leading `~` of `path` to the home directory of the current user, and a leading
+/// `~name` to the home directory of the user `name`.
+///
+/// The path is returned untouched if the user can't be found.
+pub fn expand_tilde(path: &BStr) -> BString {
+    if !path.starts_with(b"~") {
+        return path.to_owned();
+    }
+
+    let (user, rest) = path.split_at(path.find_byte(b'/').unwrap_or_else(|| path.len()));
+    let passwd = match user[1..].to_str() {
+        Ok("") => Passwd::from_uid(unsafe { geteuid() }),
+        Ok(name) => Passwd::from_name(name),
+        Err(_) => return path.to_owned(),
+    };
+
+    match passwd {
+        Ok(pw) => {
+            let mut expanded = pw.dir().to_vec();
+            expanded.extend_from_slice(rest);
+            BString::from(expanded)
+        },
+        Err(_) => path.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env,
+        ffi::CString,
+        fs::{self, File},
+        io::Write,
+        mem,
+        os::raw::c_char,
+        process,
+    };
+
+    use bstr::B;
+
+    fn fixture_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = env::temp_dir().join(format!("coreutils_passwd_{}_{}", name, process::id()));
+        File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    /// Entries of a fake passwd database with the user IDs `uids`, pointing to the strings
+    /// `names` and `empty`, that must outlive them.
+    fn c_entries(names: &[CString], uids: &[Uid], empty: &CString) -> Vec<passwd> {
+        names
+            .iter()
+            .zip(uids.iter())
+            .map(|(name, uid)| {
+                let mut pw: passwd = unsafe { mem::zeroed() };
+                pw.pw_name = name.as_ptr() as *mut c_char;
+                pw.pw_passwd = empty.as_ptr() as *mut c_char;
+                pw.pw_uid = *uid;
+                pw.pw_gecos = empty.as_ptr() as *mut c_char;
+                pw.pw_dir = empty.as_ptr() as *mut c_char;
+                pw.pw_shell = empty.as_ptr() as *mut c_char;
+                #[cfg(not(any(
+                    target_os = "linux",
+                    target_os = "haiku",
+                    target_os = "fuchsia",
+                    target_os = "solaris"
+                )))]
+                {
+                    pw.pw_class = empty.as_ptr() as *mut c_char;
+                }
+                pw
+            })
+            .collect()
+    }
+
+    #[test]
+    fn collect_uid_keeps_every_name() {
+        let names = ["root", "toor", "daemon"];
+        let name_cstrs: Vec<_> = names.iter().map(|n| CString::new(*n).unwrap()).collect();
+        let empty = CString::new("").unwrap();
+        let mut entries = c_entries(&name_cstrs, &[0, 0, 1], &empty);
+
+        let mut database = |uid| {
+            let mut iter = entries.iter_mut();
+            Passwd::collect_uid(
+                || iter.next().map(|pw| pw as *mut passwd).unwrap_or_else(ptr::null_mut),
+                uid,
+            )
+        };
+
+        let roots: Vec<_> = database(0).unwrap().iter().map(|pw| pw.name().to_string()).collect();
+        assert_eq!(roots, ["root", "toor"]);
+
+        let daemons = database(1).unwrap();
+        assert_eq!(daemons.len(), 1);
+        assert_eq!(daemons[0].name(), "daemon");
+
+        assert!(database(2).is_err());
+    }
+
+    #[test]
+    fn collect_where_filters_uid_range() {
+        let names = ["root", "daemon", "systemd", "alice", "bob", "nobody"];
+        let name_cstrs: Vec<_> = names.iter().map(|n| CString::new(*n).unwrap()).collect();
+        let empty = CString::new("").unwrap();
+        let mut entries = c_entries(&name_cstrs, &[0, 1, 999, 1000, 1001, 65534], &empty);
+
+        let mut database = |range: RangeInclusive<Uid>| {
+            let mut iter = entries.iter_mut();
+            let list = Passwd::collect_where(
+                || iter.next().map(|pw| pw as *mut passwd).unwrap_or_else(ptr::null_mut),
+                |uid| range.contains(&uid),
+            );
+            list.unwrap().iter().map(|pw| pw.name().to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(database(0..=999), ["root", "daemon", "systemd"]);
+        assert_eq!(database(1000..=60000), ["alice", "bob"]);
+        assert_eq!(database(2000..=3000), Vec::<String>::new());
+
+        let system = in_uid_range(0..=0).unwrap();
+        assert!(system.iter().any(|pw| pw.name() == "root"));
+    }
+
+    #[test]
+    fn primary_group_of_root_and_orphaned_gid() {
+        let root = Passwd::from_uid(0).unwrap();
+        assert_eq!(root.primary_group().unwrap().id(), root.gid());
+
+        let path = fixture_file("orphan", "orphan:x:4000:3999999999::/nonexistent:\n");
+        let list = Passwd::from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        match list.unwrap()[0].primary_group() {
+            Err(PrimaryGroupNotFound(gid)) => assert_eq!(gid, 3_999_999_999),
+            res => panic!("expected PrimaryGroupNotFound, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn login_shell_falls_back_to_sh() {
+        let path = fixture_file(
+            "shells",
+            "alice:x:1000:1000::/home/alice:/usr/bin/zsh\nbob:x:1001:1001::/home/bob:\n",
+        );
+        let list = Passwd::from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let list = list.unwrap();
+        assert_eq!(list[0].login_shell(), "/usr/bin/zsh");
+        assert_eq!(list[1].shell(), "");
+        assert_eq!(list[1].login_shell(), "/bin/sh");
+    }
+
+    #[test]
+    fn resolve_prefers_numeric_names() {
+        let path = fixture_file(
+            "numeric",
+            "root:x:0:0::/root:/bin/sh\n12345:x:1000:1000::/home/numeric:/bin/sh\n\
+             alice:x:12345:12345::/home/alice:/bin/sh\nbob:x:2000:2000::/home/bob:/bin/sh\n",
+        );
+        let list = Passwd::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        let list = list.unwrap();
+
+        let resolve = |spec: &str| {
+            let by_name = |name: &str| {
+                list.iter().find(|pw| pw.name() == name).cloned().ok_or(PasswdNotFound)
+            };
+            let by_uid =
+                |uid: Uid| list.iter().find(|pw| pw.uid() == uid).cloned().ok_or(PasswdNotFound);
+            resolve_with(spec, by_name, by_uid).map(|pw| pw.name().to_string())
+        };
+
+        // The login name `12345` wins over the user ID 12345 of alice
+        assert_eq!(resolve("12345").unwrap(), "12345");
+        assert_eq!(resolve("2000").unwrap(), "bob");
+        assert_eq!(resolve("alice").unwrap(), "alice");
+        assert!(resolve("3000").is_err());
+        assert!(resolve("+2000").is_err());
+        assert!(resolve("99999999999999999999").is_err());
+
+        // Only a user ID in the system database
+        assert_eq!(super::resolve("0").unwrap().name(), "root");
+    }
+
+    #[test]
+    fn from_file_parses_every_line() {
+        let path = fixture_file(
+            "valid",
+            "root:x:0:0:root:/root:/bin/sh\n\nnobody:*:65534:65534:Nobody:/nonexistent:\nlegacy:x:1000:100:Legacy User:/home/legacy\n",
+        );
+        let list = Passwd::from_file(&path);
+        let legacy = Passwd::from_name_in_file(&path, "legacy");
+        let missing = Passwd::from_name_in_file(&path, "alice");
+        fs::remove_file(&path).unwrap();
+
+        let list = list.unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].name(), "root");
+        assert_eq!(list[0].shell(), "/bin/sh");
+        assert_eq!(list[1].uid(), 65534);
+        assert_eq!(list[1].shell(), "");
+
+        assert_eq!(list[1].login_shell(), DEFAULT_SHELL);
+
+        let legacy = legacy.unwrap();
+        assert_eq!(legacy.gid(), 100);
+        assert_eq!(legacy.gecos(), "Legacy User");
+        assert_eq!(legacy.dir(), "/home/legacy");
+        assert_eq!(legacy.shell(), "");
+        assert_eq!(legacy.login_shell(), "/bin/sh");
+
+        match missing {
+            Err(PasswdNotFound) => {},
+            res => panic!("expected PasswdNotFound, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn from_file_reports_malformed_line() {
+        let path = fixture_file(
+            "malformed",
+            "root:x:0:0:root:/root:/bin/sh\ndaemon:x:1:1\nbad:x:uid:1::/:/bin/sh\n",
+        );
+        let res = Passwd::from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        match res {
+            Err(Malformed { line_number: 2 }) => {},
+            res => panic!("expected Malformed at line 2, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn from_file_classifies_nis_lines() {
+        let path = fixture_file(
+            "nis",
+            "root:x:0:0:root:/root:/bin/sh\n-mallory\n+@staff::::::\n-@guests:x:::::\n+\n",
+        );
+        let list = Passwd::from_file(&path);
+        let with_nis = Passwd::from_file_with_nis(&path);
+        fs::remove_file(&path).unwrap();
+
+        let list = list.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name(), "root");
+
+        let (with_nis_list, nis_lines) = with_nis.unwrap();
+        assert_eq!(with_nis_list, list);
+        assert_eq!(nis_lines, [
+            PasswdLine::Exclude(BString::from("mallory")),
+            PasswdLine::Netgroup(BString::from("@staff")),
+            PasswdLine::Exclude(BString::from("@guests")),
+            PasswdLine::Netgroup(BString::from("")),
+        ]);
+    }
+
+    #[test]
+    fn all_for_uid_contains_from_uid() {
+        let root = Passwd::from_uid(0).unwrap();
+
+        assert!(Passwd::all_for_uid(0).unwrap().contains(&root));
+    }
+
+    #[test]
+    fn gecos_parse_fills_missing_fields() {
+        let gecos = Gecos::parse(B("& Smith,Room 101").as_bstr());
+
+        assert_eq!(gecos.full_name(B("alice").as_bstr()), "Alice Smith");
+        assert_eq!(gecos.office(), "Room 101");
+        assert_eq!(gecos.office_phone(), "");
+        assert_eq!(gecos.home_phone(), "");
+        assert_eq!(gecos.other(), "");
+
+        let gecos = Gecos::parse(B("Bob,,x5555,555-0199,vip,extra").as_bstr());
+        assert_eq!(gecos.home_phone(), "555-0199");
+        assert_eq!(gecos.other(), "vip,extra");
+    }
+
+    #[test]
+    fn format_finger_matches_fixture() {
+        let path = fixture_file(
+            "finger",
+            "alice:x:1000:1000:& Smith,Room 101,x5555,555-0199:/home/alice:/bin/zsh\nbob:x:1001:1001:Bob:/home/bob:/bin/sh\n",
+        );
+        let list = Passwd::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        let list = list.unwrap();
+
+        assert_eq!(
+            format_finger(&list[0]),
+            "Login: alice                            Name: Alice Smith\n\
+             Directory: /home/alice                  Shell: /bin/zsh\n\
+             Office: Room 101, x5555                 Home Phone: 555-0199\n"
+        );
+        assert_eq!(
+            format_finger(&list[1]),
+            "Login: bob                              Name: Bob\n\
+             Directory: /home/bob                    Shell: /bin/sh\n"
+        );
+    }
+
+    #[test]
+    fn expand_tilde_homes() {
+        let current = Passwd::from_uid(unsafe { geteuid() }).unwrap();
+        let root = Passwd::from_name("root").unwrap();
+
+        assert_eq!(expand_tilde(B("~").as_bstr()), current.dir());
+        assert_eq!(expand_tilde(B("~/notes").as_bstr()), format!("{}/notes", current.dir()));
+        assert_eq!(expand_tilde(B("~root").as_bstr()), root.dir());
+        assert_eq!(expand_tilde(B("~root/bin").as_bstr()), format!("{}/bin", root.dir()));
+        assert_eq!(expand_tilde(B("~nonexistent/x").as_bstr()), "~nonexistent/x");
+        assert_eq!(expand_tilde(B("/tmp/~root").as_bstr()), "/tmp/~root");
+    }
+}
@@ -6,6 +6,12 @@ use std::{
     io::Error as IOError,
     os::raw::c_int,
 };
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::{fs, io::ErrorKind};
+#[cfg(target_os = "freebsd")]
+use std::{mem, ptr};
+
+use crate::types::Pid;
 
 pub use libc::PRIO_PROCESS;
 use libc::{getpriority, setpriority};
@@ -16,11 +22,27 @@ use libc::id_t;
 #[cfg(target_os = "linux")]
 use libc::c_uint;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use libc::__errno_location as errno_location;
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+use libc::__error as errno_location;
+#[cfg(any(target_os = "openbsd", target_os = "netbsd"))]
+use libc::__errno as errno_location;
+#[cfg(any(target_os = "solaris", target_os = "illumos"))]
+use libc::___errno as errno_location;
+#[cfg(target_os = "haiku")]
+use libc::_errnop as errno_location;
+
+pub mod io;
+
 /// Possible errors
 #[derive(Debug)]
 pub enum Error {
     SetPriority(IOError),
     GetPriority(IOError),
+    SetIoPriority(IOError),
+    GetIoPriority(IOError),
+    IoPriorityUnsupported,
 }
 
 impl Display for Error {
@@ -32,6 +54,15 @@ impl Display for Error {
             Self::GetPriority(ioerr) => {
                 write!(f, "getpriority: failed to get priority with error: {}", ioerr)
             },
+            Self::SetIoPriority(ioerr) => {
+                write!(f, "ioprio_set: failed to set I/O priority with error: {}", ioerr)
+            },
+            Self::GetIoPriority(ioerr) => {
+                write!(f, "ioprio_get: failed to get I/O priority with error: {}", ioerr)
+            },
+            Self::IoPriorityUnsupported => {
+                write!(f, "I/O priority is not supported on this platform")
+            },
         }
     }
 }
@@ -42,6 +73,9 @@ impl StdError for Error {
         match self {
             Self::SetPriority(ioerr) => Some(ioerr),
             Self::GetPriority(ioerr) => Some(ioerr),
+            Self::SetIoPriority(ioerr) => Some(ioerr),
+            Self::GetIoPriority(ioerr) => Some(ioerr),
+            Self::IoPriorityUnsupported => None,
         }
     }
 }
@@ -50,45 +84,74 @@ impl StdError for Error {
 /// the specified processes if successful.
 #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
 pub fn get_priority(which: c_int, who: c_int) -> Result<c_int, Error> {
+    set_errno(0);
     let res = unsafe { getpriority(which, who) };
 
-    if IOError::last_os_error().raw_os_error().unwrap() != 0 {
-        return Err(Error::GetPriority(IOError::last_os_error()));
-    }
-
-    Ok(res)
+    check_priority(res)
 }
 
 /// This function returns the highest priority (lowest numerical value) enjoyed by any of
 /// the specified processes if successful.
 #[cfg(not(any(target_os = "freebsd", target_os = "dragonfly", target_os = "linux")))]
 pub fn get_priority(which: c_int, who: id_t) -> Result<c_int, Error> {
+    set_errno(0);
     let res = unsafe { getpriority(which, who) };
 
-    if IOError::last_os_error().raw_os_error().unwrap() != 0 {
-        return Err(Error::GetPriority(IOError::last_os_error()));
-    }
-
-    Ok(res)
+    check_priority(res)
 }
 
 /// Get the highest priority (lowest numerical value) enjoyed by any of
 /// the specified processes.
 #[cfg(target_os = "linux")]
 pub fn get_priority(which: c_uint, who: id_t) -> Result<c_int, Error> {
+    set_errno(0);
     #[cfg(target_env = "musl")]
     let res = unsafe { getpriority(which as c_int, who) };
 
     #[cfg(not(target_env = "musl"))]
     let res = unsafe { getpriority(which, who) };
 
-    if IOError::last_os_error().raw_os_error().unwrap() != 0 {
-        return Err(Error::GetPriority(IOError::last_os_error()));
+    check_priority(res)
+}
+
+/// The type of the `which` argument of [`get_priority`] and [`set_priority`], that changes
+/// between platforms.
+///
+/// [`get_priority`]: fn.get_priority.html
+/// [`set_priority`]: fn.set_priority.html
+#[cfg(target_os = "linux")]
+pub type Which = c_uint;
+/// The type of the `which` argument of [`get_priority`] and [`set_priority`], that changes
+/// between platforms.
+///
+/// [`get_priority`]: fn.get_priority.html
+/// [`set_priority`]: fn.set_priority.html
+#[cfg(not(target_os = "linux"))]
+pub type Which = c_int;
+
+/// Get the nice value of the calling process, process group or user, depending on `which`.
+///
+/// Unlike a bare `getpriority`, a nice value of `-1` is never taken as a error.
+pub fn nice_value(which: Which) -> Result<c_int, Error> { get_priority(which, 0) }
+
+/// Check the result of `getpriority`, with `errno` reset before calling it.
+///
+/// `-1` is both a valid priority and the error return, so only `errno` tells them apart.
+fn check_priority(res: c_int) -> Result<c_int, Error> {
+    if res == -1 {
+        let err = IOError::last_os_error();
+        if err.raw_os_error() != Some(0) {
+            return Err(Error::GetPriority(err));
+        }
     }
 
     Ok(res)
 }
 
+/// Set `errno` of the calling thread to `value`.
+#[inline]
+fn set_errno(value: c_int) { unsafe { *errno_location() = value } }
+
 /// Set the priority of a specified process.
 #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
 pub fn set_priority(which: c_int, who: c_int, prio: c_int) -> Result<(), Error> {
@@ -128,3 +191,158 @@ pub fn set_priority(which: c_uint, who: id_t, prio: c_int) -> Result<(), Error>
 
     Ok(())
 }
+
+/// Get the nice value of every process of the system, as pairs of process ID and nice value
+/// in no particular order.
+///
+/// The backend depends on the platform:
+/// - Linux and Android read the `nice` field of every `/proc/<pid>/stat`, skipping the
+///   processes that exit while they are read.
+/// - FreeBSD gets every process at once with the `kern.proc.proc` sysctl.
+/// - Other platforms are not supported, and always fail with `ErrorKind::Other`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn all_process_priorities() -> std::io::Result<Vec<(Pid, i32)>> {
+    let mut priorities = Vec::new();
+
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let pid = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+            Some(pid) => Pid(pid),
+            None => continue,
+        };
+
+        let stat = match fs::read_to_string(entry.path().join("stat")) {
+            Ok(stat) => stat,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+            // Exited between reading the directory and its `stat`
+            Err(ref err) if err.raw_os_error() == Some(libc::ESRCH) => continue,
+            Err(err) => return Err(err),
+        };
+
+        if let Some(nice) = stat_nice(&stat) {
+            priorities.push((pid, nice));
+        }
+    }
+
+    Ok(priorities)
+}
+
+/// Get the nice value of every process of the system, as pairs of process ID and nice value
+/// in no particular order.
+///
+/// The backend depends on the platform:
+/// - Linux and Android read the `nice` field of every `/proc/<pid>/stat`, skipping the
+///   processes that exit while they are read.
+/// - FreeBSD gets every process at once with the `kern.proc.proc` sysctl.
+/// - Other platforms are not supported, and always fail with `ErrorKind::Other`.
+#[cfg(target_os = "freebsd")]
+pub fn all_process_priorities() -> std::io::Result<Vec<(Pid, i32)>> {
+    use libc::{kinfo_proc, sysctl, CTL_KERN, KERN_PROC, KERN_PROC_PROC};
+
+    let mib = [CTL_KERN, KERN_PROC, KERN_PROC_PROC, 0];
+    let mut len = 0;
+
+    let res = unsafe {
+        sysctl(mib.as_ptr(), mib.len() as _, ptr::null_mut(), &mut len, ptr::null(), 0)
+    };
+    if res < 0 {
+        return Err(IOError::last_os_error());
+    }
+
+    // Room for the processes started between both calls
+    len += len / 8;
+    let mut procs: Vec<kinfo_proc> = Vec::with_capacity(len / mem::size_of::<kinfo_proc>());
+    let res = unsafe {
+        sysctl(mib.as_ptr(), mib.len() as _, procs.as_mut_ptr() as *mut _, &mut len, ptr::null(), 0)
+    };
+    if res < 0 {
+        return Err(IOError::last_os_error());
+    }
+    unsafe { procs.set_len(len / mem::size_of::<kinfo_proc>()) };
+
+    Ok(procs.iter().map(|kp| (Pid(kp.ki_pid), i32::from(kp.ki_nice))).collect())
+}
+
+/// Get the nice value of every process of the system, as pairs of process ID and nice value
+/// in no particular order.
+///
+/// The backend depends on the platform:
+/// - Linux and Android read the `nice` field of every `/proc/<pid>/stat`, skipping the
+///   processes that exit while they are read.
+/// - FreeBSD gets every process at once with the `kern.proc.proc` sysctl.
+/// - Other platforms are not supported, and always fail with `ErrorKind::Other`.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub fn all_process_priorities() -> std::io::Result<Vec<(Pid, i32)>> {
+    Err(IOError::new(
+        std::io::ErrorKind::Other,
+        "listing the priorities of every process is not supported on this platform",
+    ))
+}
+
+/// Parse the nice value of the contents of a `/proc/<pid>/stat` file, its 19th field.
+///
+/// The command name, the 2nd field, is between parentheses and may have spaces and
+/// parentheses itself, so the fields are counted from the last `)`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn stat_nice(stat: &str) -> Option<i32> {
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+
+    // The state is the 3rd field
+    after_comm.split_whitespace().nth(19 - 3)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libc::EPERM;
+
+    #[test]
+    fn minus_one_is_not_a_error() {
+        set_errno(0);
+        assert_eq!(check_priority(-1).unwrap(), -1);
+
+        set_errno(EPERM);
+        match check_priority(-1) {
+            Err(Error::GetPriority(err)) => assert_eq!(err.raw_os_error(), Some(EPERM)),
+            res => panic!("expected a error, got {:?}", res),
+        }
+        // A stale errno is ignored when the call didn't fail
+        assert_eq!(check_priority(5).unwrap(), 5);
+    }
+
+    #[test]
+    fn nice_value_of_minus_one() {
+        let which = PRIO_PROCESS as Which;
+        let original = nice_value(which).unwrap();
+
+        // Lowering the nice value needs privileges
+        if set_priority(which, 0, -1).is_ok() {
+            set_errno(EPERM);
+            assert_eq!(nice_value(which).unwrap(), -1);
+            set_priority(which, 0, original).unwrap();
+        }
+
+        assert_eq!(nice_value(which).unwrap(), original);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn stat_nice_after_odd_command_names() {
+        let stat = "42 (a) b (c) S 1 42 42 0 -1 4194560 100 0 0 0 1 2 0 0 30 10 1 0 100 1000";
+        assert_eq!(stat_nice(stat), Some(10));
+        assert_eq!(stat_nice("42 (short) S 1"), None);
+        assert_eq!(stat_nice("garbage"), None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn own_process_has_its_nice_value() {
+        let priorities = all_process_priorities().unwrap();
+        let own = priorities.iter().find(|(pid, _)| *pid == Pid::current()).map(|&(_, nice)| nice);
+
+        // The nice value on Linux is per thread, and other tests change the one of theirs
+        let nice = own.expect("the current process is missing");
+        assert!((-20..=19).contains(&nice), "{}", nice);
+    }
+}
@@ -0,0 +1,149 @@
+//! Module for safe API for I/O scheduling class and priority of processes (`ionice`).
+//!
+//! Only Linux has I/O scheduling classes, on other platforms the functions of this module
+//! always return [`Error::IoPriorityUnsupported`].
+//!
+//! [`Error::IoPriorityUnsupported`]: ../enum.Error.html#variant.IoPriorityUnsupported
+
+use std::{
+    fmt::{self, Display},
+    os::raw::c_int,
+};
+
+#[cfg(target_os = "linux")]
+use std::io::Error as IOError;
+
+#[cfg(target_os = "linux")]
+use libc::{syscall, SYS_ioprio_get, SYS_ioprio_set};
+
+use super::Error;
+
+/// `who` is a process ID (`0` for the calling process).
+pub const IOPRIO_WHO_PROCESS: c_int = 1;
+/// `who` is a process group ID (`0` for the calling process group).
+pub const IOPRIO_WHO_PGRP: c_int = 2;
+/// `who` is a user ID (`0` for the calling process real user ID).
+pub const IOPRIO_WHO_USER: c_int = 3;
+
+const IOPRIO_CLASS_SHIFT: c_int = 13;
+const IOPRIO_PRIO_MASK: c_int = (1 << IOPRIO_CLASS_SHIFT) - 1;
+
+/// I/O scheduling class of a process.
+///
+/// The classes that have priority levels hold it, where `0` is the highest priority and
+/// `7` is the lowest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IoClass {
+    /// Gets first access to the disk regardless of what else is going on in the system.
+    RealTime(u8),
+    /// Default class for any process that hasn't set a specific I/O priority.
+    BestEffort(u8),
+    /// Only gets disk time when no other process has asked for disk I/O.
+    Idle,
+    /// No class was set, the priority is derived from the CPU niceness of the process.
+    None,
+}
+
+impl IoClass {
+    /// Creates a `IoClass` from the raw value used by `ioprio_get` and `ioprio_set`.
+    pub fn from_raw(ioprio: c_int) -> Self {
+        let level = (ioprio & IOPRIO_PRIO_MASK) as u8;
+
+        match ioprio >> IOPRIO_CLASS_SHIFT {
+            1 => Self::RealTime(level),
+            2 => Self::BestEffort(level),
+            3 => Self::Idle,
+            _ => Self::None,
+        }
+    }
+
+    /// Get the raw value used by `ioprio_get` and `ioprio_set` of the `IoClass`.
+    pub fn to_raw(self) -> c_int {
+        let (class, level) = match self {
+            Self::None => (0, 0),
+            Self::RealTime(level) => (1, level),
+            Self::BestEffort(level) => (2, level),
+            Self::Idle => (3, 0),
+        };
+
+        (class << IOPRIO_CLASS_SHIFT) | c_int::from(level)
+    }
+}
+
+impl Display for IoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RealTime(level) => write!(f, "realtime: prio {}", level),
+            Self::BestEffort(level) => write!(f, "best-effort: prio {}", level),
+            Self::Idle => write!(f, "idle"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Get the I/O scheduling class and priority of the specified processes.
+///
+/// `which` is one of `IOPRIO_WHO_PROCESS`, `IOPRIO_WHO_PGRP` or `IOPRIO_WHO_USER`, and
+/// determines how `who` is interpreted.
+#[cfg(target_os = "linux")]
+pub fn get_io_priority(which: c_int, who: c_int) -> Result<IoClass, Error> {
+    let res = unsafe { syscall(SYS_ioprio_get, which, who) };
+
+    if res < 0 {
+        return Err(Error::GetIoPriority(IOError::last_os_error()));
+    }
+
+    Ok(IoClass::from_raw(res as c_int))
+}
+
+/// Get the I/O scheduling class and priority of the specified processes.
+///
+/// Always fails, since I/O scheduling classes are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn get_io_priority(_which: c_int, _who: c_int) -> Result<IoClass, Error> {
+    Err(Error::IoPriorityUnsupported)
+}
+
+/// Set the I/O scheduling class and priority of the specified processes.
+///
+/// `which` is one of `IOPRIO_WHO_PROCESS`, `IOPRIO_WHO_PGRP` or `IOPRIO_WHO_USER`, and
+/// determines how `who` is interpreted.
+#[cfg(target_os = "linux")]
+pub fn set_io_priority(which: c_int, who: c_int, class: IoClass) -> Result<(), Error> {
+    let res = unsafe { syscall(SYS_ioprio_set, which, who, class.to_raw()) };
+
+    if res < 0 {
+        return Err(Error::SetIoPriority(IOError::last_os_error()));
+    }
+
+    Ok(())
+}
+
+/// Set the I/O scheduling class and priority of the specified processes.
+///
+/// Always fails, since I/O scheduling classes are only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn set_io_priority(_which: c_int, _who: c_int, _class: IoClass) -> Result<(), Error> {
+    Err(Error::IoPriorityUnsupported)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_round_trip() {
+        let classes = [IoClass::RealTime(0), IoClass::BestEffort(4), IoClass::Idle, IoClass::None];
+
+        for class in &classes {
+            assert_eq!(IoClass::from_raw(class.to_raw()), *class);
+        }
+    }
+
+    #[test]
+    fn set_and_get_own_io_priority() {
+        set_io_priority(IOPRIO_WHO_PROCESS, 0, IoClass::BestEffort(7)).unwrap();
+
+        assert_eq!(get_io_priority(IOPRIO_WHO_PROCESS, 0).unwrap(), IoClass::BestEffort(7));
+    }
+}
@@ -2,8 +2,18 @@
 
 use std::{
     error::Error as StdError,
+    ffi::CStr,
     fmt::{self, Display},
-    os::raw::c_int,
+    io,
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::raw::{c_char, c_int, c_uint},
+    ptr,
+};
+
+use libc::{
+    if_indextoname, sysctl, AF_INET, AF_INET6, CTL_NET, IF_NAMESIZE, NET_RT_DUMP, PF_ROUTE,
+    RTA_DST, RTA_GATEWAY, RTA_NETMASK, RTF_HOST, RTF_UP,
 };
 
 // TODO(GrayJack): Make a pull request to expose that on libc crate
@@ -50,3 +60,379 @@ pub fn set_routing_table(rtableid: c_int) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// A entry of the routing table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Route {
+    destination: IpAddr,
+    prefix_len:  u8,
+    gateway:     Option<IpAddr>,
+    flags:       c_int,
+    interface:   String,
+}
+
+impl Route {
+    /// Get the destination network address.
+    #[inline]
+    pub fn destination(&self) -> IpAddr { self.destination }
+
+    /// Get the number of leading bits of the destination that are matched.
+    #[inline]
+    pub fn prefix_len(&self) -> u8 { self.prefix_len }
+
+    /// Get the gateway address, `None` if the destination is directly reachable.
+    #[inline]
+    pub fn gateway(&self) -> Option<IpAddr> { self.gateway }
+
+    /// Get the `RTF_*` flags of the route.
+    #[inline]
+    pub fn flags(&self) -> c_int { self.flags }
+
+    /// Get the name of the interface the route goes through, empty if it is unknown.
+    #[inline]
+    pub fn interface(&self) -> &str { &self.interface }
+
+    /// Check if `addr` is inside the destination network of the route.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.destination, addr) {
+            (IpAddr::V4(dst), IpAddr::V4(addr)) => {
+                let mask = (!0u32).checked_shl(32 - u32::from(self.prefix_len));
+                let mask = mask.unwrap_or(0);
+                u32::from(dst) & mask == u32::from(addr) & mask
+            },
+            (IpAddr::V6(dst), IpAddr::V6(addr)) => {
+                let mask = (!0u128).checked_shl(128 - u32::from(self.prefix_len));
+                let mask = mask.unwrap_or(0);
+                u128::from(dst) & mask == u128::from(addr) & mask
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Get every route of the routing table of the current process.
+pub fn routes() -> Result<Vec<Route>, Error> {
+    let mib = [CTL_NET, PF_ROUTE, 0, 0, NET_RT_DUMP, 0, get_routing_table()];
+    let mut len = 0;
+
+    let res = unsafe {
+        sysctl(mib.as_ptr(), mib.len() as c_uint, ptr::null_mut(), &mut len, ptr::null_mut(), 0)
+    };
+    if res < 0 {
+        return Err(sysctl_error());
+    }
+
+    let mut buf = vec![0u8; len];
+    let res = unsafe {
+        sysctl(
+            mib.as_ptr(),
+            mib.len() as c_uint,
+            buf.as_mut_ptr() as *mut _,
+            &mut len,
+            ptr::null_mut(),
+            0,
+        )
+    };
+    if res < 0 {
+        return Err(sysctl_error());
+    }
+    buf.truncate(len);
+
+    Ok(parse_routes(&buf, interface_name))
+}
+
+/// Get the up route of the routing table of the current process with the longest prefix
+/// containing `addr`.
+pub fn route_for(addr: IpAddr) -> Option<Route> { longest_match(routes().ok()?, addr) }
+
+/// Pick the up route of `routes` with the longest prefix containing `addr`.
+fn longest_match(routes: Vec<Route>, addr: IpAddr) -> Option<Route> {
+    routes
+        .into_iter()
+        .filter(|route| route.flags & RTF_UP != 0 && route.contains(addr))
+        .max_by_key(|route| route.prefix_len)
+}
+
+/// Parse the routing messages of a `NET_RT_DUMP` sysctl, using `interface` to get the name
+/// of a interface index. Routes of address families other than IPv4 and IPv6 are skipped.
+fn parse_routes(mut buf: &[u8], interface: impl Fn(u16) -> String) -> Vec<Route> {
+    let mut routes = Vec::new();
+
+    // Offsets of the `rt_msghdr` fields that are used
+    const MSGLEN: usize = 0;
+    const HDRLEN: usize = 4;
+    const INDEX: usize = 6;
+    const ADDRS: usize = 12;
+    const FLAGS: usize = 16;
+
+    while buf.len() >= FLAGS + 4 {
+        let msg_len = usize::from(read_u16(buf, MSGLEN));
+        if msg_len < FLAGS + 4 || msg_len > buf.len() {
+            break;
+        }
+        let (msg, rest) = buf.split_at(msg_len);
+        buf = rest;
+
+        let hdr_len = usize::from(read_u16(msg, HDRLEN)).min(msg.len());
+        let addrs = read_i32(msg, ADDRS);
+        let flags = read_i32(msg, FLAGS);
+
+        let mut sockaddrs = &msg[hdr_len..];
+        let (mut destination, mut gateway, mut netmask) = (None, None, None);
+        for bit in 0..8 {
+            let kind = 1 << bit;
+            if addrs & kind == 0 {
+                continue;
+            }
+            if sockaddrs.is_empty() {
+                break;
+            }
+
+            let len = usize::from(sockaddrs[0]);
+            let sockaddr = &sockaddrs[..len.min(sockaddrs.len())];
+            sockaddrs = &sockaddrs[round_up(len).min(sockaddrs.len())..];
+
+            match kind {
+                RTA_DST => destination = sockaddr_ip(sockaddr),
+                RTA_GATEWAY => gateway = sockaddr_ip(sockaddr),
+                RTA_NETMASK => netmask = Some(sockaddr),
+                _ => {},
+            }
+        }
+
+        let destination = match destination {
+            Some(destination) => destination,
+            None => continue,
+        };
+        let max_len = if destination.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match netmask {
+            Some(mask) if flags & RTF_HOST == 0 => mask_prefix_len(mask, destination),
+            _ => max_len,
+        };
+
+        routes.push(Route {
+            destination,
+            prefix_len,
+            gateway,
+            flags,
+            interface: interface(read_u16(msg, INDEX)),
+        });
+    }
+
+    routes
+}
+
+/// Get the address of a `sockaddr_in` or `sockaddr_in6` in `sockaddr`, `None` for any other
+/// address family.
+fn sockaddr_ip(sockaddr: &[u8]) -> Option<IpAddr> {
+    match c_int::from(*sockaddr.get(1)?) {
+        AF_INET => {
+            let mut octets = [0; 4];
+            copy_from(&mut octets, sockaddr, 4);
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        },
+        AF_INET6 => {
+            let mut octets = [0; 16];
+            copy_from(&mut octets, sockaddr, 8);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        },
+        _ => None,
+    }
+}
+
+/// Count the leading ones of the netmask `sockaddr`, which may be shorter than a full
+/// `sockaddr` and has no address family, so the one of `destination` is used.
+fn mask_prefix_len(sockaddr: &[u8], destination: IpAddr) -> u8 {
+    let ones = match destination {
+        IpAddr::V4(_) => {
+            let mut octets = [0; 4];
+            copy_from(&mut octets, sockaddr, 4);
+            (!u32::from_be_bytes(octets)).leading_zeros()
+        },
+        IpAddr::V6(_) => {
+            let mut octets = [0; 16];
+            copy_from(&mut octets, sockaddr, 8);
+            (!u128::from_be_bytes(octets)).leading_zeros()
+        },
+    };
+    ones as u8
+}
+
+/// Copy the bytes of `src` starting at `offset` into `dst`, leaving the missing ones as
+/// they are.
+fn copy_from(dst: &mut [u8], src: &[u8], offset: usize) {
+    let src = src.get(offset..).unwrap_or_default();
+    let len = dst.len().min(src.len());
+    dst[..len].copy_from_slice(&src[..len]);
+}
+
+/// Round `len` up to the alignment of the `sockaddr`s in a routing message, where empty
+/// ones still take space.
+#[inline]
+fn round_up(len: usize) -> usize {
+    if len == 0 { mem::size_of::<usize>() } else { 1 + ((len - 1) | (mem::size_of::<usize>() - 1)) }
+}
+
+#[inline]
+fn read_u16(buf: &[u8], offset: usize) -> u16 { u16::from_ne_bytes([buf[offset], buf[offset + 1]]) }
+
+#[inline]
+fn read_i32(buf: &[u8], offset: usize) -> i32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    i32::from_ne_bytes(bytes)
+}
+
+/// Get the name of the interface with `index`, empty if there is none.
+fn interface_name(index: u16) -> String {
+    let mut name = [0 as c_char; IF_NAMESIZE];
+    let res = unsafe { if_indextoname(c_uint::from(index), name.as_mut_ptr()) };
+
+    if res.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy().into_owned()
+    }
+}
+
+#[inline]
+fn sysctl_error() -> Error {
+    Error { err: format!("sysctl: failed to dump routing table: {}", io::Error::last_os_error()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a routing message with `index` as interface index and the given `sockaddr`s.
+    fn message(index: u16, flags: c_int, addrs: c_int, sockaddrs: &[Vec<u8>]) -> Vec<u8> {
+        let hdr_len = 96;
+        let mut msg = vec![0; hdr_len];
+        msg[4..6].copy_from_slice(&(hdr_len as u16).to_ne_bytes());
+        msg[6..8].copy_from_slice(&index.to_ne_bytes());
+        msg[12..16].copy_from_slice(&addrs.to_ne_bytes());
+        msg[16..20].copy_from_slice(&flags.to_ne_bytes());
+
+        for sockaddr in sockaddrs {
+            let mut sockaddr = sockaddr.clone();
+            sockaddr.resize(round_up(sockaddr.len()), 0);
+            msg.extend(sockaddr);
+        }
+
+        let msg_len = msg.len() as u16;
+        msg[0..2].copy_from_slice(&msg_len.to_ne_bytes());
+        msg
+    }
+
+    fn sockaddr_in(octets: [u8; 4]) -> Vec<u8> {
+        let mut sockaddr = vec![16, AF_INET as u8, 0, 0];
+        sockaddr.extend(&octets);
+        sockaddr.resize(16, 0);
+        sockaddr
+    }
+
+    fn sockaddr_in6(addr: Ipv6Addr) -> Vec<u8> {
+        let mut sockaddr = vec![28, AF_INET6 as u8, 0, 0, 0, 0, 0, 0];
+        sockaddr.extend(&addr.octets());
+        sockaddr.resize(28, 0);
+        sockaddr
+    }
+
+    /// Netmasks are cut after their last non zero byte.
+    fn netmask(octets: &[u8], offset: usize) -> Vec<u8> {
+        let used = octets.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        if used == 0 {
+            return Vec::new();
+        }
+        let mut sockaddr = vec![0; offset];
+        sockaddr.extend(&octets[..used]);
+        sockaddr[0] = sockaddr.len() as u8;
+        sockaddr
+    }
+
+    fn synthetic_table() -> Vec<Route> {
+        let all = RTA_DST | RTA_GATEWAY | RTA_NETMASK;
+        let mut buf = Vec::new();
+        buf.extend(message(
+            1,
+            RTF_UP | libc::RTF_GATEWAY,
+            all,
+            &[sockaddr_in([0, 0, 0, 0]), sockaddr_in([10, 0, 0, 1]), netmask(&[0; 4], 4)],
+        ));
+        buf.extend(message(
+            1,
+            RTF_UP,
+            all,
+            // The gateway of a directly connected network is a link level address
+            &[
+                sockaddr_in([10, 0, 0, 0]),
+                vec![8, libc::AF_LINK as u8, 0, 0, 0, 0, 0, 0],
+                netmask(&[255, 0, 0, 0], 4),
+            ],
+        ));
+        buf.extend(message(
+            2,
+            RTF_UP | libc::RTF_GATEWAY,
+            all,
+            &[
+                sockaddr_in([10, 1, 0, 0]),
+                sockaddr_in([10, 0, 0, 254]),
+                netmask(&[255, 255, 0, 0], 4),
+            ],
+        ));
+        buf.extend(message(
+            2,
+            0,
+            all,
+            &[
+                sockaddr_in([10, 1, 2, 0]),
+                sockaddr_in([10, 0, 0, 253]),
+                netmask(&[255, 255, 255, 0], 4),
+            ],
+        ));
+        buf.extend(message(
+            3,
+            RTF_UP | RTF_HOST,
+            RTA_DST | RTA_GATEWAY,
+            &[sockaddr_in6(Ipv6Addr::LOCALHOST), sockaddr_in6(Ipv6Addr::LOCALHOST)],
+        ));
+
+        parse_routes(&buf, |index| format!("if{}", index))
+    }
+
+    #[test]
+    fn parse_synthetic_table() {
+        let routes = synthetic_table();
+
+        assert_eq!(routes.len(), 5);
+        assert_eq!(routes[0].destination(), IpAddr::from([0, 0, 0, 0]));
+        assert_eq!(routes[0].prefix_len(), 0);
+        assert_eq!(routes[0].gateway(), Some(IpAddr::from([10, 0, 0, 1])));
+        assert_eq!(routes[1].prefix_len(), 8);
+        assert_eq!(routes[1].gateway(), None);
+        assert_eq!(routes[1].interface(), "if1");
+        assert_eq!(routes[2].prefix_len(), 16);
+        assert_eq!(routes[4].destination(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(routes[4].prefix_len(), 128);
+        assert_eq!(routes[4].interface(), "if3");
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let route = |addr: IpAddr| longest_match(synthetic_table(), addr).map(|r| r.destination());
+
+        assert_eq!(route(IpAddr::from([8, 8, 8, 8])), Some(IpAddr::from([0, 0, 0, 0])));
+        assert_eq!(route(IpAddr::from([10, 9, 9, 9])), Some(IpAddr::from([10, 0, 0, 0])));
+        // The /24 route is down, so the /16 one is used
+        assert_eq!(route(IpAddr::from([10, 1, 2, 3])), Some(IpAddr::from([10, 1, 0, 0])));
+        assert_eq!(route(IpAddr::V6(Ipv6Addr::LOCALHOST)), Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+        assert_eq!(route("2001:db8::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn routes_of_current_table() {
+        let routes = routes().unwrap();
+
+        assert!(routes.iter().all(|route| route.prefix_len() <= 128));
+    }
+}
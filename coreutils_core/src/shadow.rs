@@ -0,0 +1,129 @@
+//! Module for the shadow password file, where the password hashes and aging information of
+//! the users are kept away from the world readable passwd file.
+use std::{fs, io, path::Path, str};
+
+use bstr::{BStr, BString, ByteSlice};
+
+/// Path of the shadow password file.
+pub const SHADOW_FILE: &str = "/etc/shadow";
+
+/// A entry of the shadow password file.
+///
+/// The dates are in days since the epoch and the periods in days, `None` when the field is
+/// empty.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Shadow {
+    name:        BString,
+    passwd:      BString,
+    last_change: Option<i64>,
+    min:         Option<i64>,
+    max:         Option<i64>,
+    warn:        Option<i64>,
+    inactive:    Option<i64>,
+    expire:      Option<i64>,
+}
+
+impl Shadow {
+    /// Find the shadow entry of the user `name` on the shadow password file.
+    ///
+    /// Returns `Ok(None)` if the user has no entry, and fails if the file can't be read, like
+    /// without enough privileges.
+    pub fn from_name(name: &str) -> io::Result<Option<Self>> {
+        Self::from_name_in_file(Path::new(SHADOW_FILE), name)
+    }
+
+    /// Find the shadow entry of the user `name` on the shadow password file at `path`.
+    pub fn from_name_in_file(path: &Path, name: &str) -> io::Result<Option<Self>> {
+        let contents = fs::read(path)?;
+
+        Ok(contents.lines().filter_map(Self::from_line).find(|sp| sp.name == name))
+    }
+
+    /// Parse a line in the `/etc/shadow` format, `None` if it is not valid.
+    fn from_line(line: &[u8]) -> Option<Self> {
+        let fields: Vec<_> = line.split_str(":").collect();
+        if fields.len() != 9 || fields[0].is_empty() {
+            return None;
+        }
+
+        let parse_days = |field: &[u8]| -> Option<Option<i64>> {
+            if field.is_empty() {
+                Some(None)
+            } else {
+                str::from_utf8(field).ok()?.parse().ok().map(Some)
+            }
+        };
+
+        Some(Shadow {
+            name:        BString::from(fields[0]),
+            passwd:      BString::from(fields[1]),
+            last_change: parse_days(fields[2])?,
+            min:         parse_days(fields[3])?,
+            max:         parse_days(fields[4])?,
+            warn:        parse_days(fields[5])?,
+            inactive:    parse_days(fields[6])?,
+            expire:      parse_days(fields[7])?,
+        })
+    }
+
+    /// Get the user login name.
+    #[inline]
+    pub fn name(&self) -> &BStr { self.name.as_bstr() }
+
+    /// Get the user encrypted password.
+    #[inline]
+    pub fn passwd(&self) -> &BStr { self.passwd.as_bstr() }
+
+    /// Get the date of the last password change.
+    #[inline]
+    pub fn last_change(&self) -> Option<i64> { self.last_change }
+
+    /// Get the minimum password age.
+    #[inline]
+    pub fn min(&self) -> Option<i64> { self.min }
+
+    /// Get the maximum password age.
+    #[inline]
+    pub fn max(&self) -> Option<i64> { self.max }
+
+    /// Get the password warning period.
+    #[inline]
+    pub fn warn(&self) -> Option<i64> { self.warn }
+
+    /// Get the password inactivity period.
+    #[inline]
+    pub fn inactive(&self) -> Option<i64> { self.inactive }
+
+    /// Get the account expiration date.
+    #[inline]
+    pub fn expire(&self) -> Option<i64> { self.expire }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, process};
+
+    #[test]
+    fn from_name_in_file_parses_fields() {
+        let path = env::temp_dir().join(format!("coreutils_shadow_{}", process::id()));
+        fs::write(&path, "root:!:18000:0:99999:7:::\nalice:$6$salt$hash:18500::90:7:30:19000:\n")
+            .unwrap();
+
+        let alice = Shadow::from_name_in_file(&path, "alice");
+        let missing = Shadow::from_name_in_file(&path, "bob");
+        fs::remove_file(&path).unwrap();
+
+        let alice = alice.unwrap().unwrap();
+        assert_eq!(alice.name(), "alice");
+        assert_eq!(alice.passwd(), "$6$salt$hash");
+        assert_eq!((alice.last_change(), alice.min(), alice.max()), (Some(18500), None, Some(90)));
+        assert_eq!(alice.warn(), Some(7));
+        assert_eq!((alice.inactive(), alice.expire()), (Some(30), Some(19000)));
+        assert_eq!(missing.unwrap(), None);
+
+        assert_eq!(Shadow::from_line(b"bad:line"), None);
+        assert_eq!(Shadow::from_line(b"x:!:soon::::::"), None);
+    }
+}
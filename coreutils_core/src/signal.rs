@@ -0,0 +1,118 @@
+//! Module for signal helpers, like the ones to keep a process running after its terminal
+//! hangs up, as `nohup`(1) does.
+
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{file_descriptor::FileDescriptor, tty::isatty};
+
+use libc::{signal, SIGHUP, SIG_ERR, SIG_IGN};
+
+/// Name of the file the output is appended to when it would go to a terminal.
+pub const NOHUP_FILE: &str = "nohup.out";
+
+/// Ignore the SIGHUP signal, sent when the terminal hangs up. It stays ignored on the
+/// programs executed after.
+pub fn ignore_sighup() -> io::Result<()> {
+    if unsafe { signal(SIGHUP, SIG_IGN) } == SIG_ERR {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Redirect the standard output, if it is a terminal, to the end of [`NOHUP_FILE`] on the
+/// current directory, or on `$HOME` if that one can't be opened, creating it readable and
+/// writable only by the user. Then redirect the standard error, if it is a terminal, to the
+/// standard output.
+///
+/// Returns the file the standard output was redirected to, if it was.
+///
+/// [`NOHUP_FILE`]: constant.NOHUP_FILE.html
+pub fn ensure_output_redirect() -> io::Result<Option<PathBuf>> {
+    let output = if isatty(FileDescriptor::StdOut) {
+        let targets = output_targets(env::var_os("HOME").map(PathBuf::from));
+        let (path, file) = open_first(&targets, |path| {
+            OpenOptions::new().create(true).append(true).mode(0o600).open(path)
+        })?;
+
+        FileDescriptor::StdOut.redirect_to(&file)?;
+        Some(path)
+    } else {
+        None
+    };
+
+    if isatty(FileDescriptor::StdErr) {
+        FileDescriptor::StdErr.redirect_to(&FileDescriptor::StdOut)?;
+    }
+
+    Ok(output)
+}
+
+/// The files the output may be redirected to, in order of preference.
+fn output_targets(home: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut targets = vec![PathBuf::from(NOHUP_FILE)];
+
+    match home {
+        Some(home) if !home.as_os_str().is_empty() => targets.push(home.join(NOHUP_FILE)),
+        _ => {},
+    }
+
+    targets
+}
+
+/// Open the first of `targets` that `open` can open, failing with the error of the last one
+/// if none can.
+fn open_first(
+    targets: &[PathBuf], mut open: impl FnMut(&Path) -> io::Result<File>,
+) -> io::Result<(PathBuf, File)> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no file to redirect to");
+
+    for target in targets {
+        match open(target) {
+            Ok(file) => return Ok((target.clone(), file)),
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, process};
+
+    #[test]
+    fn targets_fall_back_to_home() {
+        let home = PathBuf::from("/home/alice");
+
+        assert_eq!(output_targets(Some(home)), [
+            PathBuf::from("nohup.out"),
+            PathBuf::from("/home/alice/nohup.out")
+        ]);
+        assert_eq!(output_targets(None), [PathBuf::from("nohup.out")]);
+        assert_eq!(output_targets(Some(PathBuf::new())), [PathBuf::from("nohup.out")]);
+    }
+
+    #[test]
+    fn first_target_that_opens_is_used() {
+        let path = env::temp_dir().join(format!("coreutils_nohup_{}", process::id()));
+        let targets = [PathBuf::from("/coreutils-no-such-dir/nohup.out"), path.clone()];
+        let open = |path: &Path| OpenOptions::new().create(true).append(true).open(path);
+
+        let res = open_first(&targets, open);
+        let missing = open_first(&targets[..1], open);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(res.unwrap().0, path);
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(open_first(&[], open).is_err());
+    }
+}
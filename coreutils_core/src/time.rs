@@ -0,0 +1,65 @@
+//! Module with a thin layer over the time implementation, so the utilities don't use its
+//! functions directly.
+
+use std::{
+    error::Error as StdError,
+    fmt::{self, Display},
+};
+
+pub use ::time::{Timespec, Tm};
+
+/// Error formatting a time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TimeError {
+    err: String,
+}
+
+impl Display for TimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.err) }
+}
+
+impl StdError for TimeError {}
+
+/// Get the current time.
+#[inline]
+pub fn now() -> Timespec { ::time::get_time() }
+
+/// Break `ts` down in the local time zone.
+#[inline]
+pub fn to_local(ts: Timespec) -> Tm { ::time::at(ts) }
+
+/// Break `ts` down in UTC.
+#[inline]
+pub fn to_utc(ts: Timespec) -> Tm { ::time::at_utc(ts) }
+
+/// Format `ts` in the local time zone with the `strftime`(3) format `fmt`.
+pub fn format(ts: Timespec, fmt: &str) -> Result<String, TimeError> {
+    match to_local(ts).strftime(fmt) {
+        Ok(t) => Ok(t.to_string()),
+        Err(err) => Err(TimeError { err: err.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_and_format_in_any_tz() {
+        // 2020-02-29 23:30:15 UTC
+        let ts = Timespec::new(1_583_019_015, 0);
+
+        let utc = to_utc(ts);
+        let local = to_local(ts);
+        // The local time is the UTC time shifted by the offset of the time zone, whatever it is
+        let shifted = to_utc(Timespec::new(ts.sec + i64::from(local.tm_utcoff), 0));
+
+        assert_eq!((utc.tm_year, utc.tm_mon, utc.tm_mday, utc.tm_hour), (120, 1, 29, 23));
+        let fields = |tm: &Tm| (tm.tm_year, tm.tm_mon, tm.tm_mday, tm.tm_hour, tm.tm_min);
+        assert_eq!(fields(&local), fields(&shifted));
+        assert_eq!(local.to_timespec(), utc.to_timespec());
+        assert_eq!(format(ts, "%s").unwrap(), "1583019015");
+        assert_eq!(format(ts, "%F %T").unwrap(), shifted.strftime("%F %T").unwrap().to_string());
+        assert!(now().sec > ts.sec);
+    }
+}
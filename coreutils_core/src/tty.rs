@@ -2,14 +2,29 @@
 
 use std::{
     error::Error as StdError,
-    ffi::CStr,
+    ffi::{CStr, OsStr},
     fmt::{self, Display},
-    os::raw::c_int,
+    fs::{self, File, OpenOptions},
+    io,
+    os::{
+        raw::c_int,
+        unix::{
+            ffi::OsStrExt,
+            fs::{FileTypeExt, MetadataExt, OpenOptionsExt},
+            io::AsRawFd,
+        },
+    },
+    path::{Path, PathBuf},
 };
 
-use libc::ttyname;
+use libc::{ttyname, O_NOCTTY, O_NONBLOCK};
 
-use crate::file_descriptor::FileDescriptor;
+use crate::{
+    file_descriptor::FileDescriptor,
+    group::tty_gid,
+    time,
+    types::{Gid, Mode},
+};
 
 use bstr::{BStr, BString, ByteSlice};
 
@@ -58,9 +73,46 @@ impl TTYName {
         Ok(TTYName(name))
     }
 
+    /// Create a `TTYName` from the `path` of a terminal device, resolving symbolic links like
+    /// `/dev/stdin` to the device they point to.
+    ///
+    /// Fails with `NotTTY` if `path` is not a character device or the device is not a
+    /// terminal.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let path = fs::canonicalize(path).map_err(|err| io_error("realpath", &err))?;
+
+        let meta = fs::metadata(&path).map_err(|err| io_error("stat", &err))?;
+        if !meta.file_type().is_char_device() {
+            return Err(Error::NotTTY);
+        }
+
+        // Terminals of other users are usually only writable
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(O_NOCTTY | O_NONBLOCK)
+            .open(&path)
+            .or_else(|_| {
+                OpenOptions::new().write(true).custom_flags(O_NOCTTY | O_NONBLOCK).open(&path)
+            })
+            .map_err(|err| io_error("open", &err))?;
+
+        if unsafe { libc::isatty(file.as_raw_fd()) } != 1 {
+            return Err(Error::NotTTY);
+        }
+
+        Ok(TTYName(BString::from(path.as_os_str().as_bytes())))
+    }
+
     pub fn as_bstr(&self) -> &BStr { self.0.as_bstr() }
 
     pub fn to_bstring(&self) -> BString { self.0.clone() }
+
+    /// Get the name without the `/dev/` prefix, as the line of the terminal is written on
+    /// the utmp entries.
+    pub fn short(&self) -> &BStr {
+        let name = self.as_bstr();
+        if name.starts_with(b"/dev/") { name[5..].as_bstr() } else { name }
+    }
 }
 
 impl Display for TTYName {
@@ -68,6 +120,31 @@ impl Display for TTYName {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.0) }
 }
 
+/// Convert a `io::Error` of the C function `fn_name` into a `Error`.
+#[inline]
+fn io_error(fn_name: &str, err: &io::Error) -> Error {
+    Error::LibcCall(fn_name.to_string(), err.raw_os_error().unwrap_or(0))
+}
+
+/// Path of the controlling terminal of the process, whatever terminal that is.
+pub const CONTROLLING_TTY: &str = "/dev/tty";
+
+/// Open the controlling terminal of the process for reading and writing, so a tool can
+/// prompt the user even when its standard input and output are redirected.
+///
+/// The returned `File` owns a new file descriptor, closed when it is dropped, since
+/// `FileDescriptor` only names the standard streams.
+///
+/// Fails, usually with `ENXIO`, if the process has no controlling terminal, like the ones
+/// started by a service manager or after `setsid`.
+pub fn controlling_tty() -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).open(CONTROLLING_TTY)
+}
+
+/// Check if the process has a controlling terminal it can open.
+#[inline]
+pub fn has_controlling_tty() -> bool { controlling_tty().is_ok() }
+
 /// Check if the given `FileDescriptor` is a TTY
 /// ## Example
 /// ```
@@ -80,3 +157,210 @@ impl Display for TTYName {
 pub fn isatty(file_descriptor: FileDescriptor) -> bool {
     unsafe { libc::isatty(file_descriptor as c_int) == 1 }
 }
+
+/// Get the mesg state character (`+` if writable by the group owning the terminals, `-` if
+/// not) of the terminal `line`, relative to `/dev`, and the last time it was accessed, `0` if
+/// the terminal can't be checked, in which case the character is `?`.
+///
+/// On systems without a group owning the terminals, any group allowed to write counts.
+pub fn device_status(line: &BStr) -> (char, i64) {
+    let mut dev_file = PathBuf::from("/dev");
+    dev_file.push(OsStr::from_bytes(line.as_bytes()));
+
+    if let Ok(meta) = dev_file.metadata() {
+        let msg = mesg_state(Mode::from_st_mode(meta.mode()), meta.gid(), tty_gid());
+        (msg, meta.atime())
+    } else {
+        ('?', 0)
+    }
+}
+
+/// Get the mesg state character of a terminal with the permissions `mode` and owned by the
+/// group `gid`, where `tty_gid` is the group owning the terminals, if any.
+fn mesg_state(mode: Mode, gid: Gid, tty_gid: Option<Gid>) -> char {
+    let owned = tty_gid.is_none() || tty_gid == Some(gid);
+    let writable = mode.contains(Mode::GROUP_WRITE) && owned;
+    if writable { '+' } else { '-' }
+}
+
+/// Seconds since `last_change`, `None` if it is unknown or in the future.
+pub fn idle_seconds(last_change: i64) -> Option<i64> {
+    let now = time::now().sec;
+
+    if 0 < last_change { now.checked_sub(last_change).filter(|&secs| secs >= 0) } else { None }
+}
+
+/// Get the idle time at the time `now` of a terminal last accessed at `last_change`, `0`
+/// meaning unknown.
+///
+/// An access time in the future, or too far from `now` to be subtracted, is unknown.
+pub fn idle_time(last_change: i64, now: i64) -> IdleTime {
+    if last_change == 0 {
+        return IdleTime::Unknown;
+    }
+
+    match now.checked_sub(last_change) {
+        Some(secs) if secs < 0 => IdleTime::Unknown,
+        Some(_) if last_change < 0 => IdleTime::Old,
+        Some(secs) if secs < 60 => IdleTime::Active(secs),
+        Some(secs) if secs < 24 * 3600 => IdleTime::Idle(secs),
+        Some(_) => IdleTime::Old,
+        None => IdleTime::Unknown,
+    }
+}
+
+/// How long a terminal has been idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdleTime {
+    /// Used in the last minute, with the seconds since then
+    Active(i64),
+    /// Used in the last day, with the seconds since then
+    Idle(i64),
+    /// Not used in the last day
+    Old,
+    /// The last access time is unknown
+    Unknown,
+}
+
+impl IdleTime {
+    /// Get the idle time of a terminal last accessed at `last_change`, `0` meaning unknown,
+    /// see [`idle_time`].
+    ///
+    /// [`idle_time`]: fn.idle_time.html
+    pub fn new(last_change: i64) -> Self { idle_time(last_change, time::now().sec) }
+
+    /// Get the idle seconds, `-1` if the terminal is old or its idle time is unknown.
+    pub fn seconds(&self) -> i64 {
+        match self {
+            IdleTime::Active(secs) | IdleTime::Idle(secs) => *secs,
+            IdleTime::Old | IdleTime::Unknown => -1,
+        }
+    }
+}
+
+impl Display for IdleTime {
+    /// Formats the idle time the way `who`(1) shows it: `.` if active in the last minute,
+    /// `HH:MM` if active in the last day, `old` otherwise and `?` if it is unknown.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdleTime::Active(_) => write!(f, "."),
+            IdleTime::Idle(secs) => write!(f, "{:02}:{:02}", secs / 3600, (secs % 3600) / 60),
+            IdleTime::Old => write!(f, "old"),
+            IdleTime::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// Format the idle time of a terminal last accessed at `last_change` the way `who`(1)
+/// shows it, see [`IdleTime`].
+///
+/// [`IdleTime`]: enum.IdleTime.html
+pub fn format_idle(last_change: i64) -> String { IdleTime::new(last_change).to_string() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn controlling_tty_opens_when_present() {
+        if !has_controlling_tty() {
+            eprintln!("skipped: the process has no controlling terminal");
+            return;
+        }
+
+        let tty = controlling_tty().unwrap();
+        assert_eq!(unsafe { libc::isatty(tty.as_raw_fd()) }, 1);
+    }
+
+    #[test]
+    fn mesg_state_needs_terminal_group() {
+        let (writable, private) = (Mode::from_st_mode(0o620), Mode::from_st_mode(0o600));
+
+        assert_eq!(mesg_state(writable, 5, Some(5)), '+');
+        assert_eq!(mesg_state(writable, 1000, Some(5)), '-');
+        assert_eq!(mesg_state(private, 5, Some(5)), '-');
+        assert_eq!(mesg_state(writable, 1000, None), '+');
+        assert_eq!(mesg_state(private, 1000, None), '-');
+    }
+
+    #[test]
+    fn format_idle_ranges() {
+        let now = time::now().sec;
+
+        assert_eq!(format_idle(0), "?");
+        assert_eq!(format_idle(now - 30), ".");
+        assert_eq!(format_idle(now - 3720), "01:02");
+        assert_eq!(format_idle(now - 2 * 24 * 3600), "old");
+        assert_eq!(idle_seconds(now + 3600), None);
+    }
+
+    #[test]
+    fn idle_time_seconds() {
+        let now = 1_500_000_000;
+
+        assert_eq!(idle_time(now - 30, now), IdleTime::Active(30));
+        assert_eq!(idle_time(now - 30, now).seconds(), 30);
+        assert_eq!(idle_time(now - 3720, now), IdleTime::Idle(3720));
+        assert_eq!(idle_time(now - 3720, now).seconds(), 3720);
+        assert_eq!(idle_time(now - 2 * 24 * 3600, now), IdleTime::Old);
+        assert_eq!(IdleTime::Old.seconds(), -1);
+        assert_eq!(idle_time(0, now), IdleTime::Unknown);
+        assert_eq!(IdleTime::Unknown.seconds(), -1);
+    }
+
+    #[test]
+    fn idle_time_odd_timestamps() {
+        let now = 1_500_000_000;
+        let (min, max) = (i64::MIN, i64::MAX);
+
+        assert_eq!(idle_time(now + 3600, now), IdleTime::Unknown);
+        assert_eq!(idle_time(0, now), IdleTime::Unknown);
+        assert_eq!(idle_time(1, now), IdleTime::Old);
+        assert_eq!(idle_time(-30, 0), IdleTime::Old);
+        assert_eq!(idle_time(min, now), IdleTime::Unknown);
+        assert_eq!(idle_time(max, -now), IdleTime::Unknown);
+        assert_eq!(idle_seconds(max), None);
+    }
+
+    #[test]
+    fn short_name_strips_dev() {
+        assert_eq!(TTYName(BString::from("/dev/pts/3")).short(), "pts/3");
+        assert_eq!(TTYName(BString::from("/dev/pts/3")).to_string(), "/dev/pts/3");
+        assert_eq!(TTYName(BString::from("console")).short(), "console");
+    }
+
+    #[test]
+    fn from_path_rejects_non_tty() {
+        assert_eq!(TTYName::from_path(Path::new("/dev/null")), Err(Error::NotTTY));
+        assert_eq!(TTYName::from_path(Path::new("/")), Err(Error::NotTTY));
+        assert!(TTYName::from_path(Path::new("/dev/coreutils-no-such-tty")).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn from_path_accepts_pty() {
+        use libc::{close, grantpt, posix_openpt, ptsname, unlockpt, O_RDWR};
+
+        let master = unsafe { posix_openpt(O_RDWR | O_NOCTTY) };
+        if master < 0 {
+            // No pseudo-terminals available on this system
+            return;
+        }
+
+        let slave = unsafe {
+            if grantpt(master) == 0 && unlockpt(master) == 0 {
+                let name = ptsname(master);
+                if name.is_null() { None } else { Some(CStr::from_ptr(name).to_bytes().to_vec()) }
+            } else {
+                None
+            }
+        };
+
+        if let Some(slave) = slave {
+            let tty = TTYName::from_path(Path::new(OsStr::from_bytes(&slave)));
+            assert_eq!(tty.map(|t| t.to_bstring()), Ok(BString::from(slave)));
+        }
+
+        unsafe { close(master) };
+    }
+}
@@ -1,9 +1,25 @@
 //! Module for more widelly used types in this crate and helper functions related to these
 //! times.
+use std::{
+    ffi::{CStr, CString, NulError},
+    fmt::{self, Display},
+    io,
+    ops::BitOr,
+    time::Duration,
+};
+
 #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
 use libc::c_int;
 
-use libc::{getegid, geteuid, getgid, getuid, gid_t, pid_t, time_t, timeval, uid_t};
+use libc::{
+    getegid, geteuid, getgid, getpid, getuid, gid_t, kill, mode_t, pid_t, time_t, timeval, uid_t,
+    EPERM, S_IRGRP, S_IROTH, S_IRUSR, S_ISGID, S_ISUID, S_ISVTX, S_IWGRP, S_IWOTH, S_IWUSR,
+    S_IXGRP, S_IXOTH, S_IXUSR,
+};
+
+use bstr::{BStr, BString};
+
+use crate::time::Timespec;
 
 /// Time stamp type used on system structures
 pub type TimeVal = timeval;
@@ -31,7 +47,107 @@ pub fn get_effective_uid() -> Uid { unsafe { geteuid() } }
 pub fn get_real_uid() -> Uid { unsafe { getuid() } }
 
 /// Process ID type
-pub type Pid = pid_t;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pid(pub pid_t);
+
+impl Pid {
+    /// Get the current running process ID.
+    #[inline]
+    pub fn current() -> Self { Pid(unsafe { getpid() }) }
+
+    /// Check if a process with this ID exists, even if we have no permission to signal it.
+    ///
+    /// IDs that are not positive never exist, since `kill` would take them as process groups.
+    pub fn exists(&self) -> bool {
+        if self.0 <= 0 {
+            return false;
+        }
+
+        let res = unsafe { kill(self.0, 0) };
+
+        res == 0 || io::Error::last_os_error().raw_os_error() == Some(EPERM)
+    }
+
+    /// Check if this is the ID of the current running process.
+    #[inline]
+    pub fn is_current(&self) -> bool { *self == Pid::current() }
+}
+
+impl From<pid_t> for Pid {
+    #[inline]
+    fn from(pid: pid_t) -> Self { Pid(pid) }
+}
+
+impl From<Pid> for pid_t {
+    #[inline]
+    fn from(pid: Pid) -> Self { pid.0 }
+}
+
+impl Display for Pid {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.0) }
+}
+
+/// Permission bits of a file mode, with the set-user-ID, set-group-ID and sticky bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Mode(mode_t);
+
+impl Mode {
+    /// Read permission of the owner.
+    pub const USER_READ: Self = Mode(S_IRUSR);
+    /// Write permission of the owner.
+    pub const USER_WRITE: Self = Mode(S_IWUSR);
+    /// Execute (search, for directories) permission of the owner.
+    pub const USER_EXEC: Self = Mode(S_IXUSR);
+    /// Read permission of the group.
+    pub const GROUP_READ: Self = Mode(S_IRGRP);
+    /// Write permission of the group.
+    pub const GROUP_WRITE: Self = Mode(S_IWGRP);
+    /// Execute (search, for directories) permission of the group.
+    pub const GROUP_EXEC: Self = Mode(S_IXGRP);
+    /// Read permission of the others.
+    pub const OTHER_READ: Self = Mode(S_IROTH);
+    /// Write permission of the others.
+    pub const OTHER_WRITE: Self = Mode(S_IWOTH);
+    /// Execute (search, for directories) permission of the others.
+    pub const OTHER_EXEC: Self = Mode(S_IXOTH);
+    /// Set-user-ID on execution.
+    pub const SET_UID: Self = Mode(S_ISUID);
+    /// Set-group-ID on execution.
+    pub const SET_GID: Self = Mode(S_ISGID);
+    /// Sticky bit.
+    pub const STICKY: Self = Mode(S_ISVTX);
+
+    /// Create a `Mode` from `bits`, dropping the ones that are not permission bits, like the
+    /// file type.
+    #[inline]
+    pub const fn from_bits(bits: mode_t) -> Self { Mode(bits & 0o7777) }
+
+    /// Create a `Mode` from the `st_mode` of a file, like the one returned by
+    /// `MetadataExt::mode`.
+    #[inline]
+    pub fn from_st_mode(st_mode: u32) -> Self {
+        // `mode_t` is smaller than `u32` on some platforms, the permission bits always fit
+        #[allow(clippy::unnecessary_cast)]
+        let bits = (st_mode & 0o7777) as mode_t;
+        Mode(bits)
+    }
+
+    /// Get the raw bits.
+    #[inline]
+    pub const fn bits(self) -> mode_t { self.0 }
+
+    /// Check if every bit of `other` is set.
+    #[inline]
+    pub fn contains(self, other: Self) -> bool { self.0 & other.0 == other.0 }
+}
+
+impl BitOr for Mode {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, other: Self) -> Self { Mode(self.0 | other.0) }
+}
 
 /// `Passwd` time type
 pub type Time = time_t;
@@ -39,3 +155,128 @@ pub type Time = time_t;
 /// `Passwd` field type
 #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
 pub type Fields = c_int;
+
+/// Nanoseconds in a second.
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Get the time elapsed from `a` to `b`, zero if `b` is before `a`, like with a clock set
+/// back between both.
+pub fn duration_between(a: Timespec, b: Timespec) -> Duration {
+    let nanos = |ts: Timespec| i128::from(ts.sec) * NANOS_PER_SEC + i128::from(ts.nsec);
+    let delta = nanos(b) - nanos(a);
+
+    if delta <= 0 {
+        Duration::from_secs(0)
+    } else {
+        Duration::new((delta / NANOS_PER_SEC) as u64, (delta % NANOS_PER_SEC) as u32)
+    }
+}
+
+/// Get the time `secs` seconds after `ts`, or before it if negative, saturating at the
+/// bounds of `Timespec` instead of overflowing.
+pub fn add_seconds(ts: Timespec, secs: i64) -> Timespec {
+    Timespec::new(ts.sec.saturating_add(secs), ts.nsec)
+}
+
+/// Convert `bytes` to a NUL terminated `CString` to pass it to libc.
+///
+/// Fails if `bytes` has a NUL byte, since libc would only see the bytes before it.
+#[inline]
+pub fn bstring_to_cstring(bytes: &BStr) -> Result<CString, NulError> {
+    CString::new(bytes.to_vec())
+}
+
+/// Convert the C string `cstr` to a `BString` with the same bytes, without the NUL terminator.
+#[inline]
+pub fn cstring_to_bstring(cstr: &CStr) -> BString { BString::from(cstr.to_bytes()) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process;
+
+    use bstr::ByteSlice;
+
+    #[test]
+    fn mode_mask_checks() {
+        let mode = Mode::from_st_mode(0o100_644);
+
+        assert_eq!(mode.bits(), 0o644);
+        assert!(mode.contains(Mode::USER_READ | Mode::USER_WRITE));
+        assert!(mode.contains(Mode::GROUP_READ));
+        assert!(!mode.contains(Mode::GROUP_WRITE));
+        assert!(!mode.contains(Mode::USER_EXEC | Mode::USER_READ));
+        assert!(mode.contains(Mode::default()));
+
+        // A terminal with mesg y
+        let tty = Mode::from_st_mode(0o020_620);
+        assert!(tty.contains(Mode::GROUP_WRITE));
+        assert!(!tty.contains(Mode::OTHER_WRITE));
+
+        let special = Mode::from_bits(0o7000);
+        assert!(special.contains(Mode::SET_UID | Mode::SET_GID | Mode::STICKY));
+        assert!(!special.contains(Mode::OTHER_EXEC));
+    }
+
+    #[test]
+    fn cstring_conversions_round_trip() {
+        let bytes = b"user\xff\xfe".as_bstr();
+        let cstring = bstring_to_cstring(bytes).unwrap();
+
+        assert_eq!(cstring.as_bytes_with_nul(), b"user\xff\xfe\0");
+        assert_eq!(cstring_to_bstring(&cstring), bytes);
+        assert_eq!(cstring_to_bstring(&bstring_to_cstring(b"".as_bstr()).unwrap()), "");
+    }
+
+    #[test]
+    fn cstring_of_embedded_nul_fails() {
+        let err = bstring_to_cstring(b"root\0evil".as_bstr()).unwrap_err();
+
+        assert_eq!(err.nul_position(), 4);
+        assert!(bstring_to_cstring(b"\0".as_bstr()).is_err());
+    }
+
+    #[test]
+    fn duration_between_timespecs() {
+        let (a, b) = (Timespec::new(100, 750_000_000), Timespec::new(103, 250_000_000));
+
+        assert_eq!(duration_between(a, b), Duration::new(2, 500_000_000));
+        assert_eq!(duration_between(a, a), Duration::from_secs(0));
+        // Negative deltas are clamped
+        assert_eq!(duration_between(b, a), Duration::from_secs(0));
+        assert_eq!(duration_between(Timespec::new(-5, 0), Timespec::new(5, 0)).as_secs(), 10);
+
+        let (min, max) = (Timespec::new(i64::MIN, 0), Timespec::new(i64::MAX, 0));
+        assert_eq!(duration_between(min, max).as_secs(), !0);
+        assert_eq!(duration_between(max, min), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn add_seconds_saturates() {
+        let ts = Timespec::new(1_000, 5);
+
+        assert_eq!(add_seconds(ts, 30), Timespec::new(1_030, 5));
+        assert_eq!(add_seconds(ts, -2_000), Timespec::new(-1_000, 5));
+        assert_eq!(add_seconds(ts, i64::MAX), Timespec::new(i64::MAX, 5));
+        assert_eq!(add_seconds(Timespec::new(-1_000, 0), i64::MIN), Timespec::new(i64::MIN, 0));
+    }
+
+    #[test]
+    fn own_pid_exists() {
+        let pid = Pid(process::id() as pid_t);
+
+        assert!(pid.exists());
+        assert!(pid.is_current());
+    }
+
+    #[test]
+    fn unlikely_pid_does_not_exist() {
+        let pid = Pid(999_999_999);
+
+        assert!(!pid.exists());
+        assert!(!pid.is_current());
+        assert!(!Pid(0).exists());
+        assert!(!Pid(-1).exists());
+    }
+}
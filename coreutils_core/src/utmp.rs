@@ -8,12 +8,14 @@ use std::{
     slice,
 };
 
-use crate::types::Time;
+use crate::{
+    time::{self, Timespec, Tm},
+    types::Time,
+};
 
 use libc::utmp;
 
 use bstr::{BStr, BString, ByteSlice};
-use time::{self, Timespec, Tm};
 
 /// A struct that represents a __user__ account, where user can be humam users or other
 /// parts of the system that requires the usage of account structure, like some daemons
@@ -66,9 +68,41 @@ impl Utmp {
     /// Get the time the entry was created
     pub fn time(&self) -> Time { self.time }
 
+    /// Returns `true` if the entry is of a logged in user.
+    ///
+    /// Special entries (boot, shutdown and time changes) are written by the system with
+    /// special line names, and entries of logged out users have a empty name.
+    pub fn is_user_process(&self) -> bool {
+        !self.user.is_empty() && !self.is_boot_time() && !self.is_new_time() && !self.is_old_time()
+    }
+
+    /// Returns `true` if the entry is the time of a system boot.
+    pub fn is_boot_time(&self) -> bool { self.line == "~" && self.user == "reboot" }
+
+    /// Always `false`, OpenBSD doesn't record dead processes.
+    pub fn is_dead_process(&self) -> bool { false }
+
+    /// Always `false`, OpenBSD doesn't record login processes.
+    pub fn is_login_process(&self) -> bool { false }
+
+    /// Always `false`, OpenBSD doesn't record run levels.
+    pub fn is_run_level(&self) -> bool { false }
+
+    /// Always `false`, OpenBSD doesn't record processes spawned by init(8).
+    pub fn is_init_process(&self) -> bool { false }
+
+    /// Returns `true` if the entry is the time after a system clock change.
+    pub fn is_new_time(&self) -> bool { self.line == "{" }
+
+    /// Returns `true` if the entry is the time before a system clock change.
+    pub fn is_old_time(&self) -> bool { self.line == "|" }
+
+    /// Get the time where the entry was created (often login time) as a `Timespec`
+    pub fn login_timespec(&self) -> Timespec { Timespec::new(self.time, 0) }
+
     /// Get the time where the entry was created (often login time) in a more complete
     /// structure
-    pub fn login_time(&self) -> Tm { time::at(Timespec::new(self.time, 0)) }
+    pub fn login_time(&self) -> Tm { time::to_local(self.login_timespec()) }
 }
 
 #[derive(Debug)]
@@ -1,19 +1,26 @@
 //! Extended account database module
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::{
-    collections::{hash_set, HashSet},
-    io,
+    collections::{hash_set, BTreeMap, HashMap, HashSet},
+    error::Error as StdError,
+    fmt::{self, Display},
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    iter::FromIterator,
+    mem,
     path::Path,
-};
-#[cfg(not(any(target_os = "linux", target_os = "macos")))]
-use std::{
-    fs::{self, File},
-    io::{BufReader, Read},
-    mem, slice,
+    ptr, slice, str, thread,
+    time::Duration,
 };
 
-use crate::types::{Pid, TimeVal};
+use crate::{
+    consts::{DEFAULT_BTMP_PATH, HAS_UT_SESSION},
+    time::{self, Timespec, Tm},
+    types::{Pid, TimeVal},
+};
 
 #[cfg(target_os = "linux")]
 use libc::__exit_status;
@@ -25,11 +32,11 @@ use libc::c_long;
 use libc::c_short;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use libc::utmpxname;
-use libc::{endutxent, getutxent, setutxent, suseconds_t, time_t, utmpx};
+use libc::{c_char, endutxent, getutxent, pid_t, setutxent, suseconds_t, time_t, utmpx};
 
 use bstr::{BStr, BString, ByteSlice};
-
-use time::{Timespec, Tm};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 /// Possible types of a `Utmpx` instance
 #[repr(u16)]
@@ -61,6 +68,31 @@ pub enum UtmpxType {
     UserProcess,
     /// Invalid entry
     Invalid,
+    /// A type not known on this platform, with its raw value.
+    Unknown(i16),
+}
+
+impl Display for UtmpxType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Accounting => "ACCOUNTING",
+            Self::BootTime => "BOOT_TIME",
+            Self::DeadProcess => "DEAD_PROCESS",
+            Self::Empty => "EMPTY",
+            Self::InitProcess => "INIT_PROCESS",
+            Self::LoginProcess => "LOGIN_PROCESS",
+            Self::NewTime => "NEW_TIME",
+            Self::OldTime => "OLD_TIME",
+            Self::RunLevel => "RUN_LVL",
+            Self::Signature => "SIGNATURE",
+            Self::ShutdownProcess => "SHUTDOWN_PROCESS",
+            Self::UserProcess => "USER_PROCESS",
+            Self::Invalid => "INVALID",
+            Self::Unknown(num) => return write!(f, "UNKNOWN({})", num),
+        };
+
+        write!(f, "{}", name)
+    }
 }
 
 #[cfg(target_os = "freebsd")]
@@ -76,7 +108,7 @@ impl From<c_short> for UtmpxType {
             6 => Self::LoginProcess,
             7 => Self::DeadProcess,
             8 => Self::ShutdownProcess,
-            _ => Self::Invalid,
+            _ => Self::Unknown(num),
         }
     }
 }
@@ -100,7 +132,7 @@ impl From<c_short> for UtmpxType {
             10 => Self::Signature,
             #[cfg(target_os = "macos")]
             11 => Self::ShutdownProcess,
-            _ => Self::Invalid,
+            _ => Self::Unknown(num),
         }
     }
 }
@@ -118,7 +150,67 @@ impl From<u16> for UtmpxType {
             6 => Self::LoginProcess,
             7 => Self::UserProcess,
             8 => Self::DeadProcess,
-            _ => Self::Invalid,
+            _ => Self::Unknown(num as i16),
+        }
+    }
+}
+
+#[cfg(target_os = "freebsd")]
+impl From<UtmpxType> for c_short {
+    fn from(utype: UtmpxType) -> Self {
+        match utype {
+            UtmpxType::BootTime => 1,
+            UtmpxType::OldTime => 2,
+            UtmpxType::NewTime => 3,
+            UtmpxType::UserProcess => 4,
+            UtmpxType::InitProcess => 5,
+            UtmpxType::LoginProcess => 6,
+            UtmpxType::DeadProcess => 7,
+            UtmpxType::ShutdownProcess => 8,
+            UtmpxType::Unknown(num) => num,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "netbsd", target_os = "dragonfly", target_os = "freebsd")))]
+impl From<UtmpxType> for c_short {
+    fn from(utype: UtmpxType) -> Self {
+        match utype {
+            UtmpxType::RunLevel => 1,
+            UtmpxType::BootTime => 2,
+            UtmpxType::NewTime => 3,
+            UtmpxType::OldTime => 4,
+            UtmpxType::InitProcess => 5,
+            UtmpxType::LoginProcess => 6,
+            UtmpxType::UserProcess => 7,
+            UtmpxType::DeadProcess => 8,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            UtmpxType::Accounting => 9,
+            #[cfg(target_os = "macos")]
+            UtmpxType::Signature => 10,
+            #[cfg(target_os = "macos")]
+            UtmpxType::ShutdownProcess => 11,
+            UtmpxType::Unknown(num) => num,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(any(target_os = "netbsd", target_os = "dragonfly"))]
+impl From<UtmpxType> for u16 {
+    fn from(utype: UtmpxType) -> Self {
+        match utype {
+            UtmpxType::RunLevel => 1,
+            UtmpxType::BootTime => 2,
+            UtmpxType::NewTime => 3,
+            UtmpxType::OldTime => 4,
+            UtmpxType::InitProcess => 5,
+            UtmpxType::LoginProcess => 6,
+            UtmpxType::UserProcess => 7,
+            UtmpxType::DeadProcess => 8,
+            UtmpxType::Unknown(num) => num as u16,
+            _ => 0,
         }
     }
 }
@@ -157,41 +249,45 @@ pub struct Utmpx {
 }
 
 impl Utmpx {
+    /// Creates a new `Utmpx` entry from its parts.
+    ///
+    /// The fields that only exist in some platforms are zeroed.
+    pub fn new(
+        ut_type: UtmpxType, pid: Pid, line: &BStr, id: &BStr, user: &BStr, host: &BStr,
+        timeval: TimeVal,
+    ) -> Self {
+        Utmpx {
+            user: BString::from(user.as_bytes()),
+            host: BString::from(host.as_bytes()),
+            pid,
+            id: BString::from(id.as_bytes()),
+            line: BString::from(line.as_bytes()),
+            ut_type,
+            timeval,
+            #[cfg(target_os = "linux")]
+            exit: __exit_status { e_termination: 0, e_exit: 0 },
+            #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly"))]
+            session: 0,
+            #[cfg(target_os = "linux")]
+            addr_v6: [0; 4],
+        }
+    }
+
     /// Creates a new `Utmpx` entry from the `C` version of the structure
     pub fn from_c_utmpx(utm: utmpx) -> Self {
         #[cfg(not(any(target_os = "dragonfly")))]
-        let user = {
-            let cstr: String =
-                utm.ut_user.iter().map(|cc| *cc as u8 as char).filter(|cc| cc != &'\0').collect();
-            BString::from(cstr.as_bytes())
-        };
+        let user = from_c_array(&utm.ut_user);
 
         #[cfg(any(target_os = "dragonfly"))]
-        let user = {
-            let cstr: String =
-                utm.ut_name.iter().map(|cc| *cc as u8 as char).filter(|cc| cc != &'\0').collect();
-            BString::from(cstr.as_bytes())
-        };
+        let user = from_c_array(&utm.ut_name);
 
-        let host = {
-            let cstr: String =
-                utm.ut_host.iter().map(|cc| *cc as u8 as char).filter(|cc| cc != &'\0').collect();
-            BString::from(cstr.as_bytes())
-        };
+        let host = from_c_array(&utm.ut_host);
 
-        let pid = utm.ut_pid;
+        let pid = Pid(utm.ut_pid);
 
-        let id = {
-            let cstr: String =
-                utm.ut_id.iter().map(|cc| *cc as u8 as char).filter(|cc| cc != &'\0').collect();
-            BString::from(cstr.as_bytes())
-        };
+        let id = from_c_array(&utm.ut_id);
 
-        let line = {
-            let cstr: String =
-                utm.ut_line.iter().map(|cc| *cc as u8 as char).filter(|cc| cc != &'\0').collect();
-            BString::from(cstr.as_bytes())
-        };
+        let line = from_c_array(&utm.ut_line);
 
         let ut_type = UtmpxType::from(utm.ut_type);
 
@@ -226,6 +322,40 @@ impl Utmpx {
         }
     }
 
+    /// Creates the `C` version of the structure from this `Utmpx` entry.
+    ///
+    /// Fields longer than their `C` counterpart are truncated.
+    pub fn to_c_utmpx(&self) -> utmpx {
+        // Zeroed so every field we don't set (including padding) is NUL
+        let mut utm: utmpx = unsafe { mem::zeroed() };
+
+        #[cfg(not(target_os = "dragonfly"))]
+        copy_to_c_array(&mut utm.ut_user, &self.user);
+        #[cfg(target_os = "dragonfly")]
+        copy_to_c_array(&mut utm.ut_name, &self.user);
+        copy_to_c_array(&mut utm.ut_host, &self.host);
+        copy_to_c_array(&mut utm.ut_id, &self.id);
+        copy_to_c_array(&mut utm.ut_line, &self.line);
+
+        utm.ut_pid = self.pid.into();
+        utm.ut_type = self.ut_type.into();
+        utm.ut_tv.tv_sec = self.timeval.tv_sec as _;
+        utm.ut_tv.tv_usec = self.timeval.tv_usec as _;
+
+        #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly"))]
+        {
+            utm.ut_session = self.session;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            utm.ut_exit = self.exit;
+            utm.ut_addr_v6 = self.addr_v6;
+        }
+
+        utm
+    }
+
     /// Get user name
     pub fn user(&self) -> &BStr { self.user.as_bstr() }
 
@@ -244,14 +374,53 @@ impl Utmpx {
     /// Get the type kind if the entry
     pub fn utype(&self) -> UtmpxType { self.ut_type }
 
+    /// Returns `true` if the entry is of a user process (a logged in user).
+    #[inline]
+    pub fn is_user_process(&self) -> bool { self.ut_type == UtmpxType::UserProcess }
+
+    /// Returns `true` if the entry is the time of a system boot.
+    #[inline]
+    pub fn is_boot_time(&self) -> bool { self.ut_type == UtmpxType::BootTime }
+
+    /// Returns `true` if the entry is of a session leader that exited.
+    #[inline]
+    pub fn is_dead_process(&self) -> bool { self.ut_type == UtmpxType::DeadProcess }
+
+    /// Returns `true` if the entry is of a session leader of a logged in user.
+    #[inline]
+    pub fn is_login_process(&self) -> bool { self.ut_type == UtmpxType::LoginProcess }
+
+    /// Returns `true` if the entry is a run level change.
+    #[inline]
+    pub fn is_run_level(&self) -> bool { self.ut_type == UtmpxType::RunLevel }
+
+    /// Returns `true` if the entry is of a process spawned by init(8).
+    #[inline]
+    pub fn is_init_process(&self) -> bool { self.ut_type == UtmpxType::InitProcess }
+
+    /// Returns `true` if the entry is the time after a system clock change.
+    #[inline]
+    pub fn is_new_time(&self) -> bool { self.ut_type == UtmpxType::NewTime }
+
+    /// Returns `true` if the entry is the time before a system clock change.
+    #[inline]
+    pub fn is_old_time(&self) -> bool { self.ut_type == UtmpxType::OldTime }
+
     /// Get the time where the entry was created (often login time)
     pub fn timeval(&self) -> TimeVal { self.timeval }
 
+    /// Get the time where the entry was created (often login time) as a `Timespec`,
+    /// keeping the microseconds of the entry, clamped to a second on corrupted entries.
+    // `time_t` and `suseconds_t` are `i64` on Linux, where the casts do nothing
+    #[allow(clippy::unnecessary_cast)]
+    pub fn login_timespec(&self) -> Timespec {
+        let usec = (self.timeval.tv_usec as i64).clamp(0, 999_999);
+        Timespec::new(self.timeval.tv_sec as i64, (usec * 1000) as i32)
+    }
+
     /// Get the time where the entry was created (often login time) in a more complete
     /// structure
-    pub fn login_time(&self) -> Tm {
-        time::at(Timespec::new(self.timeval.tv_sec as i64, self.timeval.tv_usec as i32))
-    }
+    pub fn login_time(&self) -> Tm { time::to_local(self.login_timespec()) }
 
     /// Get the session ID
     #[cfg(all(target_os = "linux", any(target_arch = "x86_64")))]
@@ -265,8 +434,196 @@ impl Utmpx {
     #[cfg(any(target_os = "netbsd", target_os = "dragonfly"))]
     pub fn session(&self) -> u16 { self.session }
 
+    /// Get the session ID of the entry, `None` if the entries of the platform don't have
+    /// one (see [`HAS_UT_SESSION`]).
+    ///
+    /// [`HAS_UT_SESSION`]: ../consts/constant.HAS_UT_SESSION.html
+    pub fn session_id(&self) -> Option<i32> {
+        // `c_int` on Linux x86_64, where the cast does nothing
+        #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly"))]
+        #[allow(clippy::unnecessary_cast)]
+        let session = Some(self.session as i32);
+        #[cfg(not(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly")))]
+        let session = None;
+
+        if HAS_UT_SESSION { session } else { None }
+    }
+
+    /// Check if the process of the entry is in the same PID namespace as the current process,
+    /// comparing their `/proc/<pid>/ns/pid`, so sessions of containers, where the process ID
+    /// is misleading, can be told apart.
+    ///
+    /// `None` if the process exited or its namespace can't be read.
+    #[cfg(target_os = "linux")]
+    pub fn in_host_namespace(&self) -> Option<bool> {
+        if self.pid.0 <= 0 {
+            return None;
+        }
+
+        let namespace = fs::read_link(format!("/proc/{}/ns/pid", self.pid.0)).ok()?;
+        let own_namespace = fs::read_link("/proc/self/ns/pid").ok()?;
+
+        Some(namespace == own_namespace)
+    }
+
     #[cfg(target_os = "linux")]
     pub fn v6_addr(&self) -> [i32; 4] { self.addr_v6 }
+
+    /// Get the remote end of the login from the host field, `None` if it is a local login.
+    ///
+    /// See [`RemoteEndpoint::parse`] for the recognized forms.
+    ///
+    /// [`RemoteEndpoint::parse`]: struct.RemoteEndpoint.html#method.parse
+    #[inline]
+    pub fn remote_endpoint(&self) -> Option<RemoteEndpoint> { RemoteEndpoint::parse(self.host()) }
+
+    /// Get the current and previous runlevel of a run level entry, `None` for other entries.
+    ///
+    /// By convention init stores the current runlevel on the low byte of the PID and the
+    /// previous one on the next byte, a previous runlevel of 0 meaning there was none and
+    /// being reported as `'N'`.
+    pub fn run_level(&self) -> Option<(char, char)> {
+        if !self.is_run_level() {
+            return None;
+        }
+
+        let (current, previous) = ((self.pid.0 & 0xff) as u8, ((self.pid.0 >> 8) & 0xff) as u8);
+        if current == 0 {
+            return None;
+        }
+        let previous = if previous == 0 { 'N' } else { char::from(previous) };
+
+        Some((char::from(current), previous))
+    }
+
+    /// Get the IP address of the remote host, if the entry has one.
+    ///
+    /// The address is a IPv4 address if only the first element of the raw address is set.
+    #[cfg(target_os = "linux")]
+    pub fn address(&self) -> Option<IpAddr> {
+        // The raw address is stored in network byte order
+        let mut bytes = [0; 16];
+        for (chunk, part) in bytes.chunks_mut(4).zip(self.addr_v6.iter()) {
+            chunk.copy_from_slice(&part.to_ne_bytes());
+        }
+
+        match self.addr_v6 {
+            [0, 0, 0, 0] => None,
+            [_, 0, 0, 0] => Some(IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))),
+            _ => Some(IpAddr::V6(Ipv6Addr::from(bytes))),
+        }
+    }
+}
+
+/// The remote end of a login, as recorded in the host field of its entry by `sshd`(8) and
+/// other remote login daemons: `host`, `host:port` or `[host]:port`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemoteEndpoint {
+    host: BString,
+    port: Option<u16>,
+}
+
+impl RemoteEndpoint {
+    /// Parse the host field `host` of a entry, `None` if it is empty or a local X display,
+    /// like `:0`.
+    ///
+    /// This is best effort: a IPv6 address without brackets has no port, and the X displays
+    /// with a screen number, like `host:0.0`, are taken as `host`, but `host:0` has the port
+    /// `0`.
+    pub fn parse(host: &BStr) -> Option<Self> {
+        if host.is_empty() || host.starts_with(b":") {
+            return None;
+        }
+
+        let (name, port): (&[u8], Option<&[u8]>) = if host.starts_with(b"[") {
+            match host.find_byte(b']') {
+                Some(end) if host.get(end + 1) == Some(&b':') => {
+                    (&host[1..end], Some(&host[end + 2..]))
+                },
+                Some(end) => (&host[1..end], None),
+                None => (host.as_bytes(), None),
+            }
+        } else {
+            match host.find_byte(b':') {
+                // More than one `:` is a IPv6 address
+                Some(colon) if host.rfind_byte(b':') == Some(colon) => {
+                    (&host[..colon], Some(&host[colon + 1..]))
+                },
+                _ => (host.as_bytes(), None),
+            }
+        };
+        let port = port.and_then(|port| port.to_str().ok()).and_then(|port| port.parse().ok());
+
+        Some(RemoteEndpoint { host: BString::from(name), port })
+    }
+
+    /// Get the host name or address.
+    #[inline]
+    pub fn host(&self) -> &BStr { self.host.as_bstr() }
+
+    /// Get the port, if it was recorded.
+    #[inline]
+    pub fn port(&self) -> Option<u16> { self.port }
+}
+
+impl Display for RemoteEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.port {
+            Some(port) if self.host.contains(&b':') => write!(f, "[{}]:{}", self.host, port),
+            Some(port) => write!(f, "{}:{}", self.host, port),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
+
+/// Errors reading utmpx entries from a file or a buffer.
+#[derive(Debug)]
+pub enum UtmpxError {
+    /// Happens when reading the file fails.
+    Io(io::Error),
+    /// Happens when the data length is not a multiple of the entry size.
+    ///
+    /// It holds the offset of the trailing partial entry.
+    Parse(usize),
+}
+
+impl Display for UtmpxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UtmpxError::Io(err) => write!(f, "{}", err),
+            UtmpxError::Parse(offset) => {
+                write!(f, "partial utmpx entry at offset {}, the data may be corrupted", offset)
+            },
+        }
+    }
+}
+
+impl StdError for UtmpxError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            UtmpxError::Io(err) => Some(err),
+            UtmpxError::Parse(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for UtmpxError {
+    fn from(err: io::Error) -> Self { UtmpxError::Io(err) }
+}
+
+impl From<UtmpxError> for io::Error {
+    fn from(err: UtmpxError) -> Self {
+        match err {
+            UtmpxError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// Check that `len` bytes hold only whole utmpx entries.
+fn check_length(len: usize) -> Result<(), UtmpxError> {
+    let partial = len % mem::size_of::<utmpx>();
+    if partial == 0 { Ok(()) } else { Err(UtmpxError::Parse(len - partial)) }
 }
 
 /// A collection of Utmpx entries
@@ -277,6 +634,11 @@ impl UtmpxSet {
     /// Creates a new collection over a utmpx entry binary file
     #[cfg(any(target_os = "linux", target_os = "macos"))]
     pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        // utmpxname(3) doesn't open the file, and getutxent(3) can't tell a missing file
+        // from a empty one, nor a corrupted one from a valid one, so the file is checked first
+        let len = OpenOptions::new().read(true).open(path.as_ref())?.metadata()?.len();
+        check_length(len as usize)?;
+
         let file = {
             let str = match path.as_ref().to_str() {
                 Some(s) => s,
@@ -313,47 +675,83 @@ impl UtmpxSet {
     /// Creates a new collection over a utmpx entry binary file
     #[cfg(not(any(target_os = "linux", target_os = "macos")))]
     pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        Ok(Self::from_bytes(&bytes)?)
+    }
+
+    /// Creates a new collection over the utmpx entries in `bytes`.
+    ///
+    /// Fails if `bytes` ends with a partial entry.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, UtmpxError> {
+        check_length(bytes.len())?;
+
         let struct_size = mem::size_of::<utmpx>();
-        let num_bytes = fs::metadata(&path)?.len() as usize;
-        let num_structs = num_bytes / struct_size;
-        let mut reader = BufReader::new(File::open(&path)?);
-        let mut vec = Vec::with_capacity(num_structs);
-        let mut set = HashSet::with_capacity(num_structs);
+        let set = bytes
+            .chunks_exact(struct_size)
+            .map(|chunk| {
+                let mut utm: utmpx = unsafe { mem::zeroed() };
+                // `chunk` has exactly the size of `utm`, and every field of it is a integer or
+                // a array of them, so any bytes make a valid entry
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        chunk.as_ptr(),
+                        &mut utm as *mut utmpx as *mut u8,
+                        struct_size,
+                    );
+                }
+                Utmpx::from_c_utmpx(utm)
+            })
+            .collect();
 
-        unsafe {
-            let mut buffer = slice::from_raw_parts_mut(vec.as_mut_ptr() as *mut u8, num_bytes);
-            reader.read_exact(&mut buffer)?;
-            vec.set_len(num_structs);
-        }
+        Ok(UtmpxSet(set))
+    }
+
+    /// Reads every entry of a utmp entry binary file whose entries are in `layout`, like a
+    /// file captured on another platform, in the order they are stored, duplicates included.
+    pub fn from_file_with_layout(
+        path: impl AsRef<Path>, layout: UtmpLayout,
+    ) -> io::Result<Vec<Utmpx>> {
+        let bytes = fs::read(path)?;
+        Ok(Self::from_bytes_with_layout(&bytes, layout)?)
+    }
 
-        for raw_utm in vec {
-            set.insert(Utmpx::from_c_utmpx(raw_utm));
+    /// Reads every utmp entry in `bytes`, in the `layout` of the platform that wrote them, in
+    /// the order they are stored, duplicates included.
+    ///
+    /// Fails if `bytes` ends with a partial entry.
+    pub fn from_bytes_with_layout(
+        bytes: &[u8], layout: UtmpLayout,
+    ) -> Result<Vec<Utmpx>, UtmpxError> {
+        let partial = bytes.len() % layout.size;
+        if partial != 0 {
+            return Err(UtmpxError::Parse(bytes.len() - partial));
         }
 
-        Ok(UtmpxSet(set))
+        Ok(bytes.chunks_exact(layout.size).map(|record| layout.parse(record)).collect())
+    }
+
+    /// Creates a new collection over a utmpx entry binary stream, like a pipe, reading it
+    /// until the end.
+    pub fn from_reader(reader: impl Read) -> io::Result<Self> {
+        UtmpxReader::new(reader).collect()
     }
 
     /// Creates a new collection geting all entries from the running system
-    pub fn system() -> Self {
-        let mut set = HashSet::new();
+    pub fn system() -> Self { system_entries().into_iter().collect() }
 
-        unsafe {
-            setutxent();
+    /// Overlay the `live` snapshot of a utmp file onto the `history` of a wtmp file, usually
+    /// its last entries, so the sessions closed recently are kept next to the live ones.
+    ///
+    /// Entries with the same line, process ID and login time are the same session, and only
+    /// one of them is kept: the one of `live` if both have it, since it is the current state
+    /// of the session.
+    pub fn merge(live: &UtmpxSet, history: &UtmpxSet) -> UtmpxSet {
+        let key = |utm: &Utmpx| (utm.line.clone(), utm.pid, utm.login_timespec());
 
-            loop {
-                let ut = getutxent();
-                if ut.is_null() {
-                    break;
-                } else {
-                    let utm = Utmpx::from_c_utmpx(*ut);
-                    set.insert(utm);
-                }
-            }
-
-            endutxent();
-        }
+        let mut merged: HashMap<_, &Utmpx> = history.iter().map(|utm| (key(utm), utm)).collect();
+        merged.extend(live.iter().map(|utm| (key(utm), utm)));
 
-        UtmpxSet(set)
+        merged.values().map(|&utm| utm.clone()).collect()
     }
 
     /// Returns `true` if collection nas no elements
@@ -373,3 +771,986 @@ impl IntoIterator for UtmpxSet {
     #[inline]
     fn into_iter(self) -> Self::IntoIter { self.0.into_iter() }
 }
+
+impl FromIterator<Utmpx> for UtmpxSet {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = Utmpx>>(iter: I) -> Self {
+        UtmpxSet(iter.into_iter().collect())
+    }
+}
+
+/// A change of the logged in users between two snapshots of a utmp file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UtmpEvent {
+    /// A user process entry appeared.
+    Login(Utmpx),
+    /// A user process entry disappeared, usually replaced by a dead process entry.
+    Logout(Utmpx),
+}
+
+/// The user, line, process ID and login time identifying a login session.
+type SessionKey = (BString, BString, Pid, Timespec);
+
+/// Tracker of the login sessions in successive snapshots of a utmp file, reporting the
+/// logins and logouts between each of them.
+///
+/// A session is identified by its user, line, process ID and login time, so its other
+/// fields changing is not reported.
+#[derive(Debug, Clone, Default)]
+pub struct UtmpWatcher {
+    sessions: HashMap<SessionKey, Utmpx>,
+}
+
+impl UtmpWatcher {
+    /// Create a watcher without sessions, so every session of the first snapshot is reported
+    /// as a login.
+    pub fn new() -> Self { Self::default() }
+
+    /// Create a watcher starting at `snapshot`, so only the changes after it are reported.
+    pub fn from_snapshot(snapshot: &UtmpxSet) -> Self {
+        UtmpWatcher { sessions: user_sessions(snapshot) }
+    }
+
+    /// Compare `snapshot` with the previous one, calling `on_event` with every logout, then
+    /// every login, each in login time order.
+    pub fn update(&mut self, snapshot: &UtmpxSet, mut on_event: impl FnMut(UtmpEvent)) {
+        let sessions = user_sessions(snapshot);
+        let missing_from = |from: &HashMap<SessionKey, Utmpx>, of: &HashMap<SessionKey, Utmpx>| {
+            let mut missing: Vec<_> =
+                of.iter().filter(|(key, _)| !from.contains_key(key)).map(|(_, utm)| utm).collect();
+            missing.sort_by_key(|utm| (utm.login_timespec(), utm.device_name()));
+            missing.into_iter().cloned().collect::<Vec<_>>()
+        };
+
+        let logouts = missing_from(&sessions, &self.sessions);
+        let logins = missing_from(&self.sessions, &sessions);
+        logouts.into_iter().map(UtmpEvent::Logout).for_each(&mut on_event);
+        logins.into_iter().map(UtmpEvent::Login).for_each(&mut on_event);
+
+        self.sessions = sessions;
+    }
+}
+
+/// Get the user process entries of `snapshot` by their session.
+fn user_sessions(snapshot: &UtmpxSet) -> HashMap<SessionKey, Utmpx> {
+    snapshot
+        .iter()
+        .filter(|utm| utm.is_user_process())
+        .map(|utm| {
+            let key = (utm.user.clone(), utm.line.clone(), utm.pid, utm.login_timespec());
+            (key, utm.clone())
+        })
+        .collect()
+}
+
+/// Watch the utmp file at `path`, reading it every `interval` and calling `on_event` with
+/// the logins and logouts since the previous read, like `who --follow` shows them.
+///
+/// The sessions already there on the first read are not reported. It only returns when
+/// reading the file fails, with that error.
+pub fn watch(
+    path: impl AsRef<Path>, interval: Duration, mut on_event: impl FnMut(UtmpEvent),
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut watcher = UtmpWatcher::from_snapshot(&UtmpxSet::from_file(path)?);
+
+    loop {
+        thread::sleep(interval);
+        watcher.update(&UtmpxSet::from_file(path)?, &mut on_event);
+    }
+}
+
+/// A field of a entry on a utmp file, as its offset and size in bytes.
+type Field = (usize, usize);
+
+/// How the time of a entry is stored on a utmp file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TimeField {
+    /// Seconds and microseconds since epoch, each on its own field.
+    Timeval(Field, Field),
+    /// Microseconds since epoch.
+    Micros(Field),
+    /// Seconds since epoch.
+    Seconds(Field),
+}
+
+/// Byte order of the integer fields of a utmp file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// Layout of the entries of the utmp files of a platform, with the offset and size of every
+/// field, so the files captured on a platform can be read on any other.
+///
+/// Fields a platform doesn't have are left empty or zeroed on the entries read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UtmpLayout {
+    size:       usize,
+    endianness: Endianness,
+    ut_type:    Option<(Field, TypeValues)>,
+    pid:        Option<Field>,
+    id:         Option<Field>,
+    line:       Field,
+    user:       Field,
+    host:       Field,
+    time:       TimeField,
+    #[cfg_attr(
+        not(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly")),
+        allow(dead_code)
+    )]
+    session:    Option<Field>,
+    /// The four 32 bits words of the address.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    addr_v6:    Option<Field>,
+}
+
+impl UtmpLayout {
+    /// The `struct futx` of the FreeBSD `utx.active` and `utx.lastlogin` files, packed and
+    /// big-endian. The records of `utx.log` are stored with their length and without the
+    /// trailing zeros, so they can't be read with it.
+    pub const FREEBSD: UtmpLayout = UtmpLayout {
+        size:       197,
+        endianness: Endianness::Big,
+        ut_type:    Some(((0, 1), TypeValues::FreeBSD)),
+        pid:        Some((17, 4)),
+        id:         Some((9, 8)),
+        line:       (53, 16),
+        user:       (21, 32),
+        host:       (69, 128),
+        time:       TimeField::Micros((1, 8)),
+        session:    None,
+        addr_v6:    None,
+    };
+    /// The `struct utmp` of 64 bits Linux with glibc, which is also its `struct utmpx`.
+    pub const LINUX: UtmpLayout = UtmpLayout {
+        size:       384,
+        endianness: Endianness::Little,
+        ut_type:    Some(((0, 2), TypeValues::Linux)),
+        pid:        Some((4, 4)),
+        id:         Some((40, 4)),
+        line:       (8, 32),
+        user:       (44, 32),
+        host:       (76, 256),
+        time:       TimeField::Timeval((340, 4), (344, 4)),
+        session:    Some((336, 4)),
+        addr_v6:    Some((348, 16)),
+    };
+    /// The `struct utmpx` of 64 bits MacOS.
+    pub const MACOS: UtmpLayout = UtmpLayout {
+        size:       640,
+        endianness: Endianness::Little,
+        ut_type:    Some(((296, 2), TypeValues::MacOS)),
+        pid:        Some((292, 4)),
+        id:         Some((256, 4)),
+        line:       (260, 32),
+        user:       (0, 256),
+        host:       (320, 256),
+        time:       TimeField::Timeval((304, 8), (312, 4)),
+        session:    None,
+        addr_v6:    None,
+    };
+    /// The `struct utmp` of OpenBSD, that has no type, so entries with a user are user
+    /// processes and the others dead processes.
+    pub const OPENBSD: UtmpLayout = UtmpLayout {
+        size:       304,
+        endianness: Endianness::Little,
+        ut_type:    None,
+        pid:        None,
+        id:         None,
+        line:       (0, 8),
+        user:       (8, 32),
+        host:       (40, 256),
+        time:       TimeField::Seconds((296, 8)),
+        session:    None,
+        addr_v6:    None,
+    };
+
+    /// Get the size of a entry.
+    pub fn size(&self) -> usize { self.size }
+
+    /// Get the byte order of the integer fields.
+    pub fn endianness(&self) -> Endianness { self.endianness }
+
+    /// Get the same layout with the integer fields in `endianness`, like for the files of a
+    /// big-endian Linux host.
+    pub fn with_endianness(self, endianness: Endianness) -> Self {
+        UtmpLayout { endianness, ..self }
+    }
+
+    /// Read the entry in `record`, that must be `self.size()` bytes long.
+    fn parse(&self, record: &[u8]) -> Utmpx {
+        let int = |(offset, size): Field| {
+            let bytes = &record[offset..offset + size];
+            let mut num = 0u64;
+            for i in 0..size {
+                let byte = match self.endianness {
+                    Endianness::Big => bytes[i],
+                    Endianness::Little => bytes[size - 1 - i],
+                };
+                num = (num << 8) | u64::from(byte);
+            }
+            // Sign extend the fields smaller than 8 bytes
+            let shift = 64 - 8 * size as u32;
+            ((num << shift) as i64) >> shift
+        };
+        let text = |(offset, size): Field| {
+            let bytes = &record[offset..offset + size];
+            bytes[..bytes.find_byte(0).unwrap_or(size)].as_bstr()
+        };
+
+        let user = text(self.user);
+        let ut_type = match self.ut_type {
+            Some((field, values)) => values.utype(int(field)),
+            None if user.is_empty() => UtmpxType::DeadProcess,
+            None => UtmpxType::UserProcess,
+        };
+        let (sec, usec) = match self.time {
+            TimeField::Timeval(sec, usec) => (int(sec), int(usec)),
+            TimeField::Micros(micros) => (int(micros) / 1_000_000, int(micros) % 1_000_000),
+            TimeField::Seconds(sec) => (int(sec), 0),
+        };
+        let timeval = TimeVal { tv_sec: sec as time_t, tv_usec: usec as suseconds_t };
+
+        #[allow(unused_mut)]
+        let mut utm = Utmpx::new(
+            ut_type,
+            Pid(self.pid.map(int).unwrap_or(0) as pid_t),
+            text(self.line),
+            self.id.map(text).unwrap_or_default(),
+            user,
+            text(self.host),
+            timeval,
+        );
+
+        #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "dragonfly"))]
+        {
+            utm.session = self.session.map(int).unwrap_or(0) as _;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            // The address is in network byte order whatever the endianness of the layout
+            if let Some((offset, _)) = self.addr_v6 {
+                for (i, word) in utm.addr_v6.iter_mut().enumerate() {
+                    let start = offset + 4 * i;
+                    let mut raw = [0; 4];
+                    raw.copy_from_slice(&record[start..start + 4]);
+                    *word = i32::from_ne_bytes(raw);
+                }
+            }
+        }
+
+        utm
+    }
+}
+
+/// The values of the entry types of a platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TypeValues {
+    FreeBSD,
+    Linux,
+    MacOS,
+}
+
+impl TypeValues {
+    /// Get the type of the value `num`.
+    fn utype(self, num: i64) -> UtmpxType {
+        match (self, num) {
+            (_, 0) => UtmpxType::Empty,
+            (TypeValues::FreeBSD, 1) => UtmpxType::BootTime,
+            (TypeValues::FreeBSD, 2) => UtmpxType::OldTime,
+            (TypeValues::FreeBSD, 3) => UtmpxType::NewTime,
+            (TypeValues::FreeBSD, 4) => UtmpxType::UserProcess,
+            (TypeValues::FreeBSD, 5) => UtmpxType::InitProcess,
+            (TypeValues::FreeBSD, 6) => UtmpxType::LoginProcess,
+            (TypeValues::FreeBSD, 7) => UtmpxType::DeadProcess,
+            (TypeValues::FreeBSD, 8) => UtmpxType::ShutdownProcess,
+            (TypeValues::FreeBSD, _) => UtmpxType::Unknown(num as i16),
+            (_, 1) => UtmpxType::RunLevel,
+            (_, 2) => UtmpxType::BootTime,
+            (TypeValues::Linux, 3) | (TypeValues::MacOS, 4) => UtmpxType::NewTime,
+            (TypeValues::Linux, 4) | (TypeValues::MacOS, 3) => UtmpxType::OldTime,
+            (_, 5) => UtmpxType::InitProcess,
+            (_, 6) => UtmpxType::LoginProcess,
+            (_, 7) => UtmpxType::UserProcess,
+            (_, 8) => UtmpxType::DeadProcess,
+            (_, 9) => UtmpxType::Accounting,
+            (TypeValues::MacOS, 10) => UtmpxType::Signature,
+            (TypeValues::MacOS, 11) => UtmpxType::ShutdownProcess,
+            _ => UtmpxType::Unknown(num as i16),
+        }
+    }
+}
+
+/// A iterator over the entries of a utmpx entry binary stream, reading one entry at a time,
+/// so it works on streams that can't be seeked.
+#[derive(Debug)]
+pub struct UtmpxReader<R> {
+    reader: R,
+}
+
+impl<R: Read> UtmpxReader<R> {
+    /// Creates a new iterator over the entries read from `reader`
+    pub fn new(reader: R) -> Self { UtmpxReader { reader } }
+}
+
+impl<R: Read> Iterator for UtmpxReader<R> {
+    type Item = io::Result<Utmpx>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut utm: utmpx = unsafe { mem::zeroed() };
+        let buffer = unsafe {
+            slice::from_raw_parts_mut(&mut utm as *mut utmpx as *mut u8, mem::size_of::<utmpx>())
+        };
+
+        let mut read = 0;
+        while read < buffer.len() {
+            match self.reader.read(&mut buffer[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        if read == 0 {
+            None
+        } else if read < buffer.len() {
+            let msg = "stream ended in the middle of a utmpx entry";
+            Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg)))
+        } else {
+            Some(Ok(Utmpx::from_c_utmpx(utm)))
+        }
+    }
+}
+
+/// Get every entry of the running system, in the order they are stored, duplicates included.
+pub fn system_entries() -> Vec<Utmpx> {
+    let mut entries = Vec::new();
+
+    unsafe {
+        setutxent();
+
+        loop {
+            let ut = getutxent();
+            if ut.is_null() {
+                break;
+            } else {
+                entries.push(Utmpx::from_c_utmpx(*ut));
+            }
+        }
+
+        endutxent();
+    }
+
+    entries
+}
+
+/// Group `entries` by their type, keeping their order inside each group.
+pub fn partition_by_type(entries: &[Utmpx]) -> BTreeMap<UtmpxType, Vec<&Utmpx>> {
+    let mut partition = BTreeMap::new();
+    for utm in entries {
+        partition.entry(utm.utype()).or_insert_with(Vec::new).push(utm);
+    }
+    partition
+}
+
+/// Same as [`partition_by_type`], but splits the entries across threads.
+///
+/// Only worth it on very large collections, like the records of a rotated `wtmp` file.
+///
+/// [`partition_by_type`]: fn.partition_by_type.html
+#[cfg(feature = "parallel")]
+pub fn partition_by_type_parallel(entries: &[Utmpx]) -> BTreeMap<UtmpxType, Vec<&Utmpx>> {
+    let chunk_size = (entries.len() / rayon::current_num_threads()).max(1);
+    let chunks: Vec<_> = entries.par_chunks(chunk_size).map(partition_by_type).collect();
+
+    // The chunks are collected in order, so appending them keeps the order of the entries
+    let mut merged: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for chunk in chunks {
+        for (ut_type, mut group) in chunk {
+            merged.entry(ut_type).or_insert_with(Vec::new).append(&mut group);
+        }
+    }
+    merged
+}
+
+/// Append `entry` to the utmpx entry binary file on `path`, creating the file if it does
+/// not exist.
+pub fn write_entry(path: impl AsRef<Path>, entry: &Utmpx) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    write_entry_to(&mut file, entry)
+}
+
+/// Write `entry` to `out` in the utmpx entry binary format.
+pub fn write_entry_to(out: &mut impl Write, entry: &Utmpx) -> io::Result<()> {
+    let utm = entry.to_c_utmpx();
+    let bytes = unsafe {
+        slice::from_raw_parts(&utm as *const utmpx as *const u8, mem::size_of::<utmpx>())
+    };
+
+    out.write_all(bytes)
+}
+
+/// Write `entry` to `out` as a line of the `utmpdump`(1) text format,
+/// `[type] [pid] [id] [user] [line] [host] [epoch]`, with the raw value of the type and the
+/// microseconds of the time, so [`parse_dump_line`] gets the entry back.
+///
+/// The fields that only exist in some platforms are not written.
+///
+/// [`parse_dump_line`]: fn.parse_dump_line.html
+pub fn dump_entry(out: &mut impl Write, entry: &Utmpx) -> io::Result<()> {
+    let ut_type = entry.to_c_utmpx().ut_type.to_string();
+    let pid = entry.pid.0.to_string();
+    let time = format!("{}.{:06}", entry.timeval.tv_sec, entry.timeval.tv_usec);
+    let fields: [&[u8]; 7] = [
+        ut_type.as_bytes(),
+        pid.as_bytes(),
+        &entry.id,
+        &entry.user,
+        &entry.line,
+        &entry.host,
+        time.as_bytes(),
+    ];
+
+    for (num, field) in fields.iter().enumerate() {
+        out.write_all(if num == 0 { b"[" } else { b" [" })?;
+        out.write_all(field)?;
+        out.write_all(b"]")?;
+    }
+    out.write_all(b"\n")
+}
+
+/// Parse a line written by [`dump_entry`], `None` if it is not valid.
+///
+/// [`dump_entry`]: fn.dump_entry.html
+pub fn parse_dump_line(line: &[u8]) -> Option<Utmpx> {
+    if line.len() < 2 || !line.starts_with(b"[") || !line.ends_with(b"]") {
+        return None;
+    }
+
+    let fields: Vec<_> = line[1..line.len() - 1].split_str("] [").collect();
+    if fields.len() != 7 {
+        return None;
+    }
+
+    // The raw type has a different integer type between platforms
+    let mut utm: utmpx = unsafe { mem::zeroed() };
+    utm.ut_type = str::from_utf8(fields[0]).ok()?.parse().ok()?;
+    let pid = str::from_utf8(fields[1]).ok()?.parse().ok()?;
+
+    let time = str::from_utf8(fields[6]).ok()?;
+    let (sec, usec) = match time.find('.') {
+        Some(dot) => (&time[..dot], &time[dot + 1..]),
+        None => (time, "0"),
+    };
+    let timeval = TimeVal { tv_sec: sec.parse().ok()?, tv_usec: usec.parse().ok()? };
+
+    Some(Utmpx::new(
+        UtmpxType::from(utm.ut_type),
+        Pid(pid),
+        fields[4].as_bstr(),
+        fields[2].as_bstr(),
+        fields[3].as_bstr(),
+        fields[5].as_bstr(),
+        timeval,
+    ))
+}
+
+/// Append a failed login record of `user` on `line` from `host` at the time `now` to the
+/// btmp file on `path`.
+///
+/// If `path` is `None`, [`DEFAULT_BTMP_PATH`] is used.
+///
+/// [`DEFAULT_BTMP_PATH`]: ../consts/constant.DEFAULT_BTMP_PATH.html
+pub fn record_failed_login(
+    path: Option<&Path>, user: &BStr, line: &BStr, host: &BStr, now: TimeVal,
+) -> io::Result<()> {
+    let path = path.unwrap_or_else(|| Path::new(DEFAULT_BTMP_PATH));
+
+    // Same as login(1), the record ID is the last 4 characters of the line
+    let id = &line[line.len().saturating_sub(4)..];
+    let pid = Pid::current();
+
+    let entry = Utmpx::new(UtmpxType::LoginProcess, pid, line, id.as_bstr(), user, host, now);
+    write_entry(path, &entry)
+}
+
+/// The bytes of the `C` character array `src` up to its first NUL, all of them if it has
+/// none.
+fn from_c_array(src: &[c_char]) -> BString {
+    src.iter().map(|cc| *cc as u8).take_while(|&byte| byte != 0).collect::<Vec<_>>().into()
+}
+
+/// Copy `src` into the `C` character array `dst`, truncating it if it's too long.
+fn copy_to_c_array(dst: &mut [c_char], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src.iter()) {
+        *d = *s as c_char;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, fs, path::PathBuf, process};
+
+    use bstr::B;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("coreutils_core_utmpx_{}_{}", process::id(), name))
+    }
+
+    #[test]
+    fn remote_endpoint_of_host_field() {
+        let parse = |host: &str| RemoteEndpoint::parse(B(host).as_bstr());
+
+        let plain = parse("1.2.3.4").unwrap();
+        assert_eq!((plain.host(), plain.port()), (B("1.2.3.4").as_bstr(), None));
+        assert_eq!(plain.to_string(), "1.2.3.4");
+
+        let with_port = parse("1.2.3.4:54321").unwrap();
+        assert_eq!((with_port.host(), with_port.port()), (B("1.2.3.4").as_bstr(), Some(54321)));
+        assert_eq!(with_port.to_string(), "1.2.3.4:54321");
+
+        let v6 = parse("[fe80::1]:22").unwrap();
+        assert_eq!((v6.host(), v6.port()), (B("fe80::1").as_bstr(), Some(22)));
+        assert_eq!(v6.to_string(), "[fe80::1]:22");
+        assert_eq!(parse("fe80::1").unwrap().port(), None);
+
+        assert_eq!(parse("host:0.0").unwrap().port(), None);
+        assert_eq!(parse(":0"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn run_level_of_pid_bytes() {
+        let entry = |ut_type, pid| {
+            let timeval = TimeVal { tv_sec: 0, tv_usec: 0 };
+            let (line, user) = (B("~").as_bstr(), B("runlevel").as_bstr());
+            Utmpx::new(ut_type, Pid(pid), line, line, user, B("").as_bstr(), timeval)
+        };
+
+        let pid = (i32::from(b'3') << 8) | i32::from(b'5');
+        assert_eq!(entry(UtmpxType::RunLevel, pid).run_level(), Some(('5', '3')));
+        assert_eq!(entry(UtmpxType::RunLevel, i32::from(b'5')).run_level(), Some(('5', 'N')));
+        assert_eq!(entry(UtmpxType::RunLevel, 0).run_level(), None);
+        assert_eq!(entry(UtmpxType::UserProcess, i32::from(b'5')).run_level(), None);
+    }
+
+    #[test]
+    fn watcher_reports_snapshot_changes() {
+        let entry = |ut_type, user: &str, line: &str, host: &str, sec| {
+            let timeval = TimeVal { tv_sec: sec, tv_usec: 0 };
+            let (line, user, host) = (B(line).as_bstr(), B(user).as_bstr(), B(host).as_bstr());
+            Utmpx::new(ut_type, Pid(1000), line, line, user, host, timeval)
+        };
+        let first: UtmpxSet = vec![
+            entry(UtmpxType::BootTime, "reboot", "~", "", 900),
+            entry(UtmpxType::UserProcess, "alice", "pts/0", "", 1_000),
+            entry(UtmpxType::UserProcess, "bob", "pts/1", "", 1_010),
+        ]
+        .into_iter()
+        .collect();
+        let second: UtmpxSet = vec![
+            entry(UtmpxType::BootTime, "reboot", "~", "", 900),
+            // Another host is the same session
+            entry(UtmpxType::UserProcess, "alice", "pts/0", "10.0.0.1", 1_000),
+            entry(UtmpxType::DeadProcess, "", "pts/1", "", 1_020),
+            entry(UtmpxType::UserProcess, "dave", "pts/2", "", 1_040),
+            entry(UtmpxType::UserProcess, "carol", "pts/1", "", 1_030),
+        ]
+        .into_iter()
+        .collect();
+        let names = |events: &[UtmpEvent]| -> Vec<String> {
+            events
+                .iter()
+                .map(|event| match event {
+                    UtmpEvent::Login(utm) => format!("+{}", utm.user()),
+                    UtmpEvent::Logout(utm) => format!("-{}", utm.user()),
+                })
+                .collect()
+        };
+
+        let mut watcher = UtmpWatcher::new();
+        let mut events = Vec::new();
+        watcher.update(&first, |event| events.push(event));
+        assert_eq!(names(&events), ["+alice", "+bob"]);
+
+        events.clear();
+        watcher.update(&second, |event| events.push(event));
+        assert_eq!(names(&events), ["-bob", "+carol", "+dave"]);
+
+        events.clear();
+        UtmpWatcher::from_snapshot(&second).update(&second, |event| events.push(event));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn merge_prefers_live_entries() {
+        let entry = |ut_type, user: &str, line: &str, pid, sec| {
+            let timeval = TimeVal { tv_sec: sec, tv_usec: 0 };
+            let (line, user) = (B(line).as_bstr(), B(user).as_bstr());
+            Utmpx::new(ut_type, Pid(pid), line, line, user, B("").as_bstr(), timeval)
+        };
+        let history: UtmpxSet = vec![
+            entry(UtmpxType::UserProcess, "alice", "pts/0", 100, 1_000),
+            entry(UtmpxType::DeadProcess, "", "pts/0", 100, 1_500),
+            entry(UtmpxType::UserProcess, "bob", "pts/1", 200, 2_000),
+        ]
+        .into_iter()
+        .collect();
+        let live: UtmpxSet = vec![
+            // The same session as bob's in the history, but with a different user
+            entry(UtmpxType::UserProcess, "robert", "pts/1", 200, 2_000),
+            entry(UtmpxType::UserProcess, "carol", "pts/2", 300, 3_000),
+        ]
+        .into_iter()
+        .collect();
+
+        let merged = UtmpxSet::merge(&live, &history);
+
+        let mut users: Vec<_> =
+            merged.iter().map(|u| (u.device_name().to_string(), u.user().to_string())).collect();
+        users.sort();
+        let expected = [("pts/0", ""), ("pts/0", "alice"), ("pts/1", "robert"), ("pts/2", "carol")];
+        let expected: Vec<_> =
+            expected.iter().map(|&(line, user)| (line.to_string(), user.to_string())).collect();
+        assert_eq!(users, expected);
+    }
+
+    #[test]
+    fn record_failed_login_round_trip() {
+        let path = temp_path("btmp");
+        let now = TimeVal { tv_sec: 1_500_000_000, tv_usec: 42 };
+
+        record_failed_login(
+            Some(&path),
+            B("mallory").as_bstr(),
+            B("pts/7").as_bstr(),
+            B("10.0.0.1").as_bstr(),
+            now,
+        )
+        .unwrap();
+
+        let set = UtmpxSet::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        let set = set.unwrap();
+
+        assert_eq!(set.len(), 1);
+        let entry = set.iter().next().unwrap();
+        assert_eq!(entry.utype(), UtmpxType::LoginProcess);
+        assert_eq!(entry.user(), "mallory");
+        assert_eq!(entry.device_name(), "pts/7");
+        assert_eq!(entry.id(), "ts/7");
+        assert_eq!(entry.host(), "10.0.0.1");
+        assert_eq!(entry.timeval().tv_sec, now.tv_sec);
+        assert_eq!(entry.timeval().tv_usec, now.tv_usec);
+    }
+
+    #[test]
+    fn non_utf8_fields_round_trip() {
+        let path = temp_path("non_utf8");
+        let timeval = TimeVal { tv_sec: 1_500_000_000, tv_usec: 0 };
+        let (line, user) = (B("pts/1\0junk").as_bstr(), B(b"al\xffce").as_bstr());
+        let entry = Utmpx::new(UtmpxType::UserProcess, Pid(1), line, line, user, user, timeval);
+        write_entry(&path, &entry).unwrap();
+
+        let set = UtmpxSet::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        let set = set.unwrap();
+
+        let entry = set.iter().next().unwrap();
+        assert_eq!(entry.user(), B(b"al\xffce"));
+        assert_eq!(entry.user().to_string(), "al\u{FFFD}ce");
+        assert_eq!(entry.host().to_string(), "al\u{FFFD}ce");
+        assert_eq!(entry.device_name(), "pts/1");
+    }
+
+    #[test]
+    fn from_reader_reads_every_entry() {
+        let path = temp_path("stream");
+        let timeval = TimeVal { tv_sec: 1_500_000_000, tv_usec: 0 };
+        let empty = B("").as_bstr();
+        for (user, line) in &[("alice", "pts/0"), ("bob", "pts/1")] {
+            let (user, line) = (B(user).as_bstr(), B(line).as_bstr());
+            let entry =
+                Utmpx::new(UtmpxType::UserProcess, Pid(1), line, line, user, empty, timeval);
+            write_entry(&path, &entry).unwrap();
+        }
+        let bytes = fs::read(&path);
+        fs::remove_file(&path).unwrap();
+        let bytes = bytes.unwrap();
+
+        let set = UtmpxSet::from_reader(&bytes[..]).unwrap();
+        let mut users: Vec<_> = set.iter().map(|u| u.user().to_string()).collect();
+        users.sort();
+        assert_eq!(users, ["alice", "bob"]);
+
+        let truncated = UtmpxSet::from_reader(&bytes[..bytes.len() - 1]);
+        assert_eq!(truncated.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert!(UtmpxSet::from_reader(io::empty()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn dump_line_round_trip() {
+        let timeval = TimeVal { tv_sec: 1_500_000_000, tv_usec: 4_200 };
+        let (line, host) = (B("pts/3").as_bstr(), B("10.0.0.1").as_bstr());
+        let (id, user) = (B("ts/3").as_bstr(), B("alice").as_bstr());
+        let entry = Utmpx::new(UtmpxType::UserProcess, Pid(42), line, id, user, host, timeval);
+        let mut out = Vec::new();
+
+        dump_entry(&mut out, &entry).unwrap();
+
+        let ut_type = entry.to_c_utmpx().ut_type;
+        let expected =
+            format!("[{}] [42] [ts/3] [alice] [pts/3] [10.0.0.1] [1500000000.004200]\n", ut_type);
+        assert_eq!(out.as_bstr(), expected.as_str());
+        assert_eq!(parse_dump_line(&out[..out.len() - 1]), Some(entry));
+        assert_eq!(parse_dump_line(b"[7] [42] [ts/3] [alice] [pts/3]"), None);
+        assert_eq!(parse_dump_line(b"[x] [42] [ts/3] [alice] [pts/3] [] [0]"), None);
+        assert_eq!(parse_dump_line(b""), None);
+    }
+
+    #[test]
+    fn linux_layout_on_any_host() {
+        let mut record = vec![0u8; UtmpLayout::LINUX.size()];
+        record[0..2].copy_from_slice(&7i16.to_le_bytes());
+        record[4..8].copy_from_slice(&4242i32.to_le_bytes());
+        record[8..13].copy_from_slice(b"pts/3");
+        record[40..44].copy_from_slice(b"ts/3");
+        record[44..49].copy_from_slice(b"alice");
+        record[76..84].copy_from_slice(b"10.0.0.1");
+        record[340..344].copy_from_slice(&1_500_000_000i32.to_le_bytes());
+        record[344..348].copy_from_slice(&4_200i32.to_le_bytes());
+        let mut bytes = record.clone();
+        bytes.extend_from_slice(&record);
+
+        let entries = UtmpxSet::from_bytes_with_layout(&bytes, UtmpLayout::LINUX).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], entries[1]);
+        let entry = &entries[0];
+        assert_eq!(entry.utype(), UtmpxType::UserProcess);
+        assert_eq!(entry.process_id(), Pid(4242));
+        assert_eq!(entry.device_name(), "pts/3");
+        assert_eq!(entry.id(), "ts/3");
+        assert_eq!(entry.user(), "alice");
+        assert_eq!(entry.host(), "10.0.0.1");
+        assert_eq!((entry.timeval().tv_sec, entry.timeval().tv_usec), (1_500_000_000, 4_200));
+
+        match UtmpxSet::from_bytes_with_layout(&bytes[..500], UtmpLayout::LINUX) {
+            Err(UtmpxError::Parse(384)) => {},
+            res => panic!("expected a partial entry at 384, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn byte_swapped_linux_layout() {
+        let record = |big: bool| {
+            let int16 = |num: i16| if big { num.to_be_bytes() } else { num.to_le_bytes() };
+            let int32 = |num: i32| if big { num.to_be_bytes() } else { num.to_le_bytes() };
+            let mut record = vec![0u8; UtmpLayout::LINUX.size()];
+            record[0..2].copy_from_slice(&int16(7));
+            record[4..8].copy_from_slice(&int32(4242));
+            record[8..13].copy_from_slice(b"pts/3");
+            record[44..49].copy_from_slice(b"alice");
+            record[336..340].copy_from_slice(&int32(77));
+            record[340..344].copy_from_slice(&int32(1_500_000_000));
+            record[344..348].copy_from_slice(&int32(4_200));
+            record[348..352].copy_from_slice(&[10, 0, 0, 1]);
+            record
+        };
+
+        let parse = |bytes: &[u8], layout| {
+            UtmpxSet::from_bytes_with_layout(bytes, layout).unwrap().remove(0)
+        };
+        let big_endian = UtmpLayout::LINUX.with_endianness(Endianness::Big);
+        let native = parse(&record(false), UtmpLayout::LINUX);
+        let entry = parse(&record(true), big_endian);
+
+        assert_eq!(big_endian.endianness(), Endianness::Big);
+        assert_eq!(entry, native);
+        assert_ne!(parse(&record(true), UtmpLayout::LINUX), native);
+        assert_eq!(entry.utype(), UtmpxType::UserProcess);
+        assert_eq!(entry.process_id(), Pid(4242));
+        assert_eq!((entry.timeval().tv_sec, entry.timeval().tv_usec), (1_500_000_000, 4_200));
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(entry.session(), 77);
+            assert_eq!(entry.address(), Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+        }
+    }
+
+    #[test]
+    fn freebsd_layout_is_big_endian() {
+        let mut record = vec![0u8; UtmpLayout::FREEBSD.size()];
+        record[0] = 7;
+        record[1..9].copy_from_slice(&1_500_000_000_004_200i64.to_be_bytes());
+        record[17..21].copy_from_slice(&(-1i32).to_be_bytes());
+        record[53..58].copy_from_slice(b"pts/3");
+
+        let entries = UtmpxSet::from_bytes_with_layout(&record, UtmpLayout::FREEBSD).unwrap();
+
+        let entry = &entries[0];
+        assert_eq!(entry.utype(), UtmpxType::DeadProcess);
+        assert_eq!(entry.process_id(), Pid(-1));
+        assert_eq!(entry.device_name(), "pts/3");
+        assert_eq!((entry.timeval().tv_sec, entry.timeval().tv_usec), (1_500_000_000, 4_200));
+    }
+
+    #[test]
+    fn type_predicates() {
+        use UtmpxType::*;
+
+        let types = [BootTime, DeadProcess, Empty, InitProcess, LoginProcess, NewTime, OldTime];
+        let types = types.iter().chain(&[RunLevel, UserProcess, Accounting, Invalid, Unknown(42)]);
+        for ut_type in types {
+            let timeval = TimeVal { tv_sec: 0, tv_usec: 0 };
+            let empty = B("").as_bstr();
+            let entry = Utmpx::new(*ut_type, Pid(1), empty, empty, empty, empty, timeval);
+
+            assert_eq!(entry.is_user_process(), *ut_type == UserProcess);
+            assert_eq!(entry.is_boot_time(), *ut_type == BootTime);
+            assert_eq!(entry.is_dead_process(), *ut_type == DeadProcess);
+            assert_eq!(entry.is_login_process(), *ut_type == LoginProcess);
+            assert_eq!(entry.is_run_level(), *ut_type == RunLevel);
+            assert_eq!(entry.is_init_process(), *ut_type == InitProcess);
+            assert_eq!(entry.is_new_time(), *ut_type == NewTime);
+            assert_eq!(entry.is_old_time(), *ut_type == OldTime);
+        }
+    }
+
+    #[test]
+    fn from_file_missing_file() {
+        let path = temp_path("missing");
+
+        assert_eq!(UtmpxSet::from_file(&path).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn from_bytes_random_lengths() {
+        let size = mem::size_of::<utmpx>();
+        // Small xorshift generator, so the test is the same on every run
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let bytes: Vec<u8> = (0..size * 4).map(|_| next() as u8).collect();
+
+        let lengths: Vec<_> = (0..200).map(|_| next() as usize % bytes.len()).collect();
+        for &len in lengths.iter().chain(&[0, size, size * 3]) {
+            match UtmpxSet::from_bytes(&bytes[..len]) {
+                Ok(set) => {
+                    assert_eq!(len % size, 0);
+                    assert!(set.len() <= len / size);
+                },
+                Err(UtmpxError::Parse(offset)) => assert_eq!(offset, len - len % size),
+                Err(err) => panic!("unexpected error: {}", err),
+            }
+        }
+
+        let path = temp_path("partial");
+        fs::write(&path, &bytes[..size + 1]).unwrap();
+        let set = UtmpxSet::from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let err = set.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains(&format!("offset {}", size)));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn session_id_of_entry() {
+        let timeval = TimeVal { tv_sec: 0, tv_usec: 0 };
+        let line = B("pts/0").as_bstr();
+        let entry = Utmpx::new(UtmpxType::UserProcess, Pid(1), line, line, line, line, timeval);
+        let mut utm = entry.to_c_utmpx();
+        utm.ut_session = 4242;
+
+        assert_eq!(entry.session_id(), Some(0));
+        assert_eq!(Utmpx::from_c_utmpx(utm).session_id(), Some(4242));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn own_process_in_host_namespace() {
+        let timeval = TimeVal { tv_sec: 0, tv_usec: 0 };
+        let line = B("pts/0").as_bstr();
+        let entry = |pid| Utmpx::new(UtmpxType::UserProcess, pid, line, line, line, line, timeval);
+
+        assert_eq!(entry(Pid::current()).in_host_namespace(), Some(true));
+        assert_eq!(entry(Pid(0)).in_host_namespace(), None);
+        // Larger than the maximum PID of Linux
+        assert_eq!(entry(Pid(1 << 30)).in_host_namespace(), None);
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "netbsd", target_os = "dragonfly")))]
+    fn unknown_type_keeps_raw_value() {
+        let ut_type = UtmpxType::from(42 as c_short);
+
+        assert_eq!(ut_type, UtmpxType::Unknown(42));
+        assert_eq!(c_short::from(ut_type), 42);
+        assert_eq!(ut_type.to_string(), "UNKNOWN(42)");
+    }
+
+    fn synthetic_entries(len: i32) -> Vec<Utmpx> {
+        use UtmpxType::*;
+
+        let types = [UserProcess, LoginProcess, DeadProcess, InitProcess, BootTime];
+        let empty = B("").as_bstr();
+        (0..len)
+            .map(|n| {
+                let timeval = TimeVal { tv_sec: n.into(), tv_usec: 0 };
+                let line = format!("pts/{}", n % 64);
+                let ut_type = types[n as usize % types.len()];
+                Utmpx::new(ut_type, Pid(n), line.as_bytes().as_bstr(), empty, empty, empty, timeval)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partition_by_type_keeps_order() {
+        let entries = synthetic_entries(100);
+        let partition = partition_by_type(&entries);
+
+        assert_eq!(partition.len(), 5);
+        assert_eq!(partition.values().map(Vec::len).sum::<usize>(), entries.len());
+        for (ut_type, group) in &partition {
+            assert!(group.iter().all(|utm| utm.utype() == *ut_type));
+
+            let expected: Vec<_> = entries.iter().filter(|utm| utm.utype() == *ut_type).collect();
+            assert_eq!(*group, expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn partition_by_type_parallel_matches_serial() {
+        let entries = synthetic_entries(100_000);
+
+        assert_eq!(partition_by_type_parallel(&entries), partition_by_type(&entries));
+    }
+
+    #[test]
+    fn login_timespec_keeps_microseconds() {
+        let entry = |tv_usec| {
+            let timeval = TimeVal { tv_sec: 1_500_000_000, tv_usec };
+            let (line, user) = (B("pts/0").as_bstr(), B("alice").as_bstr());
+            Utmpx::new(UtmpxType::UserProcess, Pid(1), line, line, user, B("").as_bstr(), timeval)
+        };
+        let (first, second) = (entry(1), entry(2));
+
+        assert_eq!(first.login_timespec().sec, second.login_timespec().sec);
+        assert_eq!(first.login_timespec().nsec, 1000);
+        assert!(first.login_timespec() < second.login_timespec());
+        assert_eq!(entry(5_000_000).login_timespec().nsec, 999_999_000);
+        assert_eq!(entry(-1).login_timespec().nsec, 0);
+    }
+}
@@ -1,29 +1,46 @@
 //! Module for system information
 use std::{
-    ffi::CStr,
+    ffi::{CStr, CString},
     fmt::{self, Display},
     io,
-    mem::MaybeUninit,
+    mem::{self, MaybeUninit},
+    ptr,
 };
 
+use crate::consts::HOST_OS;
+
 use bstr::{BStr, BString, ByteSlice};
-use libc::{uname, utsname};
+use libc::{
+    addrinfo, freeaddrinfo, gai_strerror, getaddrinfo, uname, utsname, AI_CANONNAME, EAI_SYSTEM,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
 
 /// A struct that holds several system informations, like the system name, host name, etc.
+///
+/// With the `serde` feature it can be serialized, with every field as a UTF-8 lossy string.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct UtsName {
     /// Name of the operating system implementation.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_lossy"))]
     sysname: BString,
     /// Network name of this machine.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_lossy"))]
     nodename: BString,
     /// Release level of the operating system. (Often the kernel version)
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_lossy"))]
     release: BString,
     /// Version level of the operating system.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_lossy"))]
     version: BString,
     /// Machine hardware platform.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_lossy"))]
     machine: BString,
     /// NIS or YP domain name
     #[cfg(any(target_os = "linux", target_os = "fuchsia"))]
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_lossy"))]
     domainname: BString,
 }
 
@@ -90,6 +107,17 @@ impl UtsName {
     #[inline]
     pub fn node_name(&self) -> &BStr { self.nodename.as_bstr() }
 
+    /// Get the host name of the machine up to its first `.`, the whole name if it has none.
+    #[inline]
+    pub fn hostname_short(&self) -> BString { short_hostname(self.node_name()) }
+
+    /// Get the fully qualified domain name of the machine, the canonical name of its host name
+    /// resolved with `getaddrinfo`(3).
+    ///
+    /// Fails with a error of the `NotFound` kind if the host name can't be resolved.
+    #[inline]
+    pub fn hostname_fqdn(&self) -> Result<BString, io::Error> { fqdn(self.node_name()) }
+
     /// Get the release level of the operating system.
     #[inline]
     pub fn release(&self) -> &BStr { self.release.as_bstr() }
@@ -108,13 +136,98 @@ impl UtsName {
     pub fn domain_name(&self) -> &BStr { self.domainname.as_bstr() }
 }
 
+/// Get the host name `name` up to its first `.`, the whole name if it has none.
+pub fn short_hostname(name: &BStr) -> BString {
+    let end = name.find_byte(b'.').unwrap_or_else(|| name.len());
+    BString::from(&name[..end])
+}
+
+/// Get the fully qualified domain name of the host `name`, its canonical name resolved with
+/// `getaddrinfo`(3).
+///
+/// Fails with a error of the `NotFound` kind if `name` can't be resolved.
+pub fn fqdn(name: &BStr) -> Result<BString, io::Error> {
+    let name = CString::new(name.as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let mut hints: addrinfo = unsafe { mem::zeroed() };
+    hints.ai_flags = AI_CANONNAME;
+    let mut res = ptr::null_mut();
+
+    match unsafe { getaddrinfo(name.as_ptr(), ptr::null(), &hints, &mut res) } {
+        0 => {},
+        EAI_SYSTEM => return Err(io::Error::last_os_error()),
+        code => {
+            let msg = unsafe { CStr::from_ptr(gai_strerror(code)) };
+            return Err(io::Error::new(io::ErrorKind::NotFound, msg.to_string_lossy()));
+        },
+    }
+
+    let canonical = unsafe {
+        if res.is_null() || (*res).ai_canonname.is_null() {
+            None
+        } else {
+            Some(BString::from(CStr::from_ptr((*res).ai_canonname).to_bytes()))
+        }
+    };
+    unsafe { freeaddrinfo(res) };
+
+    canonical.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "the host name has no canonical name")
+    })
+}
+
+/// Display the `UtsName` as the `uname -a` line: system name, host name, release, version,
+/// machine and operating system.
 impl Display for UtsName {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{} {} {} {} {}",
-            self.sysname, self.nodename, self.release, self.version, self.machine
+            "{} {} {} {} {} {}",
+            self.sysname, self.nodename, self.release, self.version, self.machine, HOST_OS
         )
     }
 }
+
+#[cfg(feature = "serde")]
+fn serialize_lossy<S: Serializer>(field: &BString, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&field.to_str_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uts_name() -> UtsName {
+        UtsName {
+            sysname: BString::from("Linux"),
+            nodename: BString::from("box"),
+            release: BString::from("5.4.0-42-generic"),
+            version: BString::from("#46-Ubuntu SMP Fri Jul 10 00:24:02 UTC 2020"),
+            machine: BString::from("x86_64"),
+            #[cfg(any(target_os = "linux", target_os = "fuchsia"))]
+            domainname: BString::from("(none)"),
+        }
+    }
+
+    #[test]
+    fn hostname_short_strips_domain() {
+        let mut uts = uts_name();
+        assert_eq!(uts.hostname_short(), "box");
+
+        uts.nodename = BString::from("box.lan.example.com");
+        assert_eq!(uts.hostname_short(), "box");
+        assert_eq!(uts.node_name(), "box.lan.example.com");
+    }
+
+    #[test]
+    fn display_matches_uname_all() {
+        let expected = format!(
+            "Linux box 5.4.0-42-generic #46-Ubuntu SMP Fri Jul 10 00:24:02 UTC 2020 x86_64 {}",
+            HOST_OS
+        );
+
+        assert_eq!(uts_name().to_string(), expected);
+    }
+}
@@ -0,0 +1,16 @@
+use std::env;
+
+use clap::{load_yaml, App};
+use coreutils_core::completions;
+
+fn main() {
+    let yaml = load_yaml!("src/hostname.yml");
+    let mut app = App::from_yaml(yaml);
+
+    let out_dir = match env::var("OUT_DIR") {
+        Ok(dir) => dir,
+        _ => return,
+    };
+
+    completions::generate(&mut app, "hostname", &out_dir);
+}
@@ -0,0 +1,111 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{IpAddr, ToSocketAddrs},
+    os::raw::c_char,
+    process,
+};
+
+use coreutils_core::{
+    libc::sethostname,
+    utsname::{fqdn, short_hostname, UtsName},
+    BStr, ByteSlice,
+};
+
+use clap::{load_yaml, App, AppSettings::ColoredHelp, ArgMatches};
+
+#[cfg(test)]
+mod tests;
+
+fn main() {
+    let yaml = load_yaml!("hostname.yml");
+    let matches = App::from_yaml(yaml).settings(&[ColoredHelp]).get_matches();
+
+    if let Some(name) = matches.value_of("NAME") {
+        if let Err(err) = set_hostname(name) {
+            if err.kind() == ErrorKind::PermissionDenied {
+                eprintln!("hostname: you must be root to change the host name");
+            } else {
+                eprintln!("hostname: failed to set the host name: {}", err);
+            }
+            process::exit(1);
+        }
+        return;
+    }
+
+    let uts_name = match UtsName::new() {
+        Ok(uname) => uname,
+        Err(err) => {
+            eprintln!("hostname: {}", err);
+            process::exit(1);
+        },
+    };
+
+    match hostname(uts_name.node_name(), Format::from_matches(&matches)) {
+        Ok(name) => println!("{}", name),
+        Err(err) => {
+            eprintln!("hostname: {}", err);
+            process::exit(1);
+        },
+    }
+}
+
+/// How the host name is displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// The host name as it is.
+    Name,
+    /// Up to its first dot.
+    Short,
+    /// The fully qualified domain name.
+    Fqdn,
+    /// The network addresses of the host.
+    Addresses,
+}
+
+impl Format {
+    fn from_matches(matches: &ArgMatches<'_>) -> Self {
+        if matches.is_present("short") {
+            Format::Short
+        } else if matches.is_present("fqdn") {
+            Format::Fqdn
+        } else if matches.is_present("ip_address") {
+            Format::Addresses
+        } else {
+            Format::Name
+        }
+    }
+}
+
+/// Get the host name `nodename` displayed in `format`.
+fn hostname(nodename: &BStr, format: Format) -> io::Result<String> {
+    match format {
+        Format::Name => Ok(nodename.to_string()),
+        Format::Short => Ok(short_hostname(nodename).to_string()),
+        Format::Fqdn => fqdn(nodename).map(|name| name.to_string()),
+        Format::Addresses => {
+            let addresses: Vec<_> = addresses(nodename)?.iter().map(IpAddr::to_string).collect();
+            Ok(addresses.join(" "))
+        },
+    }
+}
+
+/// Resolve the host `nodename` to its network addresses, without repeating any.
+fn addresses(nodename: &BStr) -> io::Result<Vec<IpAddr>> {
+    let name = nodename.to_str().map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+
+    let mut addresses = Vec::new();
+    for addr in (name, 0).to_socket_addrs()? {
+        if !addresses.contains(&addr.ip()) {
+            addresses.push(addr.ip());
+        }
+    }
+
+    Ok(addresses)
+}
+
+/// Set the host name of the system to `name`.
+fn set_hostname(name: &str) -> io::Result<()> {
+    let res = unsafe { sethostname(name.as_ptr() as *const c_char, name.len() as _) };
+
+    if res == -1 { Err(io::Error::last_os_error()) } else { Ok(()) }
+}
@@ -0,0 +1,20 @@
+use super::*;
+
+use coreutils_core::B;
+
+#[test]
+fn short_of_dotted_nodename() {
+    let nodename = B("box.lan.example.com").as_bstr();
+
+    assert_eq!(hostname(nodename, Format::Name).unwrap(), "box.lan.example.com");
+    assert_eq!(hostname(nodename, Format::Short).unwrap(), "box");
+    assert_eq!(hostname(B("box").as_bstr(), Format::Short).unwrap(), "box");
+}
+
+#[test]
+fn fqdn_of_localhost() {
+    let nodename = B("localhost").as_bstr();
+
+    assert!(hostname(nodename, Format::Fqdn).unwrap().starts_with("localhost"));
+    assert!(hostname(B("no-such-host.invalid").as_bstr(), Format::Fqdn).is_err());
+}
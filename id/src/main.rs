@@ -1,6 +1,9 @@
 use std::process;
 
-use coreutils_core::{group::Group, passwd::Passwd};
+use coreutils_core::{
+    group::groups_of_user,
+    passwd::{self, Passwd},
+};
 
 use clap::{load_yaml, App, AppSettings::ColoredHelp, ArgMatches};
 
@@ -47,7 +50,7 @@ fn main() {
     let name = if flags.by_name { matches.value_of("USER").unwrap() } else { "" };
 
     let passwd = if flags.by_name {
-        Passwd::from_name(name)
+        passwd::resolve(name)
     } else if (flags.user || flags.group) && flags.real {
         Passwd::real()
     } else {
@@ -150,7 +153,7 @@ impl IdFlags {
 }
 
 fn default_logic(passwd: &Passwd, sep: char) {
-    let groups = match passwd.belongs_to() {
+    let groups = match groups_of_user(passwd) {
         Ok(gs) => gs,
         Err(err) => {
             eprintln!("id: {}", err);
@@ -179,7 +182,7 @@ fn default_logic(passwd: &Passwd, sep: char) {
 
 fn group_logic(passwd: &Passwd, flags: IdFlags, sep: char) {
     if flags.name {
-        let group = match Group::from_gid(passwd.gid()) {
+        let group = match passwd.primary_group() {
             Ok(g) => g,
             Err(err) => {
                 eprintln!("id: {}", err);
@@ -201,7 +204,7 @@ fn user_logic(passwd: &Passwd, flags: IdFlags, sep: char) {
 }
 
 fn groups_logic(passwd: &Passwd, flags: IdFlags, sep: char) {
-    let groups = match passwd.belongs_to() {
+    let groups = match groups_of_user(passwd) {
         Ok(gs) => gs,
         Err(err) => {
             eprintln!("id: {}", err);
@@ -219,7 +222,7 @@ fn groups_logic(passwd: &Passwd, flags: IdFlags, sep: char) {
 }
 
 fn pretty_logic(passwd: &Passwd, sep: char) {
-    let groups = match passwd.belongs_to() {
+    let groups = match groups_of_user(passwd) {
         Ok(gs) => gs,
         Err(err) => {
             eprintln!("id: {}", err);
@@ -1,4 +1,6 @@
-use std::{env, process};
+use std::process;
+
+use coreutils_core::env;
 
 use clap::{load_yaml, App, AppSettings::ColoredHelp};
 
@@ -6,7 +8,7 @@ fn main() {
     let yaml = load_yaml!("logname.yml");
     let _matches = App::from_yaml(yaml).settings(&[ColoredHelp]).get_matches();
 
-    let login_name = if let Ok(name) = env::var("LOGNAME") {
+    let login_name = if let Some(name) = env::login_name() {
         name
     } else {
         eprintln!("logname: No login name found.");
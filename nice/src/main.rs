@@ -5,7 +5,7 @@ use std::{
     process::{self, Command},
 };
 
-use coreutils_core::priority::{get_priority, set_priority, PRIO_PROCESS};
+use coreutils_core::priority::{nice_value, set_priority, PRIO_PROCESS};
 
 use clap::{load_yaml, App, AppSettings::ColoredHelp};
 
@@ -38,7 +38,7 @@ fn main() {
         Vec::new()
     };
 
-    let mut niceness = match get_priority(P_PROCESS, 0) {
+    let mut niceness = match nice_value(P_PROCESS) {
         Ok(nice) => nice,
         Err(err) => {
             eprintln!("nice: {}", err);
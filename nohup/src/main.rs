@@ -1,54 +1,92 @@
 use std::{
-    env,
     fs::{File, OpenOptions},
-    io::Error,
-    process::{Command, Stdio},
+    io::{self, Write},
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{self, Command},
 };
 
-use coreutils_core::{file_descriptor::FileDescriptor, tty::isatty};
+use coreutils_core::{
+    file_descriptor::FileDescriptor,
+    signal::{ensure_output_redirect, ignore_sighup},
+    tty::isatty,
+};
 
 use clap::{load_yaml, App, AppSettings::ColoredHelp};
-use signal_hook;
 
-fn main() -> Result<(), Error> {
+/// Exit code when nohup itself fails.
+const EXIT_FAILURE: i32 = 125;
+/// Exit code when COMMAND was found but could not be invoked.
+const EXIT_CANNOT_INVOKE: i32 = 126;
+/// Exit code when COMMAND could not be found.
+const EXIT_ENOENT: i32 = 127;
+
+fn main() {
     let yaml = load_yaml!("nohup.yml");
     let matches = App::from_yaml(yaml).settings(&[ColoredHelp]).get_matches();
 
-    let args_val = matches.values_of("COMMAND").unwrap();
-    let mut args = args_val.map(|x| x.to_owned()).collect::<Vec<String>>();
-    let command_name = args.remove(0);
+    let mut args = matches.values_of_os("COMMAND").unwrap();
+    let command_name = args.next().unwrap();
 
-    let mut command = &mut Command::new(command_name);
-    // If standard input is a terminal, redirect it from an unreadable file.
-    if isatty(FileDescriptor::StdIn) {
-        command = command.stdin(Stdio::null());
-    }
-    let mut open_opts = OpenOptions::new();
-    open_opts.write(true).create(true).append(true);
-    let get_stdout = || -> File {
-        open_opts.open("nohup.out").unwrap_or_else(|_| {
-            for (key, value) in env::vars() {
-                if key == "HOME" {
-                    return open_opts.open(value + "nohup.out").unwrap();
-                }
-            }
-            panic!("There is no $HOME variable in the environment.");
-        })
+    let ignoring_input = isatty(FileDescriptor::StdIn);
+    let redirecting_stderr = isatty(FileDescriptor::StdErr);
+
+    // Keep the terminal to report on, since the standard error may be redirected
+    let mut stderr = match FileDescriptor::StdErr.duplicate() {
+        Ok(file) => file,
+        Err(err) => fail(&mut io::stderr(), "failed to duplicate standard error", &err),
     };
-    let _signal_handler = unsafe {
-        signal_hook::register(signal_hook::SIGINT, || {
-            // ignore
-            println!("Hello world! :D ");
-        })
-    }?;
-    if isatty(FileDescriptor::StdOut) {
-        // Try to open in write append nohup.out else open $HOME/nohup.out
-        command = command.stdout(get_stdout());
+
+    if let Err(err) = ignore_sighup() {
+        fail(&mut stderr, "failed to ignore SIGHUP", &err);
+    }
+
+    // If standard input is a terminal, redirect it from an unreadable file
+    if ignoring_input {
+        let res = OpenOptions::new()
+            .write(true)
+            .open("/dev/null")
+            .and_then(|null| FileDescriptor::StdIn.redirect_to(&null));
+        if let Err(err) = res {
+            fail(&mut stderr, "failed to render standard input unusable", &err);
+        }
     }
-    // If standard error is a terminal, redirect it to standard output.
-    if isatty(FileDescriptor::StdErr) {
-        command = command.stderr(get_stdout());
+
+    match ensure_output_redirect() {
+        Ok(output) => report(&mut stderr, ignoring_input, output.as_ref(), redirecting_stderr),
+        Err(err) => fail(&mut stderr, "failed to redirect the output", &err),
     }
-    command.status().expect("Error while invoking the command.");
-    Ok(())
+
+    let err = Command::new(command_name).args(args).exec();
+    let _ = writeln!(stderr, "nohup: failed to run command {:?}: {}", command_name, err);
+    process::exit(if err.kind() == io::ErrorKind::NotFound {
+        EXIT_ENOENT
+    } else {
+        EXIT_CANNOT_INVOKE
+    });
+}
+
+/// Tell what was done to the standard streams, like GNU nohup.
+fn report(
+    stderr: &mut File, ignoring_input: bool, output: Option<impl AsRef<Path>>,
+    redirecting_stderr: bool,
+) {
+    let msg = match (ignoring_input, output, redirecting_stderr) {
+        (true, Some(path), _) => {
+            format!("ignoring input and appending output to '{}'", path.as_ref().display())
+        },
+        (false, Some(path), _) => format!("appending output to '{}'", path.as_ref().display()),
+        (true, None, true) => "ignoring input and redirecting stderr to stdout".to_string(),
+        (false, None, true) => "redirecting stderr to stdout".to_string(),
+        (true, None, false) => "ignoring input".to_string(),
+        (false, None, false) => return,
+    };
+
+    let _ = writeln!(stderr, "nohup: {}", msg);
+}
+
+/// Write `msg` and `err` to `stderr` and exit with the nohup failure code.
+fn fail(stderr: &mut impl Write, msg: &str, err: &io::Error) -> ! {
+    let _ = writeln!(stderr, "nohup: {}: {}", msg, err);
+    process::exit(EXIT_FAILURE);
 }
@@ -0,0 +1,195 @@
+use std::{
+    io::{self, Write},
+    process,
+};
+
+use coreutils_core::{
+    passwd::{format_finger, Gecos, Passwd},
+    time::{self, Tm},
+    tty::{device_status, format_idle},
+    utmpx::{Utmpx, UtmpxSet},
+};
+
+use clap::{load_yaml, App, AppSettings::ColoredHelp, ArgMatches};
+
+#[cfg(test)]
+mod tests;
+
+fn main() {
+    let yaml = load_yaml!("pinky.yml");
+    let matches = App::from_yaml(yaml).settings(&[ColoredHelp]).get_matches();
+
+    let flags = PinkyFlags::from_matches(&matches);
+    let users: Vec<_> = matches.values_of("USER").map(Iterator::collect).unwrap_or_default();
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    let res = if flags.long {
+        if users.is_empty() {
+            eprintln!("pinky: no username specified; at least one must be specified with -l");
+            process::exit(1);
+        }
+
+        print_long(&mut out, &users, |name| Passwd::from_name(name).ok())
+    } else {
+        let uts = UtmpxSet::system();
+        let rows = short_rows(&uts, &users, |name| Passwd::from_name(name).ok());
+        print_short(&mut out, &rows, flags)
+    };
+
+    if let Err(err) = res {
+        eprintln!("pinky: failed to write output: {}", err);
+        process::exit(1);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PinkyFlags {
+    long:    bool,
+    heading: bool,
+    name:    bool,
+    host:    bool,
+    idle:    bool,
+}
+
+impl PinkyFlags {
+    fn from_matches(matches: &ArgMatches<'_>) -> Self {
+        let no_idle = matches.is_present("no_name_host_idle");
+        let no_host = no_idle || matches.is_present("no_name_host");
+
+        PinkyFlags {
+            long:    matches.is_present("long") && !matches.is_present("short"),
+            heading: !matches.is_present("no_heading"),
+            name:    !no_host && !matches.is_present("no_name"),
+            host:    !no_host,
+            idle:    !no_idle,
+        }
+    }
+}
+
+/// A logged in user with every short format column already rendered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ShortRow {
+    login: String,
+    name:  String,
+    mesg:  char,
+    line:  String,
+    idle:  String,
+    time:  String,
+    host:  String,
+}
+
+impl ShortRow {
+    fn new(u: &Utmpx, pw: Option<&Passwd>) -> Self {
+        let (mesg, last_change) = device_status(u.device_name());
+        let name = match pw {
+            Some(pw) => Gecos::parse(pw.gecos()).full_name(pw.name()).to_string(),
+            None => "???".to_string(),
+        };
+
+        ShortRow {
+            login: u.user().to_string(),
+            name,
+            mesg: match mesg {
+                '+' => ' ',
+                '-' => '*',
+                _ => '?',
+            },
+            line: u.device_name().to_string(),
+            idle: format_idle(last_change),
+            time: format_time(&u.login_time()),
+            host: u.host().to_string(),
+        }
+    }
+}
+
+/// Join the user processes of `uts` with their passwd entries, found with `passwd`. If
+/// `users` is not empty, only their entries are kept.
+fn short_rows<F>(uts: &UtmpxSet, users: &[&str], passwd: F) -> Vec<ShortRow>
+where
+    F: Fn(&str) -> Option<Passwd>,
+{
+    let mut entries: Vec<_> = uts
+        .iter()
+        .filter(|u| u.is_user_process())
+        .filter(|u| users.is_empty() || users.iter().any(|user| u.user() == *user))
+        .collect();
+    entries.sort_by(|a, b| {
+        (a.login_timespec(), a.device_name()).cmp(&(b.login_timespec(), b.device_name()))
+    });
+
+    entries
+        .into_iter()
+        .map(|u| ShortRow::new(u, passwd(&u.user().to_string()).as_ref()))
+        .collect()
+}
+
+fn print_short(out: &mut impl Write, rows: &[ShortRow], flags: PinkyFlags) -> io::Result<()> {
+    if flags.heading {
+        let header = ShortRow {
+            login: "Login".to_string(),
+            name:  "Name".to_string(),
+            mesg:  ' ',
+            line:  "TTY".to_string(),
+            idle:  "Idle".to_string(),
+            time:  "When".to_string(),
+            host:  "Where".to_string(),
+        };
+        print_short_row(out, &header, flags)?;
+    }
+
+    for row in rows {
+        print_short_row(out, row, flags)?;
+    }
+
+    Ok(())
+}
+
+fn print_short_row(out: &mut impl Write, row: &ShortRow, flags: PinkyFlags) -> io::Result<()> {
+    let mut line = format!("{:<8}", row.login);
+    if flags.name {
+        line.push_str(&format!(" {:<19}", row.name));
+    }
+    line.push_str(&format!(" {}{:<8}", row.mesg, row.line));
+    if flags.idle {
+        line.push_str(&format!(" {:<6}", row.idle));
+    }
+    line.push_str(&format!(" {:<16}", row.time));
+    if flags.host {
+        line.push_str(&format!(" {}", row.host));
+    }
+
+    writeln!(out, "{}", line.trim_end())
+}
+
+/// Write the passwd information of every user of `users` found with `passwd`, separated by
+/// a empty line.
+fn print_long<F>(out: &mut impl Write, users: &[&str], passwd: F) -> io::Result<()>
+where
+    F: Fn(&str) -> Option<Passwd>,
+{
+    for (num, user) in users.iter().enumerate() {
+        if num > 0 {
+            writeln!(out)?;
+        }
+
+        match passwd(user) {
+            Some(pw) => write!(out, "{}", format_finger(&pw))?,
+            None => writeln!(out, "{:<40}Name: ???", format!("Login: {}", user))?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Format a login time the same way `who` does.
+fn format_time(tm: &Tm) -> String {
+    match time::format(tm.to_timespec(), "%Y-%m-%d %H:%M") {
+        Ok(t) => t,
+        Err(err) => {
+            eprintln!("pinky: failed to format string: {}", err);
+            process::exit(1);
+        },
+    }
+}
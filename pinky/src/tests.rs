@@ -0,0 +1,97 @@
+use super::*;
+
+use std::{env, fs, path::PathBuf};
+
+use coreutils_core::{
+    time::{self, Timespec},
+    types::{Pid, TimeVal},
+    utmpx::UtmpxType::{self, *},
+    ByteSlice, B,
+};
+
+fn entry(ut_type: UtmpxType, user: &str, line: &str, host: &str, sec: i64) -> Utmpx {
+    let timeval = TimeVal { tv_sec: sec, tv_usec: 0 };
+    let line = B(line).as_bstr();
+    Utmpx::new(ut_type, Pid(1000), line, line, B(user).as_bstr(), B(host).as_bstr(), timeval)
+}
+
+fn fixture_utmp() -> UtmpxSet {
+    vec![
+        entry(BootTime, "reboot", "~", "", 1_000),
+        entry(UserProcess, "bob", "tty-cu1", "", 1_060),
+        entry(UserProcess, "alice", "tty-cu0", "10.0.0.1", 1_030),
+        entry(UserProcess, "carol", "tty-cu2", "example.com", 1_090),
+        entry(DeadProcess, "", "tty-cu3", "", 1_120),
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn fixture_passwd() -> PathBuf {
+    let path = env::temp_dir().join(format!("coreutils_pinky_passwd_{}", process::id()));
+    fs::write(
+        &path,
+        "alice:x:1000:1000:& Smith,Room 101,x5555,555-0199:/home/alice:/bin/zsh\n\
+         bob:x:1001:1001:Bob Jones:/home/bob:/bin/sh\n",
+    )
+    .unwrap();
+    path
+}
+
+fn when(sec: i64) -> String { format_time(&time::to_local(Timespec::new(sec, 0))) }
+
+const SHORT: PinkyFlags =
+    PinkyFlags { long: false, heading: true, name: true, host: true, idle: true };
+
+fn render(users: &[&str], flags: PinkyFlags) -> String {
+    let path = fixture_passwd();
+    let passwd = Passwd::from_file(&path);
+    fs::remove_file(&path).unwrap();
+    let passwd = passwd.unwrap();
+
+    let lookup = |name: &str| passwd.iter().find(|pw| pw.name() == name).cloned();
+    let mut out = Vec::new();
+    if flags.long {
+        print_long(&mut out, users, lookup).unwrap();
+    } else {
+        let rows = short_rows(&fixture_utmp(), users, lookup);
+        print_short(&mut out, &rows, flags).unwrap();
+    }
+
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn short_joins_utmp_and_passwd() {
+    let expected = format!(
+        "Login    Name                 TTY      Idle   When             Where\n\
+         alice    Alice Smith         ?tty-cu0  ?      {:<16} 10.0.0.1\n\
+         bob      Bob Jones           ?tty-cu1  ?      {}\n\
+         carol    ???                 ?tty-cu2  ?      {:<16} example.com\n",
+        when(1_030),
+        when(1_060),
+        when(1_090),
+    );
+
+    assert_eq!(render(&[], SHORT), expected);
+}
+
+#[test]
+fn short_omits_columns_and_filters_users() {
+    let flags = PinkyFlags { heading: false, name: false, host: false, idle: false, ..SHORT };
+    let expected =
+        format!("alice    ?tty-cu0  {}\ncarol    ?tty-cu2  {}\n", when(1_030), when(1_090));
+
+    assert_eq!(render(&["carol", "alice"], flags), expected);
+}
+
+#[test]
+fn long_uses_finger_format() {
+    let expected = "Login: alice                            Name: Alice Smith\n\
+                    Directory: /home/alice                  Shell: /bin/zsh\n\
+                    Office: Room 101, x5555                 Home Phone: 555-0199\n\
+                    \n\
+                    Login: nobody                           Name: ???\n";
+
+    assert_eq!(render(&["alice", "nobody"], PinkyFlags { long: true, ..SHORT }), expected);
+}
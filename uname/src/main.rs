@@ -43,7 +43,7 @@ fn main() {
     }
 
     if all_flag {
-        println!("{} {}", uts_name, HOST_OS);
+        println!("{}", uts_name);
         return;
     }
 
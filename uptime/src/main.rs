@@ -19,6 +19,7 @@ use coreutils_core::{
     libc::time_t,
     load::load_average,
     time,
+    types::duration_between,
     utmpx::{
         UtmpxSet,
         UtmpxType::{BootTime, UserProcess},
@@ -37,10 +38,10 @@ fn main() {
     let utmpxs = UtmpxSet::system();
 
     let mut num_users = 0;
-    let mut boot_time = time::empty_tm();
+    let mut boot_time = time::Timespec::new(0, 0);
     for utmpx in utmpxs {
         match utmpx.utype() {
-            BootTime => boot_time = utmpx.login_time(),
+            BootTime => boot_time = utmpx.login_timespec(),
             UserProcess => num_users += 1,
             _ => continue,
         }
@@ -57,7 +58,7 @@ fn main() {
             target_arch = "powerpc64le",
             target_arch = "sparc64"
         ))]
-        boot_time.to_timespec().sec,
+        boot_time.sec,
         #[cfg(not(any(
             target_arch = "x86_64",
             target_arch = "aarch64",
@@ -67,7 +68,7 @@ fn main() {
             target_arch = "powerpc64le",
             target_arch = "sparc64"
         )))]
-        match boot_time.to_timespec().sec.try_into() {
+        match boot_time.sec.try_into() {
             Ok(time) => time,
             Err(err) => {
                 eprintln!("uptime: failed to change from u64 to u32: {}", err);
@@ -83,7 +84,7 @@ fn main() {
     };
 
     if since_flag {
-        let fmt = match boot_time.strftime("%F %T") {
+        let fmt = match time::format(boot_time, "%F %T") {
             Ok(f) => f,
             Err(err) => {
                 eprintln!("uptime: failed to format time: {}", err);
@@ -121,13 +122,15 @@ fn uptime(boot_time: time_t) -> io::Result<time_t> {
             .parse()
             .or_else(|_| Err(io::Error::last_os_error()))
     } else {
-        let now = time::get_time().sec as time_t;
-        Ok(now - boot_time)
+        // `time_t` is already `i64` on 64 bits platforms
+        #[allow(clippy::unnecessary_cast)]
+        let boot_time = time::Timespec::new(boot_time as i64, 0);
+        Ok(duration_between(boot_time, time::now()).as_secs() as time_t)
     }
 }
 
 fn fmt_time() -> String {
-    let now = time::now();
+    let now = time::to_local(time::now());
 
     format!(" {:02}:{:02}:{:02}", now.tm_hour, now.tm_min, now.tm_sec)
 }
@@ -138,17 +141,7 @@ fn fmt_load() -> String {
             eprintln!("{}", err);
             "".to_string()
         },
-        Ok(slice) => {
-            let mut msg = String::from("load average: ");
-            for item in &slice {
-                if item == &slice[2] {
-                    msg.push_str(&format!("{:.2}", item));
-                } else {
-                    msg.push_str(&format!("{:.2}, ", item));
-                }
-            }
-            msg
-        },
+        Ok(load) => format!("load average: {}", load),
     }
 }
 
@@ -1,19 +1,21 @@
 use std::env;
 
-use clap::{load_yaml, App, Shell};
+use coreutils_core::completions;
+
+#[allow(dead_code)]
+#[path = "src/cli.rs"]
+mod cli;
 
 fn main() {
-    let yaml = load_yaml!("src/who.yml");
-    let mut app = App::from_yaml(yaml);
+    // The build script runs on the host, so the target has to be asked to Cargo
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let logind = target_os == "linux" && env::var_os("CARGO_FEATURE_LOGIND").is_some();
+    let mut app = cli::who_app_for(target_os == "openbsd", logind);
 
     let out_dir = match env::var("OUT_DIR") {
         Ok(dir) => dir,
         _ => return,
     };
 
-    app.gen_completions("who", Shell::Zsh, out_dir.clone());
-    app.gen_completions("who", Shell::Fish, out_dir.clone());
-    app.gen_completions("who", Shell::Bash, out_dir.clone());
-    app.gen_completions("who", Shell::PowerShell, out_dir.clone());
-    app.gen_completions("who", Shell::Elvish, out_dir);
+    completions::generate(&mut app, "who", &out_dir);
 }
@@ -0,0 +1,439 @@
+use clap::{App, Arg};
+
+/// Build the command line interface of `who` for the target platform.
+pub fn who_app() -> App<'static, 'static> {
+    who_app_for(cfg!(target_os = "openbsd"), cfg!(all(target_os = "linux", feature = "logind")))
+}
+
+/// Build the command line interface of `who`, leaving out the flags about entry types and
+/// sessions if `openbsd`, since its `utmp` doesn't have them, and `--logind` unless `logind`.
+pub fn who_app_for(openbsd: bool, logind: bool) -> App<'static, 'static> {
+    let app = App::new("who")
+        .version("0.0.0")
+        .author("Eric Shimizu Karbstein <gr41.j4ck@gmail.com>")
+        .about("Display information about users who are currently logged in.")
+        .arg(Arg::with_name("FILE").help(if openbsd {
+            "A file to use to get the user information (Linux and MacOS only)"
+        } else {
+            "A file to use to get the user information, `-` to read it from the standard input \
+             (Linux and MacOS only)"
+        }))
+        .arg(
+            Arg::with_name("ARG2")
+                .help("With a FILE, like in `who am i`, display only the current terminal, like -m")
+                .hidden(true),
+        )
+        .arg(
+            Arg::with_name("ignore_file_errors")
+                .help("Read the system database if FILE can't be read, instead of failing")
+                .long("ignore-file-errors"),
+        )
+        .arg(
+            Arg::with_name("all")
+                .help("Display all possible information (Equivalent of -bdlprTtuv)")
+                .long("all")
+                .short("a"),
+        );
+
+    let app = if openbsd {
+        app
+    } else {
+        app.arg(
+            Arg::with_name("boot")
+                .help("Display the time of the last system boot")
+                .long("boot")
+                .short("b"),
+        )
+        .arg(
+            Arg::with_name("boot_header")
+                .help(
+                    "Display only a 'system boot YYYY-MM-DDTHH:MM:SS' line with the time of the \
+                     last system boot, or nothing if there is no boot entry",
+                )
+                .long("boot-header")
+                .conflicts_with_all(&["count", "terse", "follow"]),
+        )
+        .arg(Arg::with_name("dead").help("Display dead processes").long("dead").short("d"))
+    };
+
+    let app = app.arg(
+        Arg::with_name("heading")
+            .help("Display line of collumn headings")
+            .long("heading")
+            .short("H"),
+    )
+    .arg(
+        Arg::with_name("no_header_on_empty")
+            .help("With -H, don't display the headings when there is nothing to show")
+            .long("no-header-on-empty"),
+    );
+
+    let app = if openbsd {
+        app
+    } else {
+        app.arg(
+            Arg::with_name("login")
+                .help("Display system login processes")
+                .long("login")
+                .short("l"),
+        )
+    };
+
+    let app = app.arg(
+        Arg::with_name("only_me")
+            .help("Only display information about the current terminal, like `who am i`")
+            .long("me")
+            .alias("associated_stdin")
+            .short("m"),
+    );
+
+    let app = if openbsd {
+        app
+    } else {
+        app.arg(
+            Arg::with_name("process")
+                .help("Display active processes spawned by init")
+                .long("process")
+                .short("p"),
+        )
+    };
+
+    let app = app
+        .arg(
+            Arg::with_name("count")
+                .help("Display all login names and number of users logged on")
+                .long("count")
+                .short("q"),
+        )
+        .arg(
+            Arg::with_name("terse")
+                .help("Display only the login names, each once per line and sorted")
+                .long("terse")
+                .short("1")
+                .conflicts_with("count"),
+        );
+
+    let app = if openbsd {
+        app
+    } else {
+        app.arg(
+            Arg::with_name("runlevel")
+                .help("Display current run level")
+                .long("runlevel")
+                .short("r"),
+        )
+    };
+
+    let app = app.arg(
+        Arg::with_name("short")
+            .help("Display only name, line, and time (default)")
+            .long("short")
+            .short("s"),
+    );
+
+    let app = if openbsd {
+        app
+    } else {
+        app.arg(
+            Arg::with_name("time")
+                .help("Display the system clock changes, as the time before and after each one")
+                .long("time")
+                .short("t"),
+        )
+    };
+
+    let app = app.arg(
+        Arg::with_name("message")
+            .help(
+                "Display a character after the user name indicating the state of the terminal \
+                 line: '+' if the terminal is writable; '-' if it is not; and '?' if a bad line \
+                 is encountered",
+            )
+            .long("message")
+            .short("T")
+            .visible_aliases(&["mesg", "writable"]),
+    )
+    .arg(Arg::with_name("message_w").help("Same as -T").short("w"));
+
+    let app = if openbsd {
+        app
+    } else {
+        app.arg(
+            Arg::with_name("users")
+                .help("Display the idle time for each user")
+                .long("users")
+                .short("u"),
+        )
+    };
+
+    let app = app
+        .arg(
+            Arg::with_name("idle")
+                .help("Display the idle time for each user")
+                .long("idle")
+                .short("i"),
+        )
+        .arg(
+            Arg::with_name("idle_seconds")
+                .help(
+                    "Like --idle, but display the idle time as seconds, -1 if it is old or \
+                     unknown",
+                )
+                .long("idle-seconds"),
+        );
+
+    let app = if openbsd {
+        app
+    } else {
+        app.arg(
+            Arg::with_name("all_types")
+                .help(
+                    "Display every entry, including the ones of accounting and unknown types, \
+                     with a column for its type",
+                )
+                .long("all-types"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .help(
+                    "Display how long each session has lasted, as (HH:MM). If FILE is a wtmp \
+                     file (its name contains 'wtmp'), a session ends with the next dead process \
+                     entry of its line",
+                )
+                .long("duration"),
+        )
+        .arg(
+            Arg::with_name("dump")
+                .help(
+                    "Display every entry in the utmpdump(1) text format, '[type] [pid] [id] \
+                     [user] [line] [host] [epoch]', that --undump turns back into a file",
+                )
+                .long("dump")
+                .conflicts_with_all(&["count", "terse", "follow"]),
+        )
+        .arg(
+            Arg::with_name("undump")
+                .help(
+                    "Read FILE, or the standard input, as entries in the --dump format and write \
+                     them as a utmpx file on the standard output",
+                )
+                .long("undump")
+                .conflicts_with("dump"),
+        )
+        .arg(
+            Arg::with_name("session")
+                .help(
+                    "Display the session ID of each entry, empty where the entries don't have \
+                     one (only Linux, NetBSD and DragonflyBSD have it)",
+                )
+                .long("session"),
+        )
+        .arg(
+            Arg::with_name("namespaces")
+                .help(
+                    "Display if the process of each entry is in the PID namespace of who ('host'), \
+                     in another one, like in a container ('other'), or '?' if it exited (only \
+                     Linux)",
+                )
+                .long("namespaces"),
+        )
+        .arg(
+            Arg::with_name("endpoint")
+                .help(
+                    "Display the remote end of each login from its host, as 'host' or \
+                     'host:port' when the login daemon, like sshd, recorded the port",
+                )
+                .long("endpoint"),
+        )
+        .arg(
+            Arg::with_name("file")
+                .help(
+                    "Also read the entries of FILE, after the ones of the FILE operand, each file \
+                     displayed as its own group",
+                )
+                .long("file")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("header_per_group")
+                .help("With -H and more than one file, display the header before each group")
+                .long("header-per-group"),
+        )
+    };
+
+    let app = if logind {
+        app.arg(
+            Arg::with_name("logind")
+                .help(
+                    "Display the user sessions of systemd-logind instead of the utmp entries, so \
+                     the graphical and remote sessions missing from utmp are displayed too. \
+                     Falls back to utmp if systemd-logind is not running",
+                )
+                .long("logind")
+                .conflicts_with_all(&[
+                    "FILE",
+                    "file",
+                    "dump",
+                    "undump",
+                    "boot_header",
+                    "count",
+                    "terse",
+                    "follow",
+                    "verify_owner",
+                ]),
+        )
+    } else {
+        app
+    };
+
+    app.arg(
+        Arg::with_name("highlight_me")
+            .help(
+                "Mark with a '*' the logins of the current user on other terminals than the one \
+                 of the standard input",
+            )
+            .long("highlight-me"),
+    )
+    .arg(
+        Arg::with_name("verify_owner")
+            .help(
+                "Mark with a '!' the logins whose terminal is owned by another user than the \
+                 logged in one, and with a '?' the ones whose terminal is missing",
+            )
+            .long("verify-owner"),
+    )
+    .arg(
+        Arg::with_name("relative")
+            .help(
+                "Display the login time relative to now, like '3m ago', '2h ago' or '4d ago', \
+                 keeping the absolute time for the logins a week old or more",
+            )
+            .long("relative"),
+    )
+    .arg(
+        Arg::with_name("host_width")
+            .help(
+                "Truncate the hostnames longer than N characters, ending them with '…' (or '+' \
+                 if the locale is not UTF-8)",
+            )
+            .long("host-width")
+            .takes_value(true)
+            .value_name("N"),
+    )
+    .arg(
+        Arg::with_name("host_only")
+            .help(
+                "Display only the host name of the hosts of the X display form \
+                 (host:display[.screen]), empty for a local display",
+            )
+            .long("host-only"),
+    )
+    .arg(
+        Arg::with_name("full_host")
+            .help("Never truncate the hostnames, overriding --host-width")
+            .long("full-host"),
+    )
+    .arg(
+        Arg::with_name("host")
+            .help(
+                "Display only the entries whose host matches the shell glob GLOB, like \
+                 '192.168.*' or '*.example.com', before any --lookup",
+            )
+            .long("host")
+            .takes_value(true)
+            .value_name("GLOB"),
+    )
+    .arg(
+        Arg::with_name("user")
+            .help("Display only the entries whose user matches the shell glob GLOB, like 'svc-*'")
+            .long("user")
+            .takes_value(true)
+            .value_name("GLOB"),
+    )
+    .arg(
+        Arg::with_name("quote")
+            .help(
+                "Wrap every field in single quotes, with its own single quotes written as '\\'', \
+                 so the output is safe to split in shell scripts",
+            )
+            .long("quote"),
+    )
+    .arg(
+        Arg::with_name("print_empty")
+            .help("Print a '(no entries)' line after the header when there is no entry to display")
+            .long("print-empty"),
+    )
+    .arg(
+        Arg::with_name("follow")
+            .help(
+                "Clear the screen and display the information again every SECONDS (default: 2), \
+                 showing the logins and logouts since the last refresh, until interrupted",
+            )
+            .long("follow")
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .value_name("SECONDS"),
+    )
+    .arg(Arg::with_name("lookup").help("Attempt to canonicalize hostnames via DNS").long("lookup"))
+    .arg(
+        Arg::with_name("lookup_timeout")
+            .help("Maximum time in milliseconds to wait for the hostnames to be canonicalized")
+            .long("lookup-timeout")
+            .takes_value(true)
+            .value_name("MS")
+            .default_value("2000"),
+    )
+    .arg(
+        Arg::with_name("debug")
+            .help(
+                "Print every record of the database with its decoded fields before the normal \
+                 output",
+            )
+            .long("debug"),
+    )
+    .arg(
+        Arg::with_name("output")
+            .help("Output format: 'table' for aligned columns, 'tsv' for tab-separated columns")
+            .long("output")
+            .takes_value(true)
+            .value_name("FORMAT")
+            .possible_values(&["table", "tsv"])
+            .default_value("table"),
+    )
+    .arg(
+        Arg::with_name("null")
+            .help(
+                "Display the columns of --output=tsv separated by NUL instead, with every entry \
+                 ending with two NULs, to handle any character on a field, like with 'xargs -0'",
+            )
+            .long("null")
+            .short("0"),
+    )
+    .arg(
+        Arg::with_name("columns")
+            .help(
+                "Display only the columns of LIST, in its order, instead of the ones the other \
+                 flags select. LIST is a comma separated list of user, mesg, line, pid, time, \
+                 idle, host and comment (the host in parentheses)",
+            )
+            .long("columns")
+            .takes_value(true)
+            .value_name("LIST"),
+    )
+    .arg(
+        Arg::with_name("sort")
+            .help(
+                "Order of the entries: 'login' by login time, 'idle' by idle time, the longest \
+                 first, with the old and unknown ones before and the active ones last",
+            )
+            .long("sort")
+            .takes_value(true)
+            .value_name("KEY")
+            .possible_values(&["login", "idle"])
+            .default_value("login"),
+    )
+}
@@ -0,0 +1,87 @@
+//! Sessions of systemd-logind, read from the state files it keeps for each session.
+//!
+//! Those files are not a stable interface, but they are what `sd_get_sessions` and friends
+//! read, and reading them directly avoids linking with libsystemd.
+use std::{fs, io, path::Path};
+
+/// Directory where systemd-logind keeps a file for each session.
+pub const SESSIONS_DIR: &str = "/run/systemd/sessions";
+
+/// A login session as recorded by systemd-logind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    pub id:          String,
+    pub user:        String,
+    pub tty:         String,
+    pub display:     String,
+    pub remote_host: String,
+    pub leader:      Option<i32>,
+    /// Seconds since the epoch the session started.
+    pub started:     i64,
+    pub class:       String,
+}
+
+impl Session {
+    /// Parse the state file of the session `id`, made of `KEY=value` lines.
+    ///
+    /// Returns `None` if it has no user, like the files of sessions still being created.
+    pub fn parse(id: &str, contents: &str) -> Option<Self> {
+        let mut session = Session { id: id.to_string(), ..Session::default() };
+
+        for line in contents.lines().filter(|line| !line.starts_with('#')) {
+            let (key, value) = match line.find('=') {
+                Some(pos) => (&line[..pos], &line[pos + 1..]),
+                None => continue,
+            };
+
+            match key {
+                "USER" => session.user = value.to_string(),
+                "TTY" => session.tty = value.to_string(),
+                "DISPLAY" => session.display = value.to_string(),
+                "REMOTE_HOST" => session.remote_host = value.to_string(),
+                "LEADER" => session.leader = value.parse().ok(),
+                // Microseconds since the epoch
+                "REALTIME" => {
+                    session.started = value.parse::<i64>().map(|us| us / 1_000_000).unwrap_or(0)
+                },
+                "CLASS" => session.class = value.to_string(),
+                _ => {},
+            }
+        }
+
+        if session.user.is_empty() { None } else { Some(session) }
+    }
+
+    /// Check if it is the session of a user login, not the one of a greeter or of a
+    /// background service.
+    pub fn is_user(&self) -> bool { self.class.is_empty() || self.class == "user" }
+}
+
+/// Read the user sessions on the systemd-logind directory `dir`, in no particular order.
+///
+/// Fails if `dir` can't be read, like on systems without systemd-logind.
+pub fn sessions(dir: &Path) -> io::Result<Vec<Session>> {
+    let mut sessions = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let id = entry.file_name().to_string_lossy().into_owned();
+        // Skip the `ID.ref` FIFOs next to the session files
+        if id.contains('.') || !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        // A session may end while the directory is read
+        let contents = match fs::read_to_string(entry.path()) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+
+        if let Some(session) = Session::parse(&id, &contents).filter(Session::is_user) {
+            sessions.push(session);
+        }
+    }
+
+    Ok(sessions)
+}
@@ -1,124 +1,395 @@
-use std::{os::unix::fs::MetadataExt, path::PathBuf, process};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet},
+    env,
+    ffi::{CStr, CString},
+    fs,
+    io::{self, Write},
+    mem,
+    net::IpAddr,
+    os::unix::fs::MetadataExt,
+    path::Path,
+    process, ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+#[cfg(not(target_os = "openbsd"))]
+use std::io::Read;
 
 #[cfg(target_os = "openbsd")]
 use coreutils_core::utmp::{Utmp, UtmpSet};
+#[cfg(all(target_os = "linux", feature = "logind"))]
+use coreutils_core::utmpx::RemoteEndpoint;
 #[cfg(not(target_os = "openbsd"))]
-use coreutils_core::utmpx::{
-    Utmpx, UtmpxSet,
-    UtmpxType::{BootTime, DeadProcess, InitProcess, LoginProcess, NewTime, RunLevel, UserProcess},
+use coreutils_core::{
+    utmpx::{
+        dump_entry, parse_dump_line, system_entries, write_entry_to, UtmpEvent, UtmpWatcher,
+        Utmpx, UtmpxReader, UtmpxSet, UtmpxType,
+    },
+    ByteSlice,
 };
 use coreutils_core::{
-    file_descriptor::FileDescriptor, libc::S_IWGRP, time, tty::TTYName, ByteSlice,
+    cache::{Lookup, NameResolver},
+    env::login_name,
+    file_descriptor::FileDescriptor,
+    libc::{addrinfo, freeaddrinfo, getaddrinfo, AI_CANONNAME},
+    time::{self, Tm},
+    tty::{device_status, idle_seconds, IdleTime, TTYName},
+    BStr,
 };
 
-use clap::{load_yaml, App, AppSettings::ColoredHelp, ArgMatches};
+use clap::{AppSettings::ColoredHelp, ArgMatches};
 
-fn main() {
-    #[cfg(not(target_os = "openbsd"))]
-    let yaml = load_yaml!("who.yml");
-    #[cfg(target_os = "openbsd")]
-    let yaml = load_yaml!("who_openbsd.yml");
+#[cfg(all(not(target_os = "openbsd"), feature = "gzip"))]
+use flate2::read::GzDecoder;
+
+use signal_hook::{flag, SIGINT};
+#[cfg(feature = "unicode-width")]
+use unicode_width::UnicodeWidthStr;
+
+mod cli;
+#[cfg(all(target_os = "linux", feature = "logind"))]
+mod logind;
+
+#[cfg(all(test, not(target_os = "openbsd")))]
+mod tests;
+
+/// Maximum number of threads resolving hosts at the same time with `--lookup`.
+const LOOKUP_THREADS: usize = 8;
+
+/// Time between refreshes with `--follow` when no interval is given.
+const DEFAULT_FOLLOW_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Switch to the alternate screen so the terminal is restored when `--follow` ends.
+const ENTER_ALT_SCREEN: &str = "\x1b[?1049h";
+/// Go back to the normal screen.
+const LEAVE_ALT_SCREEN: &str = "\x1b[?1049l";
+/// Move the cursor to the top and clear the screen.
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+fn main() { process::exit(run()) }
+
+/// Run who, returning its exit status: 0 on success, 1 on any error, and 141 if the reading
+/// end of the output was closed, like GNU who killed by SIGPIPE.
+fn run() -> i32 {
+    let matches = cli::who_app().settings(&[ColoredHelp]).get_matches();
+
+    let flags = match WhoFlags::from_matches(&matches) {
+        Ok(flags) => flags,
+        Err(err) => {
+            eprintln!("who: {}", err);
+            return 1;
+        },
+    };
+    let lookup_timeout = match matches.value_of("lookup_timeout").map(str::parse) {
+        Some(Ok(ms)) => Duration::from_millis(ms),
+        Some(Err(err)) => {
+            eprintln!("who: invalid lookup timeout: {}", err);
+            return 1;
+        },
+        None => Duration::from_millis(0),
+    };
+
+    let follow_interval = match follow_interval(&matches) {
+        Ok(interval) => interval,
+        Err(err) => {
+            eprintln!("who: {}", err);
+            return 1;
+        },
+    };
+
+    if let Err(err) = check_operands(&file_operands(&matches)) {
+        eprintln!("who: {}", err);
+        return 1;
+    }
 
-    let matches = App::from_yaml(yaml).settings(&[ColoredHelp]).get_matches();
+    if follow_interval.is_some() && file_operands(&matches).contains(&"-") {
+        eprintln!("who: --follow can't be used reading from standard input");
+        return 1;
+    }
 
-    let flags = WhoFlags::from_matches(&matches);
+    let stdout = io::stdout();
 
-    let uts = if matches.is_present("FILE") {
-        let file = PathBuf::from(matches.value_of("FILE").unwrap());
+    #[cfg(all(target_os = "linux", feature = "logind"))]
+    {
+        // Without systemd-logind, the utmp entries are displayed as usual
+        let sessions = if matches.is_present("logind") {
+            logind::sessions(Path::new(logind::SESSIONS_DIR)).ok()
+        } else {
+            None
+        };
 
-        #[cfg(any(target_os = "openbsd"))]
-        match UtmpSet::from_file(&file) {
-            Ok(u) => u,
-            Err(_) => match UtmpSet::system() {
-                Ok(uu) => uu,
+        if let Some(sessions) = sessions {
+            let mut rows = match session_rows(&sessions, &matches, flags) {
+                Ok(rows) => rows,
                 Err(err) => {
-                    eprintln!("users: failed to get utsp: {}", err);
-                    process::exit(1);
+                    eprintln!("who: {}", err);
+                    return 1;
                 },
-            },
-        }
+            };
+            if flags.lookup {
+                lookup_rows(&mut rows, lookup_timeout);
+            }
+            if flags.sort_idle {
+                sort_by_idle(&mut rows);
+            }
+            if flags.highlight_me {
+                let (me, current) = (login_name().map(|name| name.to_string()), current_tty());
+                mark_mine(&mut rows, me.as_deref(), current.as_deref());
+            }
 
-        #[cfg(not(any(target_os = "openbsd")))]
-        match UtmpxSet::from_file(&file) {
-            Ok(u) => u,
-            Err(_) => UtmpxSet::system(),
+            let mut out = stdout.lock();
+            let res = print_groups(&mut out, &[rows], flags);
+            return exit_status(res.and_then(|_| out.flush()), "failed to write output");
         }
-    } else {
-        #[cfg(any(target_os = "openbsd"))]
-        match UtmpSet::system() {
-            Ok(u) => u,
-            Err(err) => {
-                eprintln!("users: failed to get utsp: {}", err);
-                process::exit(1);
-            },
+    }
+
+    #[cfg(not(target_os = "openbsd"))]
+    {
+        if matches.is_present("undump") {
+            let res = read_dump(file_operand(&matches))
+                .and_then(|dump| undump(&dump, &mut stdout.lock()));
+            return exit_status(res, "failed to undump entries");
         }
+    }
 
-        #[cfg(not(any(target_os = "openbsd")))]
-        UtmpxSet::system()
+    #[cfg(not(target_os = "openbsd"))]
+    let record_groups = match load_record_groups(&matches) {
+        Ok(groups) => groups,
+        Err(err) => {
+            eprintln!("who: {}", err);
+            return 1;
+        },
+    };
+    #[cfg(not(target_os = "openbsd"))]
+    let records = record_groups.concat();
+    #[cfg(not(target_os = "openbsd"))]
+    let groups: Vec<UtmpxSet> =
+        record_groups.into_iter().map(|records| records.into_iter().collect()).collect();
+    #[cfg(not(target_os = "openbsd"))]
+    let uts: UtmpxSet = records.iter().cloned().collect();
+    #[cfg(target_os = "openbsd")]
+    let uts = match load_entries(&matches) {
+        Ok(uts) => uts,
+        Err(err) => {
+            eprintln!("who: {}", err);
+            return 1;
+        },
     };
 
-    let mut ut_vec = filter_entries(&uts, flags);
-    ut_vec.sort_unstable_by_key(|u| u.login_time());
+    #[cfg(not(target_os = "openbsd"))]
+    {
+        if matches.is_present("dump") {
+            return exit_status(print_dump(&mut stdout.lock(), &records), "failed to write output");
+        }
 
-    if flags.count {
-        let mut counter = 0;
-        #[cfg(not(target_os = "openbsd"))]
-        for ut in ut_vec.iter().filter(|u| u.utype() == UserProcess) {
-            print!("{} ", ut.user());
-            counter += 1;
+        if matches.is_present("boot_header") {
+            let res = print_boot_header(&mut stdout.lock(), &uts);
+            return exit_status(res, "failed to write output");
         }
+    }
 
+    if flags.debug {
+        #[cfg(not(target_os = "openbsd"))]
+        let res = write_debug(&mut stdout.lock(), &records);
         #[cfg(target_os = "openbsd")]
-        for ut in ut_vec.iter() {
-            print!("{} ", ut.user());
-            counter += 1;
+        let res = write_debug(&mut stdout.lock(), &uts);
+        let status = exit_status(res, "failed to write debug output");
+        if status != 0 {
+            return status;
         }
+    }
+
+    let tty = TTYName::new(FileDescriptor::StdIn).ok();
+    let mut ut_vec = filter_entries(&uts, flags, tty.as_ref());
+    let (host_glob, user_glob) = (matches.value_of("host"), matches.value_of("user"));
+    ut_vec.retain(|u| glob_allows(host_glob, u.host()) && glob_allows(user_glob, u.user()));
+    #[cfg(not(target_os = "openbsd"))]
+    let changes = if flags.time {
+        // The shown entries, back in the order of the database
+        let shown: HashSet<&Utmpx> = ut_vec.iter().copied().collect();
+        clock_changes(records.iter().filter(|u| shown.contains(u)))
+    } else {
+        Vec::new()
+    };
+    sort_entries(&mut ut_vec);
 
-        println!("\n# users={}", counter);
-        return;
+    if let Some(interval) = follow_interval {
+        let res = follow(&matches, flags, lookup_timeout, interval);
+        return exit_status(res, "failed to refresh");
     }
 
-    if flags.heading {
-        print_header(flags);
+    let users = ut_vec.iter().filter(|u| u.is_user_process()).map(|u| u.user().to_string());
+    if flags.count {
+        return exit_status(print_count(&mut stdout.lock(), users), "failed to write output");
+    }
+    if flags.terse {
+        return exit_status(print_terse(&mut stdout.lock(), users), "failed to write output");
     }
 
-    print_info(&ut_vec, flags);
+    #[cfg(not(target_os = "openbsd"))]
+    let groups: io::Result<Vec<_>> = groups
+        .iter()
+        .map(|uts| {
+            let mut ut_vec = filter_entries(uts, flags, tty.as_ref());
+            ut_vec.retain(|u| glob_allows(host_glob, u.host()) && glob_allows(user_glob, u.user()));
+            sort_entries(&mut ut_vec);
+            make_rows(uts, &ut_vec, flags)
+        })
+        .collect();
+    #[cfg(target_os = "openbsd")]
+    let groups = make_rows(&uts, &ut_vec, flags).map(|rows| vec![rows]);
+    let mut groups = match groups {
+        Ok(groups) => groups,
+        Err(err) => {
+            eprintln!("who: {}", err);
+            return 1;
+        },
+    };
+    let (me, current) = if flags.highlight_me {
+        (login_name().map(|name| name.to_string()), current_tty())
+    } else {
+        (None, None)
+    };
+    for rows in &mut groups {
+        if flags.lookup {
+            lookup_rows(rows, lookup_timeout);
+        }
+        if flags.sort_idle {
+            sort_by_idle(rows);
+        }
+        if flags.highlight_me {
+            mark_mine(rows, me.as_deref(), current.as_deref());
+        }
+    }
+
+    let mut out = stdout.lock();
+    let res = print_groups(&mut out, &groups, flags);
+    #[cfg(not(target_os = "openbsd"))]
+    let res = res.and_then(|_| {
+        if flags.tsv || flags.null { Ok(()) } else { print_clock_changes(&mut out, &changes) }
+    });
+
+    exit_status(res.and_then(|_| out.flush()), "failed to write output")
+}
+
+/// Exit status of who after writing its output with the result `res`: 0 on success, 141 if
+/// the reading end of the output was closed, and 1 on any other error, reported as `what`.
+fn exit_status(res: io::Result<()>, what: &str) -> i32 {
+    match res {
+        Ok(()) => 0,
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => 141,
+        Err(err) => {
+            eprintln!("who: {}: {}", what, err);
+            1
+        },
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 struct WhoFlags {
     boot: bool,
     dead: bool,
     heading: bool,
     login: bool,
-    associated_stdin: bool,
+    only_me: bool,
     process: bool,
     count: bool,
+    terse: bool,
     run_level: bool,
     short: bool,
     time: bool,
     message: bool,
     users: bool,
     idle: bool,
+    idle_seconds: bool,
+    debug: bool,
+    tsv: bool,
+    null: bool,
+    sort_idle: bool,
+    lookup: bool,
+    print_empty: bool,
+    all_types: bool,
+    host_width: Option<usize>,
+    host_only: bool,
+    utf8: bool,
+    quote: bool,
+    duration: bool,
+    session: bool,
+    namespaces: bool,
+    endpoint: bool,
+    relative: bool,
+    header_per_group: bool,
+    highlight_me: bool,
+    verify_owner: bool,
+    no_header_on_empty: bool,
+    columns: Option<Columns>,
+    wtmp: bool,
 }
 
 impl WhoFlags {
-    fn from_matches(matches: &ArgMatches<'_>) -> Self {
-        WhoFlags {
+    fn from_matches(matches: &ArgMatches<'_>) -> Result<Self, String> {
+        let host_width = match matches.value_of("host_width") {
+            Some(width) if !matches.is_present("full_host") => Some(
+                width.parse().map_err(|err| format!("invalid host width: {}", err))?,
+            ),
+            _ => None,
+        };
+        let columns = match matches.value_of("columns") {
+            Some(list) => Some(Columns::parse(list)?),
+            None => None,
+        };
+
+        Ok(WhoFlags {
             boot: matches.is_present("boot") || matches.is_present("all"),
             dead: matches.is_present("dead") || matches.is_present("all"),
             heading: matches.is_present("heading"),
             login: matches.is_present("login") || matches.is_present("all"),
-            associated_stdin: matches.is_present("associated_stdin"),
+            only_me: matches.is_present("only_me") || matches.is_present("ARG2"),
             process: matches.is_present("process") || matches.is_present("all"),
             count: matches.is_present("count"),
+            terse: matches.is_present("terse"),
             run_level: matches.is_present("runlevel") || matches.is_present("all"),
             short: matches.is_present("short"),
             time: matches.is_present("time") || matches.is_present("all"),
-            message: matches.is_present("message") || matches.is_present("all"),
+            message: matches.is_present("message")
+                || matches.is_present("message_w")
+                || matches.is_present("all"),
             users: matches.is_present("users") || matches.is_present("all"),
-            idle: matches.is_present("idle") || matches.is_present("all"),
-        }
+            idle: matches.is_present("idle")
+                || matches.is_present("idle_seconds")
+                || matches.is_present("all"),
+            idle_seconds: matches.is_present("idle_seconds"),
+            debug: matches.is_present("debug"),
+            tsv: matches.value_of("output") == Some("tsv"),
+            null: matches.is_present("null"),
+            sort_idle: matches.value_of("sort") == Some("idle"),
+            lookup: matches.is_present("lookup"),
+            print_empty: matches.is_present("print_empty"),
+            all_types: matches.is_present("all_types"),
+            host_width,
+            host_only: matches.is_present("host_only"),
+            utf8: locale_is_utf8(),
+            quote: matches.is_present("quote"),
+            duration: matches.is_present("duration"),
+            session: matches.is_present("session"),
+            namespaces: matches.is_present("namespaces"),
+            endpoint: matches.is_present("endpoint"),
+            relative: matches.is_present("relative"),
+            header_per_group: matches.is_present("header_per_group"),
+            highlight_me: matches.is_present("highlight_me"),
+            verify_owner: matches.is_present("verify_owner"),
+            no_header_on_empty: matches.is_present("no_header_on_empty"),
+            columns,
+            wtmp: match file_operand(matches).and_then(|file| Path::new(file).file_name()) {
+                Some(name) => name.to_string_lossy().contains("wtmp"),
+                None => false,
+            },
+        })
     }
 
     fn is_all_false(&self) -> bool {
@@ -139,355 +410,1585 @@ impl WhoFlags {
     }
 }
 
-fn print_header(flags: WhoFlags) {
-    if flags.is_all_false() {
-        println!("{:<16} {:<10} {:<18} {:<10}", "NAME", "LINE", "TIME", "COMMENT");
-    } else if flags.short {
-        println!("{:<16} {:<10} {:<18}", "NAME", "LINE", "TIME");
-    } else if flags.idle {
-        println!("{:<16} {:<10} {:<18} {:<10} {:<10}", "NAME", "LINE", "TIME", "IDLE", "COMMENT");
-    } else {
-        #[cfg(target_os = "openbsd")]
-        println!("{:<16} {:<10} {:<18} {:<10} {:<10}", "NAME", "LINE", "TIME", "IDLE", "COMMENT");
-        #[cfg(not(target_os = "openbsd"))]
-        println!(
-            "{:<16} {:<10} {:<10} {:<18}  {:<10} {:<10}",
-            "NAME", "LINE", "PID", "TIME", "IDLE", "COMMENT"
-        );
-    }
+/// Text written instead of the entries when there is none and `--print-empty` was given.
+const EMPTY_SENTINEL: &str = "(no entries)";
+
+/// A column that can be picked with `--columns`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    User,
+    Mesg,
+    Line,
+    Pid,
+    Time,
+    Idle,
+    Host,
+    Comment,
 }
 
-#[cfg(target_os = "openbsd")]
-fn filter_entries<'a>(uts: &'a UtmpSet, flags: WhoFlags) -> Vec<&'a Utmp> {
-    if flags.associated_stdin {
-        let curr_tty_name = {
-            let tty = match TTYName::new(FileDescriptor::StdIn) {
-                Ok(t) => t,
-                Err(err) => {
-                    eprintln!("who: failed to get current tty: {}", err);
-                    process::exit(1);
-                },
-            };
+impl Column {
+    const ALL: [Column; 8] = [
+        Column::User,
+        Column::Mesg,
+        Column::Line,
+        Column::Pid,
+        Column::Time,
+        Column::Idle,
+        Column::Host,
+        Column::Comment,
+    ];
+
+    /// Name of the column on `--columns`.
+    fn name(self) -> &'static str {
+        match self {
+            Column::User => "user",
+            Column::Mesg => "mesg",
+            Column::Line => "line",
+            Column::Pid => "pid",
+            Column::Time => "time",
+            Column::Idle => "idle",
+            Column::Host => "host",
+            Column::Comment => "comment",
+        }
+    }
+
+    /// Heading of the column.
+    fn title(self) -> &'static str {
+        match self {
+            Column::User => "NAME",
+            Column::Mesg => "S",
+            Column::Line => "LINE",
+            Column::Pid => "PID",
+            Column::Time => "TIME",
+            Column::Idle => "IDLE",
+            Column::Host => "HOST",
+            Column::Comment => "COMMENT",
+        }
+    }
+
+    /// Terminal columns the column is padded to when it is not the last one.
+    fn width(self) -> usize {
+        match self {
+            Column::User => 12,
+            Column::Mesg => 1,
+            Column::Time => 16,
+            _ => 10,
+        }
+    }
 
-            format!("{}", tty).trim_start_matches("/dev/").to_string()
+    /// The field of `row` in this column.
+    fn field(self, row: &Row, flags: WhoFlags) -> String {
+        let host = || truncate_host(shown_host(&row.host, flags), flags);
+        let field = match self {
+            Column::User => row.user.clone(),
+            Column::Mesg => row.mesg.to_string(),
+            Column::Line => row.line.clone(),
+            Column::Pid => row.pid.clone(),
+            Column::Time => row.time.clone(),
+            Column::Idle if flags.idle_seconds => row.idle.seconds().to_string(),
+            Column::Idle => row.idle.to_string(),
+            Column::Host => host().into_owned(),
+            Column::Comment => format!("({})", host()),
         };
 
-        uts.iter().filter(|u| format!("{}", u.device_name()) == curr_tty_name).collect()
-    } else {
-        uts.iter().collect()
+        quote_field(&field, flags).into_owned()
     }
 }
 
-#[cfg(not(target_os = "openbsd"))]
-fn filter_entries<'a>(uts: &'a UtmpxSet, flags: WhoFlags) -> Vec<&'a Utmpx> {
-    let mut uts_user: Vec<_>;
-    let mut uts_boot: Vec<_>;
-    let mut uts_dead: Vec<_>;
-    let mut uts_login: Vec<_>;
-    let mut uts_runlv: Vec<_>;
-    let mut uts_init: Vec<_>;
-    let mut uts_time: Vec<_>;
-    let mut ut_vec: Vec<&Utmpx> = Vec::with_capacity(uts.len());
+/// The columns picked with `--columns`, in the order they are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Columns {
+    list: [Column; 8],
+    len:  usize,
+}
 
-    if flags.associated_stdin {
-        let curr_tty_name = {
-            let tty = match TTYName::new(FileDescriptor::StdIn) {
-                Ok(t) => t,
-                Err(err) => {
-                    eprintln!("who: failed to get current tty: {}", err);
-                    process::exit(1);
+impl Columns {
+    /// Parse the comma separated column names of `list`, failing with a message for the
+    /// user on a unknown or repeated column.
+    fn parse(list: &str) -> Result<Self, String> {
+        let mut columns = Columns { list: Column::ALL, len: 0 };
+
+        for name in list.split(',') {
+            let column = match Column::ALL.iter().find(|column| column.name() == name) {
+                Some(&column) => column,
+                None => {
+                    let names: Vec<_> = Column::ALL.iter().map(|column| column.name()).collect();
+                    return Err(format!(
+                        "invalid column '{}', the columns are: {}",
+                        name,
+                        names.join(", ")
+                    ));
                 },
             };
+            if columns.iter().any(|c| c == column) {
+                return Err(format!("column '{}' given more than once", name));
+            }
 
-            format!("{}", tty).trim_start_matches("/dev/").to_string()
-        };
-        let uts_iter = uts.iter().filter(|u| format!("{}", u.device_name()) == curr_tty_name);
-
-        uts_user = uts_iter.clone().filter(|u| u.utype() == UserProcess).collect();
-        uts_boot = uts_iter.clone().filter(|u| u.utype() == BootTime).collect();
-        uts_dead = uts_iter.clone().filter(|u| u.utype() == DeadProcess).collect();
-        uts_login = uts_iter.clone().filter(|u| u.utype() == LoginProcess).collect();
-        uts_runlv = uts_iter.clone().filter(|u| u.utype() == RunLevel).collect();
-        uts_init = uts_iter.clone().filter(|u| u.utype() == InitProcess).collect();
-        uts_time = uts_iter.filter(|u| u.utype() == NewTime).collect();
-    } else {
-        uts_user = uts.iter().filter(|u| u.utype() == UserProcess).collect();
-        uts_boot = uts.iter().filter(|u| u.utype() == BootTime).collect();
-        uts_dead = uts.iter().filter(|u| u.utype() == DeadProcess).collect();
-        uts_login = uts.iter().filter(|u| u.utype() == LoginProcess).collect();
-        uts_runlv = uts.iter().filter(|u| u.utype() == RunLevel).collect();
-        uts_init = uts.iter().filter(|u| u.utype() == InitProcess).collect();
-        uts_time = uts.iter().filter(|u| u.utype() == NewTime).collect();
+            columns.list[columns.len] = column;
+            columns.len += 1;
+        }
+
+        Ok(columns)
     }
 
-    if flags.is_all_false() {
-        ut_vec.append(&mut uts_user);
+    fn iter(&self) -> impl Iterator<Item = Column> + '_ { self.list[..self.len].iter().cloned() }
+}
+
+/// Write the login names of `users` on a line, then their number, like `--count`.
+fn print_count(out: &mut impl Write, users: impl IntoIterator<Item = String>) -> io::Result<()> {
+    let mut counter = 0;
+    for user in users {
+        write!(out, "{} ", user)?;
+        counter += 1;
+    }
+
+    writeln!(out, "\n# users={}", counter)
+}
+
+/// Write every user of `users` once per line, sorted.
+fn print_terse(out: &mut impl Write, users: impl IntoIterator<Item = String>) -> io::Result<()> {
+    for user in users.into_iter().collect::<BTreeSet<_>>() {
+        writeln!(out, "{}", user)?;
+    }
+
+    Ok(())
+}
+
+/// Write the rows in the requested format, with the header if requested, unless there are
+/// no rows with `--no-header-on-empty`.
+fn print_output(out: &mut impl Write, rows: &[Row], flags: WhoFlags) -> io::Result<()> {
+    let heading = flags.heading && !(rows.is_empty() && flags.no_header_on_empty);
+    let flags = WhoFlags { heading, ..flags };
+
+    if flags.tsv || flags.null {
+        print_tsv(out, rows, flags)?;
+    } else if let Some(columns) = flags.columns {
+        print_columns(out, rows, columns, flags)?;
     } else {
-        if flags.users {
-            ut_vec.append(&mut uts_user);
-        }
-        if flags.boot {
-            ut_vec.append(&mut uts_boot);
-        }
-        if flags.dead {
-            ut_vec.append(&mut uts_dead);
-        }
-        if flags.login {
-            ut_vec.append(&mut uts_login);
-        }
-        if flags.run_level {
-            ut_vec.append(&mut uts_runlv);
-        }
-        if flags.process {
-            ut_vec.append(&mut uts_init);
-        }
-        if flags.time {
-            ut_vec.append(&mut uts_time);
+        if flags.heading {
+            print_header(out, flags)?;
         }
+        print_info(out, rows, flags)?;
     }
 
-    ut_vec
+    if rows.is_empty() && flags.print_empty {
+        writeln!(out, "{}", EMPTY_SENTINEL)?;
+    }
+
+    Ok(())
 }
 
-// TODO(grayjack): Fix that code reuse after 1.39 release using param_attrs
-#[cfg(not(target_os = "openbsd"))]
-fn print_info(uts: &[&Utmpx], flags: WhoFlags) {
+/// Write each group of rows one after the other, with the header only at the top, or before
+/// every group with `--header-per-group`.
+///
+/// With `--no-header-on-empty`, the top is the first group with rows.
+fn print_groups(out: &mut impl Write, groups: &[Vec<Row>], flags: WhoFlags) -> io::Result<()> {
+    let first = if flags.no_header_on_empty {
+        groups.iter().position(|rows| !rows.is_empty())
+    } else {
+        Some(0)
+    };
+
+    for (i, rows) in groups.iter().enumerate() {
+        let heading = flags.heading && (Some(i) == first || flags.header_per_group);
+        print_output(out, rows, WhoFlags { heading, ..flags })?;
+    }
+
+    Ok(())
+}
+
+fn print_header(out: &mut impl Write, flags: WhoFlags) -> io::Result<()> {
+    // The mesg column is only present when requested
+    let name_width = if flags.message { 16 } else { 12 };
+
+    if flags.highlight_me {
+        write!(out, " ")?;
+    }
+    if flags.verify_owner {
+        write!(out, " ")?;
+    }
+
     if flags.is_all_false() {
-        uts.iter().for_each(|u| {
-            let (msg, _) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<18}   {:<10}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-                format!("({})", u.host())
-            )
-        });
+        write!(
+            out,
+            "{:<name_width$} {:<10} {:<18} {:<10}",
+            "NAME",
+            "LINE",
+            "TIME",
+            "COMMENT",
+            name_width = name_width
+        )?;
     } else if flags.short {
-        uts.iter().for_each(|u| {
-            let (msg, _) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<18}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-            )
-        });
-    } else if flags.idle {
-        uts.iter().for_each(|u| {
-            let (msg, idle) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<18}    {:<10} {:<10}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-                idle,
-                format!("({})", u.host())
-            )
-        });
+        write!(
+            out,
+            "{:<name_width$} {:<10} {:<18}",
+            "NAME",
+            "LINE",
+            "TIME",
+            name_width = name_width
+        )?;
+    } else if flags.idle || cfg!(target_os = "openbsd") {
+        write!(
+            out,
+            "{:<name_width$} {:<10} {:<18} {:<10} {:<10}",
+            "NAME",
+            "LINE",
+            "TIME",
+            "IDLE",
+            "COMMENT",
+            name_width = name_width
+        )?;
     } else {
-        uts.iter().for_each(|u| {
-            let (msg, idle) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<10} {:<18}    {:<10} {:<10}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                u.process_id(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-                idle,
-                format!("({})", u.host())
-            )
-        });
+        write!(
+            out,
+            "{:<name_width$} {:<10} {:<10} {:<18}  {:<10} {:<10}",
+            "NAME",
+            "LINE",
+            "PID",
+            "TIME",
+            "IDLE",
+            "COMMENT",
+            name_width = name_width
+        )?;
+    }
+
+    if flags.duration {
+        write!(out, " DURATION")?;
+    }
+    if flags.session {
+        write!(out, " SESSION")?;
+    }
+    if flags.namespaces {
+        write!(out, " NAMESPACE")?;
+    }
+    if flags.endpoint {
+        write!(out, " ENDPOINT")?;
+    }
+    if flags.all_types {
+        write!(out, " TYPE")?;
+    }
+    writeln!(out)
+}
+
+/// Get the refresh interval of `--follow`, if given, which must be at least a second.
+fn follow_interval(matches: &ArgMatches<'_>) -> Result<Option<Duration>, String> {
+    match matches.value_of("follow").map(str::parse) {
+        Some(Ok(0)) => Err("invalid follow interval: must be at least a second".to_string()),
+        Some(Ok(secs)) => Ok(Some(Duration::from_secs(secs))),
+        Some(Err(err)) => Err(format!("invalid follow interval: {}", err)),
+        None if matches.is_present("follow") => Ok(Some(DEFAULT_FOLLOW_INTERVAL)),
+        None => Ok(None),
     }
 }
 
-// TODO(grayjack): Fix that code reuse after 1.39 release using param_attrs
+/// The FILE operand, `None` if not given or if the two operands form of `who am i` was used.
+fn file_operand<'a>(matches: &'a ArgMatches<'_>) -> Option<&'a str> {
+    if matches.is_present("ARG2") { None } else { matches.value_of("FILE") }
+}
+
+/// The files to read the entries from, the FILE operand followed by every `--file`, empty to
+/// read the system database.
+fn file_operands<'a>(matches: &'a ArgMatches<'_>) -> Vec<&'a str> {
+    let files = matches.values_of("file").into_iter().flatten();
+    file_operand(matches).into_iter().chain(files).collect()
+}
+
+/// Check that the standard input, `-`, is the only file to read if it is given, since it can
+/// be read only once.
+fn check_operands(files: &[&str]) -> Result<(), String> {
+    if files.len() > 1 && files.contains(&"-") {
+        return Err("`-` can't be combined with other files".to_string());
+    }
+
+    Ok(())
+}
+
+/// Read the entries from FILE if given, from the system database otherwise.
 #[cfg(target_os = "openbsd")]
-fn print_info(uts: &[&Utmp], flags: WhoFlags) {
-    if flags.is_all_false() {
-        uts.iter().for_each(|u| {
-            let (msg, _) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<18}   {:<10}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-                format!("({})", u.host())
-            )
-        });
-    } else if flags.short {
-        uts.iter().for_each(|u| {
-            let (msg, _) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<18}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-            )
-        });
-    } else if flags.idle {
-        uts.iter().for_each(|u| {
-            let (msg, idle) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<18}   {:<10} {:<10}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-                idle,
-                format!("({})", u.host())
-            )
-        });
+fn load_entries(matches: &ArgMatches<'_>) -> Result<UtmpSet, String> {
+    if let Some(file) = file_operand(matches) {
+        let file = Path::new(file);
+
+        load_file(file, matches.is_present("ignore_file_errors"))
+            .map_err(|err| format!("failed to read {}: {}", file.display(), err))
     } else {
-        uts.iter().for_each(|u| {
-            let (msg, idle) = def_status(u);
-            println!(
-                "{:<12} {:<3} {:<10} {:<18}   {:<10} {:<10}",
-                u.user(),
-                if flags.message { msg } else { ' ' },
-                u.device_name(),
-                match u.login_time().strftime("%Y-%m-%d %H:%M") {
-                    Ok(t) => t,
-                    Err(err) => {
-                        eprintln!("who: failed to format string: {}", err);
-                        process::exit(1);
-                    },
-                },
-                idle,
-                format!("({})", u.host())
-            )
-        });
+        UtmpSet::system().map_err(|err| format!("failed to get utsp: {}", err))
     }
 }
 
-// TODO(grayjack): Fix that code reuse after 1.39 release using param_attrs
+/// Read the entries from every FILE given, `-` being the standard input, from the system
+/// database otherwise.
 #[cfg(not(target_os = "openbsd"))]
-fn def_status(utmp: &Utmpx) -> (char, String) {
-    let mut dev_file = PathBuf::from("/dev");
-    let dev_name = match utmp.device_name().to_str() {
-        Ok(d) => d,
-        Err(err) => {
-            eprintln!("who: failed to UTF-8 device name: {}", err);
-            process::exit(1);
-        },
-    };
-    dev_file.push(dev_name);
+fn load_entries(matches: &ArgMatches<'_>) -> Result<UtmpxSet, String> {
+    Ok(load_groups(matches)?.into_iter().flatten().collect())
+}
 
-    let msg;
-    let last_change;
-    if let Ok(meta) = dev_file.metadata() {
-        msg = if meta.mode() & (S_IWGRP as u32) == 0 { '-' } else { '+' };
-        last_change = meta.atime();
-    } else {
-        msg = '?';
-        last_change = 0;
-    };
+/// Read the entries of every FILE given as a group of its own, or the system database as the
+/// only group if there is none.
+#[cfg(not(target_os = "openbsd"))]
+fn load_groups(matches: &ArgMatches<'_>) -> Result<Vec<UtmpxSet>, String> {
+    let groups = load_record_groups(matches)?;
+    Ok(groups.into_iter().map(|records| records.into_iter().collect()).collect())
+}
+
+/// Read the entries of every FILE given, `-` being the standard input, as a group of its own,
+/// or of the system database as the only group if there is none, in the order they are
+/// stored, duplicates included.
+#[cfg(not(target_os = "openbsd"))]
+fn load_record_groups(matches: &ArgMatches<'_>) -> Result<Vec<Vec<Utmpx>>, String> {
+    let files = file_operands(matches);
+    if files.is_empty() {
+        return Ok(vec![system_entries()]);
+    }
+
+    let ignore_errors = matches.is_present("ignore_file_errors");
+    files.into_iter().map(|file| load_source(file, ignore_errors)).collect()
+}
 
-    let idle = if last_change == 0 {
-        "?".to_string()
+/// Read the entries of `file`, `-` being the standard input, in the order they are stored,
+/// or of the system database if `file` can't be read and `ignore_errors`.
+#[cfg(not(target_os = "openbsd"))]
+fn load_source(file: &str, ignore_errors: bool) -> Result<Vec<Utmpx>, String> {
+    if file == "-" {
+        let stdin = io::stdin();
+        let res: io::Result<Vec<_>> = UtmpxReader::new(stdin.lock()).collect();
+        res.map_err(|err| format!("failed to read entries from standard input: {}", err))
     } else {
-        let now = time::now().to_timespec().sec;
-        if 0 < last_change && now - 24 * 3600 < last_change && last_change <= now {
-            let seconds_idle = now - last_change;
-            if seconds_idle < 60 {
-                ".".to_string()
-            } else {
-                format!("{:02}:{:02}", seconds_idle / 3600, (seconds_idle % 3600) / 60)
-            }
-        } else {
-            "old".to_string()
+        match read_records(Path::new(file)) {
+            Err(_) if ignore_errors => Ok(system_entries()),
+            res => res.map_err(|err| format!("failed to read {}: {}", file, err)),
         }
-    };
+    }
+}
+
+/// Read the entries of `file` in the order they are stored.
+///
+/// With the `gzip` feature, a gzip-compressed `file` is decompressed while it is read.
+#[cfg(not(target_os = "openbsd"))]
+fn read_records(file: &Path) -> io::Result<Vec<Utmpx>> {
+    let reader = io::BufReader::new(fs::File::open(file)?);
+    #[cfg(feature = "gzip")]
+    {
+        if is_gzip(file) {
+            return UtmpxReader::new(GzDecoder::new(reader)).collect();
+        }
+    }
 
-    (msg, idle)
+    UtmpxReader::new(reader).collect()
 }
 
-// TODO(grayjack): Fix that code reuse after 1.39 release using param_attrs
+/// Read the entries of `file`, or of the system database if `file` can't be read and
+/// `ignore_errors`.
 #[cfg(target_os = "openbsd")]
-fn def_status(utmp: &Utmp) -> (char, String) {
-    let mut dev_file = PathBuf::from("/dev");
-    let dev_name = match utmp.device_name().to_str() {
-        Ok(d) => d,
-        Err(err) => {
-            eprintln!("who: failed to UTF-8 device name: {}", err);
-            process::exit(1);
-        },
-    };
-    dev_file.push(dev_name);
+fn load_file(file: &Path, ignore_errors: bool) -> io::Result<UtmpSet> {
+    match UtmpSet::from_file(file) {
+        Err(_) if ignore_errors => UtmpSet::system(),
+        res => res,
+    }
+}
 
-    let msg;
-    let last_change;
-    if let Ok(meta) = dev_file.metadata() {
-        msg = if meta.mode() & (S_IWGRP as u32) == 0 { '-' } else { '+' };
-        last_change = meta.atime();
-    } else {
-        msg = '?';
-        last_change = 0;
-    };
+/// Check if `file` is compressed with gzip, from its magic bytes rather than its name, so
+/// rotated files like `wtmp.1.gz` and renamed ones are both found.
+#[cfg(all(not(target_os = "openbsd"), feature = "gzip"))]
+fn is_gzip(file: &Path) -> bool {
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
-    let idle = if last_change == 0 {
-        "?".to_string()
-    } else {
-        let now = time::now().to_timespec().sec;
-        if 0 < last_change && now - 24 * 3600 < last_change && last_change <= now {
-            let seconds_idle = now - last_change;
-            if seconds_idle < 60 {
-                ".".to_string()
-            } else {
-                format!("{:02}:{:02}", seconds_idle / 3600, (seconds_idle % 3600) / 60)
-            }
-        } else {
-            "old".to_string()
+    let mut magic = [0; 2];
+    match fs::File::open(file) {
+        Ok(mut f) => f.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC,
+        Err(_) => false,
+    }
+}
+
+/// Clear the screen and display the entries again every `interval`, followed by the logins
+/// and logouts since the last refresh, until interrupted with SIGINT.
+fn follow(
+    matches: &ArgMatches<'_>, flags: WhoFlags, lookup_timeout: Duration, interval: Duration,
+) -> io::Result<()> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    flag::register(SIGINT, Arc::clone(&interrupted))?;
+
+    let stdout = io::stdout();
+    let _screen = AltScreen::enter()?;
+
+    #[cfg(not(target_os = "openbsd"))]
+    let mut watcher: Option<UtmpWatcher> = None;
+    #[cfg(target_os = "openbsd")]
+    let mut previous: Option<Vec<Row>> = None;
+    let tty = TTYName::new(FileDescriptor::StdIn).ok();
+    loop {
+        let uts = load_entries(matches).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let mut ut_vec = filter_entries(&uts, flags, tty.as_ref());
+        let (host_glob, user_glob) = (matches.value_of("host"), matches.value_of("user"));
+        ut_vec.retain(|u| glob_allows(host_glob, u.host()) && glob_allows(user_glob, u.user()));
+        sort_entries(&mut ut_vec);
+
+        let mut rows = make_rows(&uts, &ut_vec, flags)?;
+        if flags.lookup {
+            lookup_rows(&mut rows, lookup_timeout);
         }
-    };
+        if flags.sort_idle {
+            sort_by_idle(&mut rows);
+        }
+
+        // Nothing changed before the first refresh
+        #[cfg(not(target_os = "openbsd"))]
+        let changes = {
+            let shown: UtmpxSet = ut_vec.iter().map(|u| (*u).clone()).collect();
+            match &mut watcher {
+                Some(watcher) => {
+                    let mut events = Vec::new();
+                    watcher.update(&shown, |event| events.push(event));
+                    Some(event_changes(events)?)
+                },
+                None => {
+                    watcher = Some(UtmpWatcher::from_snapshot(&shown));
+                    None
+                },
+            }
+        };
+        #[cfg(target_os = "openbsd")]
+        let changes = previous.as_ref().map(|previous| row_changes(previous, &rows));
+
+        let mut out = stdout.lock();
+        write!(out, "{}", CLEAR_SCREEN)?;
+        print_output(&mut out, &rows, flags)?;
+        if let Some(changes) = &changes {
+            print_changes(&mut out, changes)?;
+        }
+        out.flush()?;
+        #[cfg(target_os = "openbsd")]
+        {
+            previous = Some(rows);
+        }
+
+        // Sleep in small steps so a SIGINT is handled without waiting for the whole interval
+        let wake_up = Instant::now() + interval;
+        while !interrupted.load(Ordering::Relaxed) && Instant::now() < wake_up {
+            thread::sleep(Duration::from_millis(100).min(interval));
+        }
+        if interrupted.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+    }
+}
+
+/// The alternate screen of `--follow`, left when dropped so the terminal is restored on
+/// every exit path, errors included.
+struct AltScreen;
+
+impl AltScreen {
+    fn enter() -> io::Result<Self> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        write!(out, "{}", ENTER_ALT_SCREEN)?;
+        out.flush()?;
+
+        Ok(AltScreen)
+    }
+}
+
+impl Drop for AltScreen {
+    fn drop(&mut self) {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        // The error that ended `--follow` matters more than failing to leave the screen
+        let _ = write!(out, "{}", LEAVE_ALT_SCREEN).and_then(|_| out.flush());
+    }
+}
+
+/// The rows that appeared and disappeared between two refreshes of `--follow`.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RowChanges {
+    logins:  Vec<Row>,
+    logouts: Vec<Row>,
+}
+
+/// Render the logins and logouts reported by a `UtmpWatcher` as rows.
+#[cfg(not(target_os = "openbsd"))]
+fn event_changes(events: Vec<UtmpEvent>) -> io::Result<RowChanges> {
+    let mut changes = RowChanges::default();
+    for event in events {
+        match event {
+            UtmpEvent::Login(u) => changes.logins.push(Row::new(&u)?),
+            UtmpEvent::Logout(u) => changes.logouts.push(Row::new(&u)?),
+        }
+    }
+    Ok(changes)
+}
+
+/// Compare two successive sets of rows. The columns that change while a user stays logged
+/// in, like idle time, are not taken into account.
+#[cfg(target_os = "openbsd")]
+fn row_changes(previous: &[Row], current: &[Row]) -> RowChanges {
+    let previous_keys: HashSet<_> = previous.iter().map(Row::session_key).collect();
+    let current_keys: HashSet<_> = current.iter().map(Row::session_key).collect();
+    let missing = |rows: &[Row], keys: &HashSet<_>| -> Vec<Row> {
+        rows.iter().filter(|row| !keys.contains(&row.session_key())).cloned().collect()
+    };
+
+    RowChanges {
+        logins:  missing(current, &previous_keys),
+        logouts: missing(previous, &current_keys),
+    }
+}
+
+/// Write the logins and logouts of a refresh of `--follow`.
+fn print_changes(out: &mut impl Write, changes: &RowChanges) -> io::Result<()> {
+    if changes.logins.is_empty() && changes.logouts.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    for row in &changes.logins {
+        writeln!(out, "+ {} {} {}", row.user, row.line, row.time)?;
+    }
+    for row in &changes.logouts {
+        writeln!(out, "- {} {} {}", row.user, row.line, row.time)?;
+    }
+
+    Ok(())
+}
+
+/// Write every entry of `records`, regardless of its type, in the `utmpdump`(1) text
+/// format, in their order.
+#[cfg(not(target_os = "openbsd"))]
+fn print_dump(out: &mut impl Write, records: &[Utmpx]) -> io::Result<()> {
+    for u in records {
+        dump_entry(out, u)?;
+    }
+
+    Ok(())
+}
+
+/// Get the last system boot entry of `uts`, the latest one if there are many.
+#[cfg(not(target_os = "openbsd"))]
+fn boot_time(uts: &UtmpxSet) -> Option<&Utmpx> {
+    uts.iter().filter(|u| u.is_boot_time()).max_by_key(|u| u.login_timespec())
+}
+
+/// Write the `system boot YYYY-MM-DDTHH:MM:SS` line of `--boot-header`, nothing if `uts` has
+/// no boot entry.
+#[cfg(not(target_os = "openbsd"))]
+fn print_boot_header(out: &mut impl Write, uts: &UtmpxSet) -> io::Result<()> {
+    match boot_time(uts) {
+        Some(boot) => match time::format(boot.login_timespec(), "%Y-%m-%dT%H:%M:%S") {
+            Ok(time) => writeln!(out, "system boot {}", time),
+            Err(err) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("failed to format string: {}", err),
+            )),
+        },
+        None => Ok(()),
+    }
+}
+
+/// Read the `--dump` text of `file`, of the standard input if it's `None` or `-`.
+#[cfg(not(target_os = "openbsd"))]
+fn read_dump(file: Option<&str>) -> io::Result<Vec<u8>> {
+    match file {
+        Some("-") | None => {
+            let mut dump = Vec::new();
+            io::stdin().lock().read_to_end(&mut dump)?;
+            Ok(dump)
+        },
+        Some(file) => fs::read(file),
+    }
+}
+
+/// Write the entries of the `--dump` text `dump` to `out` as a utmpx file. Empty lines are
+/// ignored.
+#[cfg(not(target_os = "openbsd"))]
+fn undump(dump: &[u8], out: &mut impl Write) -> io::Result<()> {
+    for (num, line) in dump.lines().enumerate().filter(|(_, line)| !line.is_empty()) {
+        match parse_dump_line(line) {
+            Some(u) => write_entry_to(out, &u)?,
+            None => {
+                let msg = format!("invalid entry at line {}", num + 1);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            },
+        }
+    }
+
+    out.flush()
+}
+
+/// Write every entry of `records` with all its decoded fields, regardless of its type,
+/// numbered in the order they were read.
+#[cfg(not(target_os = "openbsd"))]
+fn write_debug(out: &mut impl Write, records: &[Utmpx]) -> io::Result<()> {
+    for (num, u) in records.iter().enumerate() {
+        writeln!(out, "record {}:", num)?;
+        writeln!(out, "    type: {}", u.utype())?;
+        writeln!(out, "    id:   {}", u.id())?;
+        writeln!(out, "    line: {}", u.device_name())?;
+        writeln!(out, "    pid:  {}", u.process_id())?;
+        writeln!(out, "    user: {}", u.user())?;
+        writeln!(out, "    host: {}", u.host())?;
+        writeln!(out, "    time: {}.{:06}", u.timeval().tv_sec, u.timeval().tv_usec)?;
+        #[cfg(target_os = "linux")]
+        match u.address() {
+            Some(addr) => writeln!(out, "    addr: {}", addr)?,
+            None => writeln!(out, "    addr:")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Write every entry of `uts` with all its decoded fields.
+#[cfg(target_os = "openbsd")]
+fn write_debug(out: &mut impl Write, uts: &UtmpSet) -> io::Result<()> {
+    let mut entries: Vec<_> = uts.iter().collect();
+    entries.sort_by_key(|u| u.login_timespec());
+
+    for (num, u) in entries.iter().enumerate() {
+        writeln!(out, "record {}:", num)?;
+        writeln!(out, "    line: {}", u.device_name())?;
+        writeln!(out, "    user: {}", u.user())?;
+        writeln!(out, "    host: {}", u.host())?;
+        writeln!(out, "    time: {}", u.time())?;
+    }
+
+    Ok(())
+}
+
+/// The line of the terminal on the standard input, `None` if it is not a terminal.
+fn current_tty() -> Option<String> {
+    TTYName::new(FileDescriptor::StdIn).ok().map(|tty| tty.short().to_string())
+}
+
+/// Mark the rows of the user `me` on other lines than `current`, the line of the terminal
+/// of who, as the sessions of the current user elsewhere. Nothing is marked if the login
+/// name of the user is unknown.
+fn mark_mine(rows: &mut [Row], me: Option<&str>, current: Option<&str>) {
+    let me = match me {
+        Some(me) => me,
+        None => return,
+    };
+
+    for row in rows {
+        row.mine = row.user == me && Some(row.line.as_str()) != current;
+    }
+}
+
+/// Select the entries of `uts` to show with `flags`. `tty` is the terminal of the standard
+/// input, if any, the only line kept by `-m`.
+#[cfg(target_os = "openbsd")]
+fn filter_entries<'a>(
+    uts: &'a UtmpSet, flags: WhoFlags, tty: Option<&TTYName>,
+) -> Vec<&'a Utmp> {
+    if flags.only_me {
+        // Like GNU who, there is nothing to show if the standard input is not a terminal
+        let curr_tty_name = match tty {
+            Some(tty) => tty.short(),
+            None => return Vec::new(),
+        };
+
+        uts.iter().filter(|u| u.device_name() == curr_tty_name).collect()
+    } else {
+        uts.iter().collect()
+    }
+}
+
+/// Select the entries of `uts` to show with `flags`. `tty` is the terminal of the standard
+/// input, if any, the only line kept by `-m`.
+#[cfg(not(target_os = "openbsd"))]
+fn filter_entries<'a>(
+    uts: &'a UtmpxSet, flags: WhoFlags, tty: Option<&TTYName>,
+) -> Vec<&'a Utmpx> {
+    let mut uts_all: Vec<_>;
+    let mut uts_user: Vec<_>;
+    let mut uts_boot: Vec<_>;
+    let mut uts_dead: Vec<_>;
+    let mut uts_login: Vec<_>;
+    let mut uts_runlv: Vec<_>;
+    let mut uts_init: Vec<_>;
+    let mut uts_time: Vec<_>;
+    let mut ut_vec: Vec<&Utmpx> = Vec::with_capacity(uts.len());
+
+    if flags.only_me {
+        // Like GNU who, there is nothing to show if the standard input is not a terminal
+        let curr_tty_name = match tty {
+            Some(tty) => tty.short(),
+            None => return Vec::new(),
+        };
+        let uts_iter = uts.iter().filter(|u| u.device_name() == curr_tty_name);
+
+        uts_all = uts_iter.clone().collect();
+        uts_user = uts_iter.clone().filter(|u| u.is_user_process()).collect();
+        uts_boot = uts_iter.clone().filter(|u| u.is_boot_time()).collect();
+        uts_dead = uts_iter.clone().filter(|u| u.is_dead_process()).collect();
+        uts_login = uts_iter.clone().filter(|u| u.is_login_process()).collect();
+        uts_runlv = uts_iter.clone().filter(|u| u.is_run_level()).collect();
+        uts_init = uts_iter.clone().filter(|u| u.is_init_process()).collect();
+        uts_time = uts_iter.filter(|u| u.is_new_time() || u.is_old_time()).collect();
+    } else {
+        uts_all = uts.iter().collect();
+        uts_user = uts.iter().filter(|u| u.is_user_process()).collect();
+        uts_boot = uts.iter().filter(|u| u.is_boot_time()).collect();
+        uts_dead = uts.iter().filter(|u| u.is_dead_process()).collect();
+        uts_login = uts.iter().filter(|u| u.is_login_process()).collect();
+        uts_runlv = uts.iter().filter(|u| u.is_run_level()).collect();
+        uts_init = uts.iter().filter(|u| u.is_init_process()).collect();
+        uts_time = uts.iter().filter(|u| u.is_new_time() || u.is_old_time()).collect();
+    }
+
+    if flags.all_types {
+        ut_vec.append(&mut uts_all);
+    } else if flags.is_all_false() {
+        ut_vec.append(&mut uts_user);
+    } else {
+        if flags.users {
+            ut_vec.append(&mut uts_user);
+        }
+        if flags.boot {
+            ut_vec.append(&mut uts_boot);
+        }
+        if flags.dead {
+            ut_vec.append(&mut uts_dead);
+        }
+        if flags.login {
+            ut_vec.append(&mut uts_login);
+        }
+        if flags.run_level {
+            ut_vec.append(&mut uts_runlv);
+        }
+        if flags.process {
+            ut_vec.append(&mut uts_init);
+        }
+        if flags.time {
+            ut_vec.append(&mut uts_time);
+        }
+    }
+
+    ut_vec
+}
+
+/// Pair the old time entries of `uts` with the new time entries, as the clock before and
+/// after each change.
+///
+/// The entries must be in the order of the database, where each old time entry is written
+/// right before the new time entry of the same change.
+#[cfg(not(target_os = "openbsd"))]
+fn clock_changes<'a>(uts: impl IntoIterator<Item = &'a Utmpx>) -> Vec<(Tm, Tm)> {
+    let times: Vec<_> = uts.into_iter().filter(|u| u.is_old_time() || u.is_new_time()).collect();
+
+    times
+        .windows(2)
+        .filter(|pair| pair[0].is_old_time() && pair[1].is_new_time())
+        .map(|pair| (pair[0].login_time(), pair[1].login_time()))
+        .collect()
+}
+
+#[cfg(not(target_os = "openbsd"))]
+fn print_clock_changes(out: &mut impl Write, changes: &[(Tm, Tm)]) -> io::Result<()> {
+    for (old, new) in changes {
+        writeln!(out, "clock changed from {} to {}", format_time(old)?, format_time(new)?)?;
+    }
+
+    Ok(())
+}
+
+/// Sort the entries by login time, then type, line and user, so the output is the same
+/// between runs.
+///
+/// GNU who shows the entries in the order of the database, that is by time, and the entries
+/// written at the same time are in the order they are written at boot and login, given by
+/// [`type_order`]. The sort is stable, so equal entries keep their order.
+#[cfg(not(target_os = "openbsd"))]
+fn sort_entries(uts: &mut [&Utmpx]) {
+    uts.sort_by(|a, b| {
+        (a.login_timespec(), type_order(a.utype()), a.device_name(), a.user()).cmp(&(
+            b.login_timespec(),
+            type_order(b.utype()),
+            b.device_name(),
+            b.user(),
+        ))
+    });
+}
+
+/// Position of the entries of type `utype` among entries of the same time: the boot first,
+/// then the runlevel change, a clock change, the init, login and user processes of a login,
+/// and the dead processes last. Other types go after all of them.
+#[cfg(not(target_os = "openbsd"))]
+fn type_order(utype: UtmpxType) -> u8 {
+    match utype {
+        UtmpxType::BootTime => 0,
+        UtmpxType::RunLevel => 1,
+        UtmpxType::OldTime => 2,
+        UtmpxType::NewTime => 3,
+        UtmpxType::InitProcess => 4,
+        UtmpxType::LoginProcess => 5,
+        UtmpxType::UserProcess => 6,
+        UtmpxType::DeadProcess => 7,
+        _ => 8,
+    }
+}
+
+/// Sort the entries by login time, then line and then user, so the output is the same
+/// between runs.
+#[cfg(target_os = "openbsd")]
+fn sort_entries(uts: &mut [&Utmp]) {
+    uts.sort_by(|a, b| {
+        (a.login_timespec(), a.device_name(), a.user()).cmp(&(
+            b.login_timespec(),
+            b.device_name(),
+            b.user(),
+        ))
+    });
+}
+
+/// Sort the rows by idle time, the longest first, with the old and unknown ones before and
+/// the active ones last. Rows with the same idle time keep their order.
+fn sort_by_idle(rows: &mut [Row]) {
+    rows.sort_by_key(|row| match row.idle {
+        IdleTime::Old | IdleTime::Unknown => (0, 0),
+        IdleTime::Idle(secs) => (1, -secs),
+        IdleTime::Active(secs) => (2, -secs),
+    });
+}
+
+/// A entry with every column already rendered, so it can be written in any output format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    user:      String,
+    mesg:      char,
+    line:      String,
+    pid:       String,
+    /// `run-level N (prev M)` on run-level entries, shown instead of the process ID.
+    comment:   String,
+    time:      String,
+    epoch:     i64,
+    idle:      IdleTime,
+    idle_secs: Option<i64>,
+    host:      String,
+    utype:     String,
+    duration:  Option<i64>,
+    session:   Option<i32>,
+    namespace: Option<bool>,
+    endpoint:  String,
+    /// A session of the current user on another terminal.
+    mine:      bool,
+    /// `!` if the terminal is owned by another user, `?` if it is missing.
+    owner:     Option<char>,
+}
+
+impl Row {
+    /// The columns identifying a login session.
+    #[cfg(target_os = "openbsd")]
+    fn session_key(&self) -> (&str, &str, &str, i64) {
+        (&self.user, &self.line, &self.pid, self.epoch)
+    }
+
+    // TODO(grayjack): Fix that code reuse after 1.39 release using param_attrs
+    #[cfg(not(target_os = "openbsd"))]
+    fn new(u: &Utmpx) -> io::Result<Self> {
+        let (mesg, last_change) = device_status(u.device_name());
+
+        Ok(Row {
+            user: u.user().to_string(),
+            mesg,
+            line: u.device_name().to_string(),
+            pid: u.process_id().to_string(),
+            comment: match u.run_level() {
+                Some((current, previous)) => format!("run-level {} (prev {})", current, previous),
+                None => String::new(),
+            },
+            time: format_time(&u.login_time())?,
+            epoch: u.login_timespec().sec,
+            idle: IdleTime::new(last_change),
+            idle_secs: idle_seconds(last_change),
+            host: u.host().to_string(),
+            utype: u.utype().to_string(),
+            duration: None,
+            session: u.session_id(),
+            namespace: None,
+            endpoint: u.remote_endpoint().map(|endpoint| endpoint.to_string()).unwrap_or_default(),
+            mine: false,
+            owner: None,
+        })
+    }
+
+    /// A session of systemd-logind, shown like a user process entry. Graphical sessions
+    /// without a terminal show their display as line and host.
+    #[cfg(all(target_os = "linux", feature = "logind"))]
+    fn from_session(session: &logind::Session) -> io::Result<Self> {
+        let (mesg, last_change) = if session.tty.is_empty() {
+            ('?', 0)
+        } else {
+            device_status(session.tty.as_bytes().as_bstr())
+        };
+        let line = if session.tty.is_empty() { &session.display } else { &session.tty };
+        let host =
+            if session.remote_host.is_empty() { &session.display } else { &session.remote_host };
+
+        Ok(Row {
+            user: session.user.clone(),
+            mesg,
+            line: line.clone(),
+            pid: session.leader.map(|pid| pid.to_string()).unwrap_or_default(),
+            comment: String::new(),
+            time: format_time(&time::to_local(time::Timespec::new(session.started, 0)))?,
+            epoch: session.started,
+            idle: IdleTime::new(last_change),
+            idle_secs: idle_seconds(last_change),
+            host: host.clone(),
+            utype: "USER_PROCESS".to_string(),
+            duration: None,
+            session: session.id.parse().ok(),
+            namespace: None,
+            endpoint: RemoteEndpoint::parse(session.remote_host.as_bytes().as_bstr())
+                .map(|endpoint| endpoint.to_string())
+                .unwrap_or_default(),
+            mine: false,
+            owner: None,
+        })
+    }
+
+    // TODO(grayjack): Fix that code reuse after 1.39 release using param_attrs
+    #[cfg(target_os = "openbsd")]
+    fn new(u: &Utmp) -> io::Result<Self> {
+        let (mesg, last_change) = device_status(u.device_name());
+
+        Ok(Row {
+            user: u.user().to_string(),
+            mesg,
+            line: u.device_name().to_string(),
+            pid: String::new(),
+            comment: String::new(),
+            time: format_time(&u.login_time())?,
+            epoch: u.login_timespec().sec,
+            idle: IdleTime::new(last_change),
+            idle_secs: idle_seconds(last_change),
+            host: u.host().to_string(),
+            utype: String::new(),
+            duration: None,
+            session: None,
+            namespace: None,
+            endpoint: String::new(),
+            mine: false,
+            owner: None,
+        })
+    }
+}
+
+/// Render the user `sessions` of systemd-logind as rows, in the same order as the entries.
+#[cfg(all(target_os = "linux", feature = "logind"))]
+fn session_rows(
+    sessions: &[logind::Session], matches: &ArgMatches<'_>, flags: WhoFlags,
+) -> io::Result<Vec<Row>> {
+    // The sessions are all user processes
+    if !(flags.users || flags.all_types || flags.is_all_false()) {
+        return Ok(Vec::new());
+    }
+
+    let (host_glob, user_glob) = (matches.value_of("host"), matches.value_of("user"));
+    let mut sessions: Vec<_> = sessions
+        .iter()
+        .filter(|s| {
+            glob_allows(host_glob, s.remote_host.as_bytes().as_bstr())
+                && glob_allows(user_glob, s.user.as_bytes().as_bstr())
+        })
+        .collect();
+    sessions.sort_by(|a, b| (a.started, &a.tty, &a.user).cmp(&(b.started, &b.tty, &b.user)));
+
+    let mut rows = sessions.into_iter().map(Row::from_session).collect::<io::Result<Vec<_>>>()?;
+    if flags.relative {
+        relative_times(&mut rows, time::now().sec);
+    }
+
+    Ok(rows)
+}
+
+/// Render the entries of `ut_vec`, taken from `uts`, as rows.
+#[cfg(not(target_os = "openbsd"))]
+fn make_rows(uts: &UtmpxSet, ut_vec: &[&Utmpx], flags: WhoFlags) -> io::Result<Vec<Row>> {
+    let mut rows = ut_vec.iter().map(|u| Row::new(u)).collect::<io::Result<Vec<_>>>()?;
+
+    if flags.duration {
+        let now = time::now().sec;
+        let durations = session_durations(uts, ut_vec, flags.wtmp, now);
+        for (row, duration) in rows.iter_mut().zip(durations) {
+            row.duration = Some(duration);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        if flags.namespaces {
+            for (row, u) in rows.iter_mut().zip(ut_vec) {
+                row.namespace = u.in_host_namespace();
+            }
+        }
+    }
+
+    if flags.relative {
+        relative_times(&mut rows, time::now().sec);
+    }
+
+    if flags.verify_owner {
+        let user_rows =
+            rows.iter_mut().zip(ut_vec).filter(|(_, u)| u.is_user_process()).map(|(row, _)| row);
+        verify_owners(user_rows, Path::new("/dev"), &mut NameResolver::new());
+    }
+
+    Ok(rows)
+}
+
+/// Render the entries of `ut_vec` as rows.
+#[cfg(target_os = "openbsd")]
+fn make_rows(_uts: &UtmpSet, ut_vec: &[&Utmp], flags: WhoFlags) -> io::Result<Vec<Row>> {
+    let mut rows = ut_vec.iter().map(|u| Row::new(u)).collect::<io::Result<Vec<_>>>()?;
+
+    if flags.relative {
+        relative_times(&mut rows, time::now().sec);
+    }
+
+    if flags.verify_owner {
+        let user_rows = rows.iter_mut().filter(|row| !row.user.is_empty());
+        verify_owners(user_rows, Path::new("/dev"), &mut NameResolver::new());
+    }
+
+    Ok(rows)
+}
+
+/// Mark the `rows` whose terminal on `dev` is owned by another user than the one logged in
+/// on it with `!`, and the ones whose terminal is missing with `?`, looking the owners up on
+/// `names`.
+fn verify_owners<'a>(
+    rows: impl IntoIterator<Item = &'a mut Row>, dev: &Path, names: &mut NameResolver<impl Lookup>,
+) {
+    for row in rows {
+        row.owner = match fs::metadata(dev.join(&row.line)) {
+            Ok(meta) => match names.user_name(meta.uid()) {
+                Some(owner) if *owner == row.user.as_bytes() => None,
+                _ => Some('!'),
+            },
+            Err(_) => Some('?'),
+        };
+    }
+}
+
+/// Show the login time of the `rows` relative to `now` with `--relative`, the ones without a
+/// relative time keeping the absolute one.
+fn relative_times(rows: &mut [Row], now: i64) {
+    for row in rows {
+        if let Some(time) = relative_time(row.epoch, now) {
+            row.time = time;
+        }
+    }
+}
+
+/// Format the time `epoch` relative to `now`, like `3m ago`, in the largest unit it has at
+/// least one of, `None` if it is in the future or a week or more before `now`.
+fn relative_time(epoch: i64, now: i64) -> Option<String> {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const WEEK: i64 = 7 * DAY;
+
+    match now.checked_sub(epoch)? {
+        secs if !(0..WEEK).contains(&secs) => None,
+        secs if secs >= DAY => Some(format!("{}d ago", secs / DAY)),
+        secs if secs >= HOUR => Some(format!("{}h ago", secs / HOUR)),
+        secs if secs >= MINUTE => Some(format!("{}m ago", secs / MINUTE)),
+        secs => Some(format!("{}s ago", secs)),
+    }
+}
+
+/// Seconds each session of `logins` lasted. In a wtmp file a session ends with the first
+/// dead process entry of its line from `uts` after the login, otherwise, or if there is no
+/// such entry, it lasts until `now`.
+#[cfg(not(target_os = "openbsd"))]
+fn session_durations(uts: &UtmpxSet, logins: &[&Utmpx], wtmp: bool, now: i64) -> Vec<i64> {
+    let mut logouts: HashMap<&BStr, Vec<i64>> = HashMap::new();
+    if wtmp {
+        for u in uts.iter().filter(|u| u.is_dead_process()) {
+            logouts.entry(u.device_name()).or_default().push(u.login_timespec().sec);
+        }
+        for times in logouts.values_mut() {
+            times.sort_unstable();
+        }
+    }
+
+    logins
+        .iter()
+        .map(|u| {
+            let login = u.login_timespec().sec;
+            let logout = logouts
+                .get(u.device_name())
+                .and_then(|times| times.iter().find(|time| **time >= login))
+                .copied()
+                .unwrap_or(now);
+            (logout - login).max(0)
+        })
+        .collect()
+}
+
+fn print_info(out: &mut impl Write, rows: &[Row], flags: WhoFlags) -> io::Result<()> {
+    for row in rows {
+        let mesg = mesg_column(row.mesg, flags);
+        let user = quote_field(&row.user, flags);
+        let line = quote_field(&row.line, flags);
+        let pid = quote_field(if row.comment.is_empty() { &row.pid } else { &row.comment }, flags);
+        let time = quote_field(&row.time, flags);
+        let idle =
+            if flags.idle_seconds { row.idle.seconds().to_string() } else { row.idle.to_string() };
+        let idle = quote_field(&idle, flags);
+        let host = format!("({})", truncate_host(shown_host(&row.host, flags), flags));
+        let host = quote_field(&host, flags);
+        // User names, lines and hosts may have characters wider than a column
+        let (user, line, host) = (pad(&user, 12), pad(&line, 10), pad(&host, 10));
+
+        if flags.highlight_me {
+            write!(out, "{}", if row.mine { '*' } else { ' ' })?;
+        }
+        if flags.verify_owner {
+            write!(out, "{}", row.owner.unwrap_or(' '))?;
+        }
+        if flags.is_all_false() {
+            write!(out, "{} {}{} {:<16}     {}", user, mesg, line, time, host)?;
+        } else if flags.short {
+            write!(out, "{} {}{} {:<16}", user, mesg, line, time)?;
+        } else if flags.idle && !cfg!(target_os = "openbsd") {
+            write!(out, "{} {}{} {:<16}    {:<10} {}", user, mesg, line, time, idle, host)?;
+        } else if cfg!(target_os = "openbsd") {
+            // There is no PID to show on OpenBSD
+            write!(out, "{} {}{} {:<16}   {:<10} {}", user, mesg, line, time, idle, host)?;
+        } else {
+            write!(
+                out,
+                "{} {}{} {:<10} {:<16}    {:<10} {}",
+                user, mesg, line, pid, time, idle, host
+            )?;
+        }
+
+        if flags.duration {
+            write!(out, " {}", quote_field(&format_duration(row.duration), flags))?;
+        }
+        if flags.session {
+            let session = row.session.map(|id| id.to_string()).unwrap_or_default();
+            write!(out, " {}", quote_field(&session, flags))?;
+        }
+        if flags.namespaces {
+            write!(out, " {}", quote_field(namespace_column(row.namespace), flags))?;
+        }
+        if flags.endpoint {
+            write!(out, " {}", quote_field(&row.endpoint, flags))?;
+        }
+        if flags.all_types {
+            write!(out, " {}", quote_field(&row.utype, flags))?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Write the rows with only the `columns` picked with `--columns`, with the header if
+/// requested.
+fn print_columns(
+    out: &mut impl Write, rows: &[Row], columns: Columns, flags: WhoFlags,
+) -> io::Result<()> {
+    // The last column is not padded, so the lines have no trailing spaces
+    let write_line = |out: &mut dyn Write, fields: Vec<String>| {
+        let last = fields.len().saturating_sub(1);
+        let fields: Vec<_> = columns
+            .iter()
+            .zip(fields)
+            .enumerate()
+            .map(|(i, (column, field))| if i < last { pad(&field, column.width()) } else { field })
+            .collect();
+        writeln!(out, "{}", fields.join(" "))
+    };
+
+    if flags.heading {
+        write_line(out, columns.iter().map(|column| column.title().to_string()).collect())?;
+    }
+    for row in rows {
+        write_line(out, columns.iter().map(|column| column.field(row, flags)).collect())?;
+    }
+
+    Ok(())
+}
+
+/// Pad `field` with spaces to `width` terminal columns, counting the columns of each
+/// character instead of its bytes.
+fn pad(field: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(field));
+    format!("{}{}", field, " ".repeat(padding))
+}
+
+/// Number of terminal columns taken by `text`.
+#[cfg(feature = "unicode-width")]
+fn display_width(text: &str) -> usize { text.width() }
+
+/// Ranges of the characters taking two terminal columns: Hangul Jamo, CJK, Hangul syllables,
+/// fullwidth forms and emoji.
+#[cfg(not(feature = "unicode-width"))]
+const WIDE_CHARS: [(u32, u32); 15] = [
+    (0x1100, 0x115F),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+/// Number of terminal columns taken by `text`, two for the characters of `WIDE_CHARS` and
+/// one for the others. Building with the `unicode-width` feature uses the full Unicode
+/// tables instead, with the combining characters taking no column.
+#[cfg(not(feature = "unicode-width"))]
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| {
+            let wide = WIDE_CHARS.iter().any(|&(first, last)| (first..=last).contains(&(c as u32)));
+            if wide { 2 } else { 1 }
+        })
+        .sum()
+}
+
+/// Write the rows as tab-separated columns without padding: user, line, pid (always empty
+/// on OpenBSD), login time as seconds since epoch, idle seconds, host and, with
+/// `--duration`, `--session`, `--namespaces`, `--endpoint` and `--all-types`, the duration,
+/// session ID, namespace, remote endpoint and entry type.
+///
+/// With `--null`, the columns are separated by a NUL and every row ends with two, so any
+/// byte but NUL can be on a field.
+fn print_tsv(out: &mut impl Write, rows: &[Row], flags: WhoFlags) -> io::Result<()> {
+    let (sep, end) = if flags.null { ("\0", "\0\0") } else { ("\t", "\n") };
+
+    if flags.heading {
+        write!(out, "NAME{0}LINE{0}PID{0}TIME{0}IDLE{0}HOST", sep)?;
+        if flags.duration {
+            write!(out, "{}DURATION", sep)?;
+        }
+        if flags.session {
+            write!(out, "{}SESSION", sep)?;
+        }
+        if flags.namespaces {
+            write!(out, "{}NAMESPACE", sep)?;
+        }
+        if flags.endpoint {
+            write!(out, "{}ENDPOINT", sep)?;
+        }
+        if flags.all_types {
+            write!(out, "{}TYPE", sep)?;
+        }
+        write!(out, "{}", end)?;
+    }
+
+    for row in rows {
+        let idle = row.idle_secs.map(|s| s.to_string()).unwrap_or_default();
+        let epoch = row.epoch.to_string();
+        let host = shown_host(&row.host, flags);
+        let fields = [&row.user, &row.line, &row.pid, &epoch, &idle, host];
+        let fields: Vec<_> = fields.iter().map(|field| quote_field(field, flags)).collect();
+
+        write!(out, "{}", fields.join(sep))?;
+        if flags.duration {
+            let duration = row.duration.map(|s| s.to_string()).unwrap_or_default();
+            write!(out, "{}{}", sep, quote_field(&duration, flags))?;
+        }
+        if flags.session {
+            let session = row.session.map(|id| id.to_string()).unwrap_or_default();
+            write!(out, "{}{}", sep, quote_field(&session, flags))?;
+        }
+        if flags.namespaces {
+            write!(out, "{}{}", sep, quote_field(namespace_column(row.namespace), flags))?;
+        }
+        if flags.endpoint {
+            write!(out, "{}{}", sep, quote_field(&row.endpoint, flags))?;
+        }
+        if flags.all_types {
+            write!(out, "{}{}", sep, quote_field(&row.utype, flags))?;
+        }
+        write!(out, "{}", end)?;
+    }
+
+    Ok(())
+}
+
+/// Replace the host of every row by its canonical name, resolving distinct hosts
+/// concurrently. Hosts that fail to resolve, or that are not resolved within `timeout`,
+/// are kept as they are.
+fn lookup_rows(rows: &mut [Row], timeout: Duration) {
+    let mut hosts: Vec<String> =
+        rows.iter().map(|row| row.host.clone()).filter(|host| !host.is_empty()).collect();
+    hosts.sort_unstable();
+    hosts.dedup();
+
+    let names = lookup_hosts(&hosts, timeout, canonical_host);
+    let names: HashMap<_, _> = hosts.into_iter().zip(names).collect();
+
+    for row in rows {
+        if let Some(name) = names.get(&row.host) {
+            row.host = name.clone();
+        }
+    }
+}
+
+/// Resolve `hosts` with `resolve` on at most `LOOKUP_THREADS` threads.
+///
+/// The names are returned in the same order as `hosts`. A host whose resolution fails or
+/// does not finish before `timeout` has passed is returned unchanged.
+fn lookup_hosts<F>(hosts: &[String], timeout: Duration, resolve: F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+{
+    let deadline = Instant::now() + timeout;
+    let resolve = Arc::new(resolve);
+    let queue = Arc::new(Mutex::new(hosts.iter().cloned().enumerate().collect::<Vec<_>>()));
+    let (sender, receiver) = mpsc::channel();
+
+    for _ in 0..LOOKUP_THREADS.min(hosts.len()) {
+        let (resolve, queue, sender) = (Arc::clone(&resolve), Arc::clone(&queue), sender.clone());
+
+        // Threads still blocked on a lookup past the deadline are left behind
+        thread::spawn(move || loop {
+            let next = match queue.lock() {
+                Ok(mut queue) => queue.pop(),
+                Err(_) => None,
+            };
+            let (index, host) = match next {
+                Some(n) => n,
+                None => break,
+            };
+
+            if sender.send((index, resolve(&host))).is_err() {
+                break;
+            }
+        });
+    }
+    drop(sender);
+
+    let mut names = hosts.to_vec();
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+
+        match receiver.recv_timeout(deadline - now) {
+            Ok((index, Some(name))) => names[index] = name,
+            Ok((_, None)) => continue,
+            // Either every host was resolved or the deadline was reached
+            Err(_) => break,
+        }
+    }
+
+    names
+}
+
+/// Split `host` on the X display form `host:display[.screen]` into the host name and the
+/// display part, with its `:`. `None` if it is not on that form, like a IPv6 address.
+fn display_target(host: &str) -> Option<(&str, &str)> {
+    if host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+
+    let (name, display) = host.split_at(host.rfind(':')?);
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let mut parts = display[1..].splitn(2, '.');
+    let valid = match (parts.next(), parts.next()) {
+        (Some(display), None) => is_number(display),
+        (Some(display), Some(screen)) => is_number(display) && is_number(screen),
+        _ => false,
+    };
+
+    if valid { Some((name, display)) } else { None }
+}
+
+/// Check if `field` matches `glob`, of `--host` or `--user`, always `true` without it.
+fn glob_allows(glob: Option<&str>, field: &BStr) -> bool {
+    match glob {
+        Some(glob) => glob_match(glob.as_bytes(), field),
+        None => true,
+    }
+}
+
+/// Check if `text` matches the shell glob `pattern`, byte by byte: `*` matches any bytes, `?`
+/// any byte, `[...]` any byte of the set, or not of it if it starts with `!` or `^`, with
+/// ranges like `a-z`, and `\` makes the next byte literal.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    // The pattern position after the last `*` and the text position it matches up to, to go
+    // back there, matching one more byte, when the rest of the pattern fails
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        let next = match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, t));
+                p += 1;
+                continue;
+            },
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match bracket_match(&pattern[p + 1..], text[t]) {
+                Some((true, len)) => Some(p + 1 + len),
+                Some((false, _)) => None,
+                // Not terminated, so it's a literal `[`
+                None if text[t] == b'[' => Some(p + 1),
+                None => None,
+            },
+            Some(b'\\') if p + 1 < pattern.len() => {
+                if pattern[p + 1] == text[t] { Some(p + 2) } else { None }
+            },
+            Some(&byte) if byte == text[t] => Some(p + 1),
+            _ => None,
+        };
+
+        match (next, star) {
+            (Some(next), _) => {
+                p = next;
+                t += 1;
+            },
+            (None, Some((after_star, matched_to))) => {
+                p = after_star;
+                t = matched_to + 1;
+                star = Some((after_star, t));
+            },
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&byte| byte == b'*')
+}
+
+/// Match `byte` with the bracket expression `class`, right after its `[`. Returns if it
+/// matched and the length of the expression up to its `]`, `None` if it has no `]`.
+fn bracket_match(class: &[u8], byte: u8) -> Option<(bool, usize)> {
+    let negated = class.first() == Some(&b'!') || class.first() == Some(&b'^');
+    let mut i = if negated { 1 } else { 0 };
+    let mut matched = false;
+
+    loop {
+        let first = *class.get(i)?;
+        // A `]` right after the `[` (and `!`) is part of the set
+        if first == b']' && i > negated as usize {
+            return Some((matched != negated, i + 1));
+        }
+
+        let (last, len) = match (class.get(i + 1), class.get(i + 2)) {
+            (Some(b'-'), Some(&last)) if last != b']' => (last, 3),
+            _ => (first, 1),
+        };
+        matched |= (first..=last).contains(&byte);
+        i += len;
+    }
+}
+
+/// The host to show for `host`: only its host name part with `--host-only`.
+fn shown_host(host: &str, flags: WhoFlags) -> &str {
+    match display_target(host) {
+        Some((name, _)) if flags.host_only => name,
+        _ => host,
+    }
+}
+
+/// Canonicalize the host name part of `host` via DNS, keeping the X display part if any.
+fn canonical_host(host: &str) -> Option<String> {
+    let (name, display) = display_target(host).unwrap_or((host, ""));
+    let name = CString::new(name).ok()?;
+
+    let mut hints: addrinfo = unsafe { mem::zeroed() };
+    hints.ai_flags = AI_CANONNAME;
+    let mut res = ptr::null_mut();
+
+    if unsafe { getaddrinfo(name.as_ptr(), ptr::null(), &hints, &mut res) } != 0 {
+        return None;
+    }
+
+    let canonical = unsafe {
+        if res.is_null() || (*res).ai_canonname.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr((*res).ai_canonname).to_string_lossy().into_owned())
+        }
+    };
+    unsafe { freeaddrinfo(res) };
+
+    canonical.map(|name| format!("{}{}", name, display))
+}
+
+/// Cut `host` to `--host-width` characters, the last one replaced by a ellipsis, or a `+`
+/// if the locale is not UTF-8.
+fn truncate_host(host: &str, flags: WhoFlags) -> Cow<'_, str> {
+    match flags.host_width {
+        Some(width) if host.chars().count() > width => {
+            let mut truncated: String = host.chars().take(width.saturating_sub(1)).collect();
+            if width > 0 {
+                truncated.push(if flags.utf8 { '…' } else { '+' });
+            }
+            Cow::Owned(truncated)
+        },
+        _ => Cow::Borrowed(host),
+    }
+}
+
+/// Check if the locale of the environment uses UTF-8, following the POSIX precedence of
+/// `LC_ALL`, `LC_CTYPE` and `LANG`.
+fn locale_is_utf8() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+/// The mesg column of a entry, empty when the column was not requested.
+fn mesg_column(msg: char, flags: WhoFlags) -> String {
+    if flags.message {
+        format!("{:<3} ", quote_field(&msg.to_string(), flags))
+    } else {
+        String::new()
+    }
+}
+
+/// The namespace column of a entry: `host` if its process is in the PID namespace of who,
+/// `other` if not, and `?` if it is unknown.
+fn namespace_column(namespace: Option<bool>) -> &'static str {
+    match namespace {
+        Some(true) => "host",
+        Some(false) => "other",
+        None => "?",
+    }
+}
+
+/// The duration column of a entry, as `(HH:MM)`.
+fn format_duration(duration: Option<i64>) -> String {
+    match duration {
+        Some(secs) => format!("({:02}:{:02})", secs / 3600, (secs % 3600) / 60),
+        None => "?".to_string(),
+    }
+}
+
+/// Wrap `field` in single quotes with `--quote`, so it is a single word to the shell. Its
+/// single quotes are written as `'\''`.
+fn quote_field(field: &str, flags: WhoFlags) -> Cow<'_, str> {
+    if flags.quote {
+        Cow::Owned(format!("'{}'", field.replace('\'', r"'\''")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
 
-    (msg, idle)
+/// Format a login time the way every column based output shows it.
+fn format_time(tm: &Tm) -> io::Result<String> {
+    time::format(tm.to_timespec(), "%Y-%m-%d %H:%M").map_err(|err| {
+        io::Error::new(io::ErrorKind::Other, format!("failed to format string: {}", err))
+    })
 }
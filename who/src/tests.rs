@@ -0,0 +1,1224 @@
+use super::*;
+
+use std::{cmp::Reverse, fs};
+
+use coreutils_core::{
+    tty::idle_time,
+    types::{Pid, TimeVal},
+    utmpx::{
+        write_entry,
+        UtmpxType::{self, *},
+    },
+    ByteSlice, B,
+};
+
+fn entry(ut_type: UtmpxType, user: &str, line: &str, host: &str, sec: i64) -> Utmpx {
+    raw_entry(ut_type, user.as_bytes(), line.as_bytes(), host, sec)
+}
+
+fn raw_entry(ut_type: UtmpxType, user: &[u8], line: &[u8], host: &str, sec: i64) -> Utmpx {
+    let timeval = TimeVal { tv_sec: sec, tv_usec: 0 };
+    let line = line.as_bstr();
+    Utmpx::new(ut_type, Pid(1000), line, line, user.as_bstr(), B(host).as_bstr(), timeval)
+}
+
+fn fixture() -> UtmpxSet {
+    vec![
+        entry(BootTime, "reboot", "~", "", 1_000),
+        entry(RunLevel, "runlevel", "~", "", 1_010),
+        entry(LoginProcess, "LOGIN", "tty1", "", 1_020),
+        entry(UserProcess, "alice", "pts/0", "10.0.0.1", 1_030),
+        entry(DeadProcess, "", "pts/1", "", 1_040),
+        entry(Accounting, "acct", "", "", 1_050),
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[test]
+fn debug_dumps_every_record() {
+    // Newest first, so numbering them by time would change every number
+    let mut records: Vec<_> = fixture().into_iter().collect();
+    records.sort_by_key(|u| Reverse(u.login_timespec()));
+    let mut out = Vec::new();
+
+    write_debug(&mut out, &records).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    for (num, u) in records.iter().enumerate() {
+        let block = format!(
+            "    type: {}\n    id:   {}\n    line: {}\n    pid:  {}\n    user: {}\n    host: {}\n",
+            u.utype(),
+            u.id(),
+            u.device_name(),
+            u.process_id(),
+            u.user(),
+            u.host()
+        );
+        let block = format!("record {}:\n{}", num, block);
+        assert!(out.contains(&block), "missing record:\n{}", block);
+    }
+    assert_eq!(out.matches("record ").count(), records.len());
+}
+
+#[test]
+fn sort_breaks_ties_by_line_and_user() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "carol", "pts/1", "", 1_000),
+        entry(UserProcess, "bob", "pts/1", "", 1_000),
+        entry(UserProcess, "dave", "pts/0", "", 2_000),
+        entry(UserProcess, "alice", "pts/2", "", 1_000),
+        entry(UserProcess, "erin", "pts/0", "", 1_000),
+    ]
+    .into_iter()
+    .collect();
+
+    let order = |mut entries: Vec<&Utmpx>| {
+        sort_entries(&mut entries);
+        entries.iter().map(|u| u.user().to_string()).collect::<Vec<_>>()
+    };
+
+    let entries: Vec<_> = uts.iter().collect();
+    let forward = order(entries.clone());
+    let backward = order(entries.into_iter().rev().collect());
+
+    assert_eq!(forward, ["erin", "bob", "carol", "alice", "dave"]);
+    assert_eq!(forward, backward);
+}
+
+#[test]
+fn all_types_keeps_every_record() {
+    let mut uts: Vec<_> = fixture().into_iter().collect();
+    uts.push(entry(UtmpxType::Unknown(42), "", "", "", 1_060));
+    let uts: UtmpxSet = uts.into_iter().collect();
+
+    let mut ut_vec =
+        filter_entries(&uts, WhoFlags { all_types: true, ..WhoFlags::default() }, None);
+    sort_entries(&mut ut_vec);
+    let rows: Vec<_> = ut_vec.into_iter().map(|u| Row::new(u).unwrap()).collect();
+    let mut out = Vec::new();
+
+    print_output(&mut out, &rows, WhoFlags { all_types: true, heading: true, ..WhoFlags::default() })
+        .unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert_eq!(lines.len(), uts.len() + 1);
+    assert!(lines[0].ends_with(" TYPE"));
+    assert!(lines[4].ends_with(" USER_PROCESS"));
+    assert!(lines[6].ends_with(" ACCOUNTING"));
+    assert!(lines[7].ends_with(" UNKNOWN(42)"));
+
+    let default = filter_entries(&uts, WhoFlags::default(), None);
+    assert_eq!(default.len(), 1);
+}
+
+#[test]
+fn mesg_column_only_present_when_requested() {
+    let uts = fixture();
+    let rows: Vec<_> =
+        uts.iter().filter(|u| u.is_user_process()).map(|u| Row::new(u).unwrap()).collect();
+    let render = |flags: WhoFlags| {
+        let mut out = Vec::new();
+        print_info(&mut out, &rows, flags).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let plain = render(WhoFlags::default());
+    let with_mesg = render(WhoFlags { message: true, ..WhoFlags::default() });
+
+    assert!(plain.starts_with("alice        pts/0"));
+    assert_eq!(with_mesg.chars().count(), plain.chars().count() + 4);
+    assert_eq!(with_mesg.find("pts/0"), Some(17));
+}
+
+#[test]
+fn every_mesg_spelling_enables_the_column() {
+    let uts = fixture();
+    let rows: Vec<_> =
+        uts.iter().filter(|u| u.is_user_process()).map(|u| Row::new(u).unwrap()).collect();
+
+    for arg in &["-T", "-w", "--mesg", "--message", "--writable"] {
+        let matches = cli::who_app().get_matches_from(vec!["who", arg]);
+        let flags = WhoFlags::from_matches(&matches).unwrap();
+        assert!(flags.message, "{} does not enable the column", arg);
+
+        let mut out = Vec::new();
+        print_output(&mut out, &rows, flags).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.find("pts/0"), Some(17), "{}: {:?}", arg, out);
+    }
+}
+
+#[test]
+fn non_utf8_fields_are_shown_lossy() {
+    let uts: UtmpxSet =
+        vec![raw_entry(UserProcess, b"al\xffce", b"pts/\xfe0", "", 1_030)].into_iter().collect();
+    let rows: Vec<_> = uts.iter().map(|u| Row::new(u).unwrap()).collect();
+    let mut out = Vec::new();
+
+    print_output(&mut out, &rows, WhoFlags { message: true, ..WhoFlags::default() }).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("al\u{FFFD}ce"));
+    assert!(out.contains("pts/\u{FFFD}0"));
+    assert_eq!(rows[0].mesg, '?');
+}
+
+#[test]
+fn tsv_has_every_column() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "alice", "pts/0", "10.0.0.1", 1_030),
+        entry(UserProcess, "bob", "pts/1", "", 1_060),
+    ]
+    .into_iter()
+    .collect();
+    let mut users: Vec<_> = uts.iter().collect();
+    users.sort_by_key(|u| u.login_timespec());
+    let rows: Vec<_> = users.into_iter().map(|u| Row::new(u).unwrap()).collect();
+    let mut out = Vec::new();
+
+    print_tsv(&mut out, &rows, WhoFlags { heading: true, ..WhoFlags::default() }).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let rows: Vec<_> = out.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0], "NAME\tLINE\tPID\tTIME\tIDLE\tHOST");
+    assert!(rows.iter().all(|row| row.matches('\t').count() == 5));
+    assert!(rows[1].starts_with("alice\tpts/0\t1000\t1030\t"));
+    assert!(rows[1].ends_with("\t10.0.0.1"));
+    assert!(rows[2].ends_with('\t'));
+}
+
+#[test]
+fn lookup_falls_back_on_timeout() {
+    let hosts: Vec<_> = ["a.lan", "slow.lan", "b.lan"].iter().map(|h| h.to_string()).collect();
+    let resolve = |host: &str| {
+        if host == "slow.lan" {
+            thread::sleep(Duration::from_millis(1_000));
+        }
+        Some(format!("{}.example.com", host))
+    };
+
+    let names = lookup_hosts(&hosts, Duration::from_millis(200), resolve);
+
+    assert_eq!(names, ["a.lan.example.com", "slow.lan", "b.lan.example.com"]);
+}
+
+#[test]
+fn lookup_keeps_unresolved_hosts() {
+    let hosts: Vec<_> = (0..20).map(|n| format!("host{}", n)).collect();
+    let resolve = |host: &str| {
+        let n: usize = host.trim_start_matches("host").parse().unwrap();
+        if n < 10 { Some(format!("{}.example.com", host)) } else { None }
+    };
+
+    let names = lookup_hosts(&hosts, Duration::from_secs(10), resolve);
+
+    for (n, name) in names.iter().enumerate() {
+        if n < 10 {
+            assert_eq!(*name, format!("host{}.example.com", n));
+        } else {
+            assert_eq!(*name, format!("host{}", n));
+        }
+    }
+}
+
+#[test]
+fn print_empty_adds_sentinel() {
+    let render = |flags: WhoFlags| {
+        let mut out = Vec::new();
+        print_output(&mut out, &[], flags).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let heading = WhoFlags { heading: true, ..WhoFlags::default() };
+
+    assert_eq!(render(WhoFlags::default()), "");
+    assert_eq!(render(heading).lines().count(), 1);
+    assert!(render(heading).starts_with("NAME"));
+
+    let empty = render(WhoFlags { print_empty: true, ..heading });
+    let lines: Vec<_> = empty.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("NAME"));
+    assert_eq!(lines[1], EMPTY_SENTINEL);
+
+    let tsv = render(WhoFlags { print_empty: true, tsv: true, ..WhoFlags::default() });
+    assert_eq!(tsv, format!("{}\n", EMPTY_SENTINEL));
+}
+
+#[test]
+fn print_empty_ignored_with_entries() {
+    let uts = fixture();
+    let rows: Vec<_> =
+        uts.iter().filter(|u| u.is_user_process()).map(|u| Row::new(u).unwrap()).collect();
+    let mut out = Vec::new();
+
+    print_output(&mut out, &rows, WhoFlags { print_empty: true, ..WhoFlags::default() }).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().count(), 1);
+    assert!(!out.contains(EMPTY_SENTINEL));
+}
+
+#[test]
+fn follow_reports_logins_and_logouts() {
+    let before: UtmpxSet = vec![
+        entry(UserProcess, "alice", "pts/0", "", 1_000),
+        entry(UserProcess, "bob", "pts/1", "", 1_010),
+    ]
+    .into_iter()
+    .collect();
+    let after: UtmpxSet = vec![
+        entry(UserProcess, "alice", "pts/0", "", 1_000),
+        entry(UserProcess, "carol", "pts/1", "", 1_020),
+        entry(UserProcess, "dave", "pts/2", "", 1_030),
+    ]
+    .into_iter()
+    .collect();
+    let mut watcher = UtmpWatcher::from_snapshot(&before);
+    let mut events = Vec::new();
+    watcher.update(&after, |event| events.push(event));
+
+    let changes = event_changes(events).unwrap();
+    let users = |rows: &[Row]| rows.iter().map(|row| row.user.clone()).collect::<Vec<_>>();
+    assert_eq!(users(&changes.logins), ["carol", "dave"]);
+    assert_eq!(users(&changes.logouts), ["bob"]);
+
+    let mut events = Vec::new();
+    watcher.update(&after, |event| events.push(event));
+    assert_eq!(event_changes(events).unwrap(), RowChanges::default());
+}
+
+#[test]
+fn host_width_truncates_long_hosts() {
+    let host = "build-runner-42.eu-west.example.com";
+    let width = |host_width, utf8| WhoFlags { host_width, utf8, ..WhoFlags::default() };
+
+    assert_eq!(truncate_host(host, width(None, true)), host);
+    assert_eq!(truncate_host(host, width(Some(15), true)), "build-runner-4…");
+    assert_eq!(truncate_host(host, width(Some(15), false)), "build-runner-4+");
+    assert_eq!(truncate_host(host, width(Some(host.len()), true)), host);
+    assert_eq!(truncate_host("10.0.0.1", width(Some(15), true)), "10.0.0.1");
+
+    let uts: UtmpxSet = vec![entry(UserProcess, "alice", "pts/0", host, 1_030)].into_iter().collect();
+    let rows: Vec<_> = uts.iter().map(|u| Row::new(u).unwrap()).collect();
+    let render = |flags| {
+        let mut out = Vec::new();
+        print_info(&mut out, &rows, flags).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    assert!(render(width(Some(15), true)).contains("(build-runner-4…)"));
+    assert!(render(width(None, true)).contains(&format!("({})", host)));
+}
+
+#[test]
+fn stdin_stream_is_read_as_entries() {
+    let path = env::temp_dir().join(format!("who_stdin_{}", process::id()));
+    for u in fixture().iter() {
+        write_entry(&path, u).unwrap();
+    }
+    let bytes = fs::read(&path);
+    fs::remove_file(&path).unwrap();
+    let bytes = bytes.unwrap();
+
+    let uts = UtmpxSet::from_reader(&bytes[..]).unwrap();
+    assert_eq!(uts.len(), fixture().len());
+
+    let users: Vec<_> = filter_entries(&uts, WhoFlags::default(), None)
+        .iter()
+        .map(|u| u.user().to_string())
+        .collect();
+    assert_eq!(users, ["alice"]);
+}
+
+#[test]
+fn stdin_is_the_only_file() {
+    let operands = |args: Vec<&str>| {
+        let matches = cli::who_app().get_matches_from_safe(args).unwrap();
+        check_operands(&file_operands(&matches))
+    };
+
+    assert!(operands(vec!["who", "-"]).is_ok());
+    assert!(operands(vec!["who", "/var/run/utmp", "--file", "/var/log/wtmp"]).is_ok());
+    assert!(operands(vec!["who", "-", "--file", "/var/log/wtmp"]).is_err());
+    assert!(operands(vec!["who", "/var/run/utmp", "--file", "-"]).is_err());
+    assert!(operands(vec!["who", "--file", "-", "--file", "-"]).is_err());
+}
+
+#[test]
+fn quote_escapes_spaces_and_quotes() {
+    let uts: UtmpxSet =
+        vec![entry(UserProcess, "o'brien smith", "pts/0", "10.0.0.1", 1_030)].into_iter().collect();
+    let rows: Vec<_> = uts.iter().map(|u| Row::new(u).unwrap()).collect();
+    let flags = WhoFlags { quote: true, ..WhoFlags::default() };
+
+    assert_eq!(quote_field("o'brien smith", flags), r"'o'\''brien smith'");
+    assert_eq!(quote_field("alice", WhoFlags::default()), "alice");
+
+    let mut out = Vec::new();
+    print_info(&mut out, &rows, flags).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with(r"'o'\''brien smith' 'pts/0'"), "{}", out);
+    assert!(out.contains("'(10.0.0.1)'"));
+
+    let mut out = Vec::new();
+    print_tsv(&mut out, &rows, flags).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let fields: Vec<_> = out.trim_end().split('\t').collect();
+    assert_eq!(fields[0], r"'o'\''brien smith'");
+    assert_eq!(fields[3], "'1030'");
+    assert_eq!(fields[5], "'10.0.0.1'");
+}
+
+#[test]
+fn duration_pairs_wtmp_logouts() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "alice", "pts/0", "", 1_000),
+        entry(DeadProcess, "", "pts/0", "", 500),
+        entry(DeadProcess, "", "pts/0", "", 4_720),
+        entry(UserProcess, "alice", "pts/0", "", 5_000),
+        entry(DeadProcess, "", "pts/0", "", 5_600),
+        entry(UserProcess, "bob", "pts/1", "", 2_000),
+    ]
+    .into_iter()
+    .collect();
+    let mut logins = filter_entries(&uts, WhoFlags::default(), None);
+    sort_entries(&mut logins);
+
+    let wtmp = session_durations(&uts, &logins, true, 9_200);
+    assert_eq!(wtmp, [3_720, 7_200, 600]);
+    let live = session_durations(&uts, &logins, false, 9_200);
+    assert_eq!(live, [8_200, 7_200, 4_200]);
+
+    let mut rows = make_rows(&uts, &logins, WhoFlags::default()).unwrap();
+    for (row, duration) in rows.iter_mut().zip(wtmp) {
+        row.duration = Some(duration);
+    }
+    let mut out = Vec::new();
+    print_info(&mut out, &rows, WhoFlags { duration: true, ..WhoFlags::default() }).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let durations: Vec<_> = out.lines().map(|line| line.rsplit(' ').next().unwrap()).collect();
+    assert_eq!(durations, ["(01:02)", "(02:00)", "(00:10)"]);
+}
+
+#[test]
+fn idle_seconds_replaces_idle_column() {
+    let uts = fixture();
+    let row = uts.iter().find(|u| u.is_user_process()).map(|u| Row::new(u).unwrap()).unwrap();
+    let render = |idle: IdleTime, idle_seconds: bool| {
+        let rows = [Row { idle, ..row.clone() }];
+        let mut out = Vec::new();
+        print_info(&mut out, &rows, WhoFlags { idle: true, idle_seconds, ..WhoFlags::default() })
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    assert!(render(IdleTime::Active(12), true).contains(" 12 "));
+    assert!(render(IdleTime::Idle(3720), true).contains(" 3720 "));
+    assert!(render(IdleTime::Old, true).contains(" -1 "));
+    assert!(render(IdleTime::Unknown, true).contains(" -1 "));
+    assert!(render(IdleTime::Idle(3720), false).contains(" 01:02 "));
+}
+
+#[test]
+fn terse_lists_each_user_once() {
+    let users = vec!["carol", "alice", "carol", "bob", "alice"].into_iter().map(String::from);
+    let mut out = Vec::new();
+
+    print_terse(&mut out, users).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), "alice\nbob\ncarol\n");
+}
+
+#[test]
+fn app_has_expected_flags() {
+    let args = ["who", "-abdHlmprsTtui", "--idle-seconds", "--all-types", "--duration"];
+    let matches = cli::who_app().get_matches_from_safe(args.iter()).unwrap();
+
+    for flag in &["all", "boot", "dead", "heading", "login", "only_me", "process"] {
+        assert!(matches.is_present(flag), "missing --{}", flag);
+    }
+    for flag in &["runlevel", "short", "message", "time", "users", "idle", "idle_seconds"] {
+        assert!(matches.is_present(flag), "missing --{}", flag);
+    }
+    assert!(matches.is_present("all_types") && matches.is_present("duration"));
+    assert_eq!(matches.value_of("output"), Some("table"));
+    assert_eq!(matches.value_of("lookup_timeout"), Some("2000"));
+
+    let openbsd = cli::who_app_for(true, false).get_matches_from_safe(vec!["who", "--boot"]);
+    assert!(openbsd.is_err());
+    assert!(cli::who_app().get_matches_from_safe(vec!["who", "-q", "-1"]).is_err());
+}
+
+#[test]
+fn follow_interval_must_be_positive() {
+    let interval = |args: &[&str]| {
+        let matches = cli::who_app().get_matches_from_safe(args.iter()).unwrap();
+        follow_interval(&matches)
+    };
+
+    assert_eq!(interval(&["who"]), Ok(None));
+    assert_eq!(interval(&["who", "--follow"]), Ok(Some(DEFAULT_FOLLOW_INTERVAL)));
+    assert_eq!(interval(&["who", "--follow=5"]), Ok(Some(Duration::from_secs(5))));
+    assert!(interval(&["who", "--follow=0"]).unwrap_err().starts_with("invalid follow interval"));
+    assert!(interval(&["who", "--follow=-1"]).is_err());
+}
+
+#[test]
+fn clock_changes_pair_old_and_new_time() {
+    // The clock was set back an hour, so the new time is before the old one
+    let records = vec![
+        entry(UserProcess, "alice", "pts/0", "", 1_030),
+        entry(OldTime, "", "|", "", 8_000),
+        entry(NewTime, "", "}", "", 4_400),
+        entry(NewTime, "", "}", "", 9_000),
+    ];
+    let uts: UtmpxSet = records.iter().cloned().collect();
+    let ut_vec = filter_entries(&uts, WhoFlags { time: true, ..WhoFlags::default() }, None);
+
+    let changes = clock_changes(&records);
+    let mut out = Vec::new();
+    print_clock_changes(&mut out, &changes).unwrap();
+
+    assert_eq!(ut_vec.len(), 3);
+    let secs: Vec<_> =
+        changes.iter().map(|(old, new)| (old.to_timespec().sec, new.to_timespec().sec)).collect();
+    assert_eq!(secs, [(8_000, 4_400)]);
+    assert!(String::from_utf8(out).unwrap().starts_with("clock changed from "));
+}
+
+#[test]
+fn clock_changes_keep_database_order() {
+    // The clock was set forward, then back to before the first change
+    let records = vec![
+        entry(OldTime, "", "|", "", 1_000),
+        entry(NewTime, "", "}", "", 5_000),
+        entry(UserProcess, "alice", "pts/0", "", 5_050),
+        entry(OldTime, "", "|", "", 5_100),
+        entry(NewTime, "", "}", "", 2_000),
+    ];
+
+    let secs: Vec<_> = clock_changes(&records)
+        .iter()
+        .map(|(old, new)| (old.to_timespec().sec, new.to_timespec().sec))
+        .collect();
+
+    assert_eq!(secs, [(1_000, 5_000), (5_100, 2_000)]);
+}
+
+#[test]
+fn host_only_strips_display() {
+    let flags = WhoFlags { host_only: true, ..WhoFlags::default() };
+
+    assert_eq!(display_target("host:1.0"), Some(("host", ":1.0")));
+    assert_eq!(shown_host("host:1.0", flags), "host");
+    assert_eq!(shown_host(":0", flags), "");
+    assert_eq!(shown_host("host:1.0", WhoFlags::default()), "host:1.0");
+    assert_eq!(shown_host("fe80::1", flags), "fe80::1");
+    assert_eq!(shown_host("10.0.0.1", flags), "10.0.0.1");
+    assert_eq!(display_target("host:tty"), None);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn session_column_shows_session_id() {
+    let mut utm = entry(UserProcess, "alice", "pts/0", "", 1_030).to_c_utmpx();
+    utm.ut_session = 4242;
+    let rows = [Row::new(&Utmpx::from_c_utmpx(utm)).unwrap()];
+    let render = |flags: WhoFlags| {
+        let mut out = Vec::new();
+        print_output(&mut out, &rows, flags).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let table = render(WhoFlags { session: true, heading: true, ..WhoFlags::default() });
+    let tsv = render(WhoFlags { session: true, tsv: true, ..WhoFlags::default() });
+
+    let lines: Vec<_> = table.lines().collect();
+    assert!(lines[0].ends_with(" SESSION"));
+    assert!(lines[1].ends_with(" 4242"));
+    assert!(tsv.ends_with("\t4242\n"));
+    assert!(!render(WhoFlags::default()).contains("4242"));
+}
+
+#[test]
+fn missing_file_fails_unless_ignored() {
+    let path = env::temp_dir().join(format!("coreutils_who_missing_{}", process::id()));
+
+    let file = path.to_str().unwrap();
+
+    assert_eq!(read_records(&path).unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert!(load_source(file, false).unwrap_err().starts_with("failed to read "));
+    // The entries of the system database are read instead, which may change meanwhile
+    assert!(load_source(file, true).is_ok());
+}
+
+#[test]
+fn me_flag_forms_are_equivalent() {
+    let flags = |args: &[&str]| {
+        let matches = cli::who_app().get_matches_from_safe(args.iter()).unwrap();
+        let flags = WhoFlags::from_matches(&matches).unwrap();
+        (flags.only_me, file_operand(&matches).map(String::from))
+    };
+
+    let short = flags(&["who", "-m"]);
+    assert_eq!(short, (true, None));
+    assert_eq!(flags(&["who", "--me"]), short);
+    assert_eq!(flags(&["who", "--associated_stdin"]), short);
+    assert_eq!(flags(&["who", "am", "i"]), short);
+    assert_eq!(flags(&["who", "am", "I"]), short);
+    assert_eq!(flags(&["who", "/var/log/wtmp"]), (false, Some("/var/log/wtmp".to_string())));
+}
+
+#[test]
+fn sort_idle_orders_by_idle_time() {
+    let now = 100_000;
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "active", "pts/0", "", 1_000),
+        entry(UserProcess, "hour", "pts/1", "", 1_010),
+        entry(UserProcess, "old", "pts/2", "", 1_020),
+        entry(UserProcess, "minutes", "pts/3", "", 1_030),
+        entry(UserProcess, "unknown", "pts/4", "", 1_040),
+        entry(UserProcess, "fresh", "pts/5", "", 1_050),
+    ]
+    .into_iter()
+    .collect();
+    let last_changes = [
+        ("active", now - 30),
+        ("hour", now - 3_600),
+        ("old", now - 2 * 24 * 3600),
+        ("minutes", now - 300),
+        ("unknown", 0),
+        ("fresh", now - 5),
+    ];
+    let mut ut_vec: Vec<_> = uts.iter().collect();
+    sort_entries(&mut ut_vec);
+    let mut rows: Vec<_> = ut_vec.into_iter().map(|u| Row::new(u).unwrap()).collect();
+    for row in &mut rows {
+        let last_change = last_changes.iter().find(|(user, _)| *user == row.user).unwrap().1;
+        row.idle = idle_time(last_change, now);
+    }
+
+    sort_by_idle(&mut rows);
+
+    let users: Vec<_> = rows.iter().map(|row| row.user.as_str()).collect();
+    assert_eq!(users, ["old", "unknown", "hour", "minutes", "active", "fresh"]);
+
+    let matches = cli::who_app().get_matches_from_safe(vec!["who", "--sort=idle"]).unwrap();
+    assert!(WhoFlags::from_matches(&matches).unwrap().sort_idle);
+    let matches = cli::who_app().get_matches_from(vec!["who"]);
+    assert!(!WhoFlags::from_matches(&matches).unwrap().sort_idle);
+}
+
+#[test]
+fn only_me_without_tty_is_empty() {
+    let uts = fixture();
+    let flags = WhoFlags { only_me: true, ..WhoFlags::default() };
+
+    // Like `echo | who -m`, with a pipe as the standard input
+    let ut_vec = filter_entries(&uts, flags, None);
+
+    assert!(ut_vec.is_empty());
+    let rows = make_rows(&uts, &ut_vec, flags).unwrap();
+    let mut out = Vec::new();
+    print_output(&mut out, &rows, flags).unwrap();
+    assert!(out.is_empty());
+}
+
+#[test]
+fn dump_round_trips_through_undump() {
+    // Read from a file, so the fields are truncated to the size they have there
+    let mut file = Vec::new();
+    for u in fixture().iter() {
+        write_entry_to(&mut file, u).unwrap();
+    }
+    let records: Vec<_> = UtmpxReader::new(&file[..]).collect::<io::Result<_>>().unwrap();
+    let mut dump = Vec::new();
+    print_dump(&mut dump, &records).unwrap();
+
+    let mut binary = Vec::new();
+    undump(&dump, &mut binary).unwrap();
+
+    assert_eq!(dump.lines().count(), records.len());
+    let restored: Vec<_> = UtmpxReader::new(&binary[..]).collect::<io::Result<_>>().unwrap();
+    assert_eq!(restored, records);
+
+    let err = undump(b"[7] [1] [id] [alice]\n", &mut Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "invalid entry at line 1");
+}
+
+#[test]
+fn dump_keeps_duplicates_in_file_order() {
+    let path = env::temp_dir().join(format!("who_dump_{}", process::id()));
+    let bob = entry(UserProcess, "bob", "pts/1", "", 1_040);
+    let alice = entry(UserProcess, "alice", "pts/0", "", 1_030);
+    for u in &[&bob, &bob, &alice] {
+        write_entry(&path, u).unwrap();
+    }
+    let records = read_records(&path);
+    fs::remove_file(&path).unwrap();
+    let records = records.unwrap();
+
+    let mut dump = Vec::new();
+    print_dump(&mut dump, &records).unwrap();
+
+    let users: Vec<_> = records.iter().map(|u| u.user().to_string()).collect();
+    assert_eq!(users, ["bob", "bob", "alice"]);
+    let lines: Vec<_> = dump.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], lines[1]);
+    assert!(lines[2].contains_str("[alice"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn namespaces_column_of_own_process() {
+    let timeval = TimeVal { tv_sec: 1_030, tv_usec: 0 };
+    let (line, user, host) = (B("pts/0").as_bstr(), B("alice").as_bstr(), B("").as_bstr());
+    let own = Utmpx::new(UserProcess, Pid::current(), line, line, user, host, timeval);
+    let uts: UtmpxSet = vec![own].into_iter().collect();
+    let ut_vec: Vec<_> = uts.iter().collect();
+    let flags = WhoFlags { namespaces: true, heading: true, ..WhoFlags::default() };
+    let rows = make_rows(&uts, &ut_vec, flags).unwrap();
+    let mut out = Vec::new();
+
+    print_output(&mut out, &rows, flags).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert!(lines[0].ends_with(" NAMESPACE"));
+    assert!(lines[1].ends_with(" host"));
+    assert_eq!(namespace_column(None), "?");
+    assert!(make_rows(&uts, &ut_vec, WhoFlags::default()).unwrap()[0].namespace.is_none());
+}
+
+#[test]
+fn boot_header_of_boot_entry() {
+    let mut out = Vec::new();
+    print_boot_header(&mut out, &fixture()).unwrap();
+
+    let boot = entry(BootTime, "reboot", "~", "", 1_000).login_timespec();
+    let expected = time::format(boot, "%Y-%m-%dT%H:%M:%S").unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("system boot {}\n", expected));
+
+    let no_boot: UtmpxSet = fixture().iter().filter(|u| !u.is_boot_time()).cloned().collect();
+    let mut out = Vec::new();
+    print_boot_header(&mut out, &no_boot).unwrap();
+    assert!(out.is_empty());
+}
+
+#[test]
+fn host_glob_filters_entries() {
+    let uts = fixture();
+    let hosts = |glob: &str| -> Vec<String> {
+        let mut ut_vec =
+            filter_entries(&uts, WhoFlags { all_types: true, ..WhoFlags::default() }, None);
+        ut_vec.retain(|u| glob_match(glob.as_bytes(), u.host().as_bytes()));
+        ut_vec.iter().map(|u| u.user().to_string()).collect()
+    };
+
+    assert_eq!(hosts("10.0.*"), ["alice"]);
+    assert_eq!(hosts("10.0.0.?"), ["alice"]);
+    assert_eq!(hosts("10.[0-9].0.[!2]"), ["alice"]);
+    assert!(hosts("192.168.*").is_empty());
+    assert!(hosts("*.example.com").is_empty());
+
+    assert!(glob_match(b"*.example.com", b"login.example.com"));
+    assert!(!glob_match(b"*.example.com", b"example.com"));
+    assert!(glob_match(b"a\\*[]]", b"a*]"));
+    assert!(glob_match(b"*", b""));
+}
+
+#[test]
+fn user_glob_filters_entries() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "svc-backup", "pts/0", "", 1_000),
+        entry(UserProcess, "svc-web", "pts/1", "", 1_010),
+        entry(UserProcess, "alice", "pts/2", "", 1_020),
+        entry(LoginProcess, "svc-login", "tty1", "", 1_030),
+    ]
+    .into_iter()
+    .collect();
+    let users = |glob: &str| -> Vec<String> {
+        let mut ut_vec = filter_entries(&uts, WhoFlags::default(), None);
+        ut_vec.retain(|u| glob_allows(None, u.host()) && glob_allows(Some(glob), u.user()));
+        sort_entries(&mut ut_vec);
+        ut_vec.iter().map(|u| u.user().to_string()).collect()
+    };
+
+    // The login process is left out by the type filter
+    assert_eq!(users("svc-*"), ["svc-backup", "svc-web"]);
+    assert_eq!(users("alice"), ["alice"]);
+    assert!(users("alic").is_empty());
+}
+
+#[test]
+fn wide_user_names_are_padded_by_columns() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "山田", "pts/0", "", 1_000),
+        entry(UserProcess, "alice", "pts/1", "", 1_000),
+    ]
+    .into_iter()
+    .collect();
+    let mut ut_vec = filter_entries(&uts, WhoFlags::default(), None);
+    sort_entries(&mut ut_vec);
+    let rows = make_rows(&uts, &ut_vec, WhoFlags::default()).unwrap();
+    let mut out = Vec::new();
+
+    print_info(&mut out, &rows, WhoFlags::default()).unwrap();
+
+    assert_eq!(display_width("山田"), 4);
+    let out = String::from_utf8(out).unwrap();
+    let line_columns: Vec<_> = out
+        .lines()
+        .map(|line| {
+            let (user, rest) = line.split_at(line.find("pts/").unwrap());
+            assert!(!rest.is_empty());
+            display_width(user)
+        })
+        .collect();
+    assert_eq!(line_columns, [13, 13]);
+}
+
+#[test]
+fn endpoint_column_of_ssh_logins() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "alice", "pts/0", "1.2.3.4", 1_000),
+        entry(UserProcess, "bob", "pts/1", "1.2.3.4:54321", 1_010),
+        entry(UserProcess, "carol", "tty1", "", 1_020),
+    ]
+    .into_iter()
+    .collect();
+    let mut ut_vec = filter_entries(&uts, WhoFlags::default(), None);
+    sort_entries(&mut ut_vec);
+    let flags = WhoFlags { endpoint: true, tsv: true, heading: true, ..WhoFlags::default() };
+    let rows = make_rows(&uts, &ut_vec, flags).unwrap();
+    let mut out = Vec::new();
+
+    print_output(&mut out, &rows, flags).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let endpoints: Vec<_> = out.lines().map(|line| line.rsplit('\t').next().unwrap()).collect();
+    assert_eq!(endpoints, ["ENDPOINT", "1.2.3.4", "1.2.3.4:54321", ""]);
+}
+
+#[test]
+fn relative_login_times_under_pinned_clock() {
+    let now = 1_600_000_000;
+
+    assert_eq!(relative_time(now, now).unwrap(), "0s ago");
+    assert_eq!(relative_time(now - 45, now).unwrap(), "45s ago");
+    assert_eq!(relative_time(now - 3 * 60 - 59, now).unwrap(), "3m ago");
+    assert_eq!(relative_time(now - 2 * 3600, now).unwrap(), "2h ago");
+    assert_eq!(relative_time(now - 4 * 86_400 - 3600, now).unwrap(), "4d ago");
+    assert_eq!(relative_time(now - 7 * 86_400 + 1, now).unwrap(), "6d ago");
+    assert_eq!(relative_time(now - 7 * 86_400, now), None);
+    assert_eq!(relative_time(now + 10, now), None);
+
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "alice", "pts/0", "", now - 2 * 3600),
+        entry(UserProcess, "bob", "pts/1", "", now - 30 * 86_400),
+    ]
+    .into_iter()
+    .collect();
+    let mut ut_vec = filter_entries(&uts, WhoFlags::default(), None);
+    sort_entries(&mut ut_vec);
+    let mut rows = make_rows(&uts, &ut_vec, WhoFlags::default()).unwrap();
+    let absolute = rows[0].time.clone();
+
+    relative_times(&mut rows, now);
+
+    assert_eq!(rows[0].time, absolute);
+    assert_eq!(rows[1].time, "2h ago");
+}
+
+/// Writer failing every write with `kind`.
+struct FailingWriter(io::ErrorKind);
+
+impl Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> { Err(io::Error::from(self.0)) }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+#[test]
+fn exit_status_of_writes() {
+    let uts = fixture();
+    let ut_vec = filter_entries(&uts, WhoFlags::default(), None);
+    let rows = make_rows(&uts, &ut_vec, WhoFlags::default()).unwrap();
+    let mut out = Vec::new();
+
+    assert_eq!(exit_status(print_output(&mut out, &rows, WhoFlags::default()), "output"), 0);
+    assert!(!out.is_empty());
+
+    let mut closed = FailingWriter(io::ErrorKind::BrokenPipe);
+    let res = print_output(&mut closed, &rows, WhoFlags::default());
+    assert_eq!(exit_status(res, "output"), 141);
+
+    let mut full = FailingWriter(io::ErrorKind::Other);
+    let users = vec!["alice".to_string()];
+    assert_eq!(exit_status(print_count(&mut full, users), "output"), 1);
+
+    let mut out = Vec::new();
+    print_count(&mut out, vec!["alice".to_string(), "bob".to_string()]).unwrap();
+    assert_eq!(out, b"alice bob \n# users=2\n");
+}
+
+#[test]
+fn run_level_entries_show_levels() {
+    let timeval = TimeVal { tv_sec: 1_010, tv_usec: 0 };
+    let (line, user) = (B("~").as_bstr(), B("runlevel").as_bstr());
+    let pid = Pid((i32::from(b'1') << 8) | i32::from(b'3'));
+    let uts: UtmpxSet = vec![
+        Utmpx::new(RunLevel, pid, line, line, user, B("").as_bstr(), timeval),
+        entry(UserProcess, "alice", "pts/0", "", 1_030),
+    ]
+    .into_iter()
+    .collect();
+    let flags = WhoFlags { run_level: true, ..WhoFlags::default() };
+    let ut_vec = filter_entries(&uts, flags, None);
+    let rows = make_rows(&uts, &ut_vec, flags).unwrap();
+    let mut out = Vec::new();
+
+    print_info(&mut out, &rows, flags).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().count(), 1);
+    assert!(out.contains(" run-level 3 (prev 1) "), "unexpected output: {}", out);
+    assert!(!out.contains(&pid.0.to_string()));
+
+    // The machine readable outputs keep the process ID
+    let mut out = Vec::new();
+    print_tsv(&mut out, &rows, flags).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with(&format!("runlevel\t~\t{}\t1010\t", pid.0)), "{:?}", out);
+    assert_eq!(Column::Pid.field(&rows[0], flags), pid.0.to_string());
+}
+
+#[test]
+fn header_printed_once_for_many_files() {
+    let paths: Vec<_> = (0..2)
+        .map(|i| env::temp_dir().join(format!("coreutils_who_group_{}_{}", i, process::id())))
+        .collect();
+    write_entry(&paths[0], &entry(UserProcess, "alice", "pts/0", "", 1_030)).unwrap();
+    write_entry(&paths[1], &entry(UserProcess, "bob", "pts/1", "", 1_040)).unwrap();
+
+    let render = |extra: &[&str]| {
+        let mut args = vec!["who", "-H"];
+        args.extend(extra);
+        let (first, second) = (paths[0].to_str().unwrap(), paths[1].to_str().unwrap());
+        args.extend(&[first, "--file", second]);
+        let matches = cli::who_app().get_matches_from_safe(args).unwrap();
+        let flags = WhoFlags::from_matches(&matches).unwrap();
+        let groups: Vec<_> = load_groups(&matches)
+            .unwrap()
+            .iter()
+            .map(|uts| make_rows(uts, &filter_entries(uts, flags, None), flags).unwrap())
+            .collect();
+
+        let mut out = Vec::new();
+        print_groups(&mut out, &groups, flags).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let single = render(&[]);
+    let per_group = render(&["--header-per-group"]);
+    for path in &paths {
+        fs::remove_file(path).unwrap();
+    }
+
+    let lines: Vec<_> = single.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("NAME"));
+    assert!(lines[1].starts_with("alice"));
+    assert!(lines[2].starts_with("bob"));
+    assert_eq!(per_group.lines().filter(|line| line.starts_with("NAME")).count(), 2);
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "logind"))]
+fn logind_sessions_of_fixture_dir() {
+    let dir = env::temp_dir().join(format!("coreutils_who_logind_{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let files = [
+        ("2", "# This is private data. Do not parse.\nUID=1000\nUSER=alice\nACTIVE=1\n\
+               TYPE=x11\nCLASS=user\nDISPLAY=:0\nLEADER=1234\nREALTIME=1600000000123456\n"),
+        ("5", "UID=1001\nUSER=bob\nTYPE=tty\nCLASS=user\nTTY=pts/3\nREMOTE=1\n\
+               REMOTE_HOST=10.0.0.7\nLEADER=4321\nREALTIME=1600000100000000\n"),
+        ("c1", "UID=120\nUSER=gdm\nCLASS=greeter\nDISPLAY=:1\nREALTIME=1599999000000000\n"),
+        ("2.ref", ""),
+        ("7", "UID=1002\n"),
+    ];
+    for (name, contents) in &files {
+        fs::write(dir.join(name), contents).unwrap();
+    }
+
+    let sessions = logind::sessions(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+    let sessions = sessions.unwrap();
+    assert!(logind::sessions(&dir).is_err());
+
+    let matches = cli::who_app().get_matches_from(vec!["who", "--logind"]);
+    let rows = session_rows(&sessions, &matches, WhoFlags::default()).unwrap();
+
+    assert_eq!(rows.len(), 2);
+    assert_eq!((&*rows[0].user, &*rows[0].line, &*rows[0].host), ("alice", ":0", ":0"));
+    assert_eq!((&*rows[0].pid, rows[0].epoch, rows[0].session), ("1234", 1_600_000_000, Some(2)));
+    assert_eq!((&*rows[1].user, &*rows[1].line, &*rows[1].host), ("bob", "pts/3", "10.0.0.7"));
+    assert_eq!(rows[1].endpoint, "10.0.0.7");
+
+    let matches = cli::who_app().get_matches_from(vec!["who", "--logind", "--user=b*"]);
+    let rows = session_rows(&sessions, &matches, WhoFlags::default()).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].user, "bob");
+    let flags = WhoFlags { boot: true, ..WhoFlags::default() };
+    assert!(session_rows(&sessions, &matches, flags).unwrap().is_empty());
+}
+
+#[test]
+fn columns_pick_and_order_fields() {
+    let uts: UtmpxSet =
+        vec![entry(UserProcess, "alice", "pts/0", "10.0.0.1", 1_030)].into_iter().collect();
+    let ut_vec = filter_entries(&uts, WhoFlags::default(), None);
+    let rows = make_rows(&uts, &ut_vec, WhoFlags::default()).unwrap();
+    let matches = cli::who_app().get_matches_from(vec!["who", "-H", "--columns=line,user,comment"]);
+    let flags = WhoFlags::from_matches(&matches).unwrap();
+    let mut out = Vec::new();
+
+    print_output(&mut out, &rows, flags).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out, "LINE       NAME         COMMENT\npts/0      alice        (10.0.0.1)\n");
+    assert_eq!(
+        Columns::parse("time,pid").unwrap().iter().collect::<Vec<_>>(),
+        [Column::Time, Column::Pid]
+    );
+}
+
+#[test]
+fn columns_reject_unknown_names() {
+    let err = Columns::parse("user,tty").unwrap_err();
+    assert!(err.starts_with("invalid column 'tty'"), "unexpected error: {}", err);
+    assert!(err.contains("user, mesg, line, pid, time, idle, host, comment"));
+
+    assert!(Columns::parse("").is_err());
+    assert_eq!(Columns::parse("user,user").unwrap_err(), "column 'user' given more than once");
+}
+
+/// Output of `TZ=UTC who -a` of GNU coreutils 9.1 on a file with the entries of
+/// `all_matches_gnu_order`, written in that order.
+const GNU_WHO_ALL: &str = "           system boot  Sep 13 12:26
+           run-level 5  Sep 13 12:26                   last=S
+           tty1         Sep 13 12:26               800 id=tty1
+LOGIN      tty1         Sep 13 12:26               812 id=tty1
+alice    ? pts/0        Sep 13 12:27   ?          1234 (10.0.0.1)
+           pts/1        Sep 13 12:27              1300 id=ts/1  term=0 exit=0
+bob      ? pts/2        Sep 13 12:28   ?          1400
+";
+
+#[test]
+fn all_matches_gnu_order() {
+    let at = |ut_type, pid, id: &str, line: &str, user: &str, host: &str, sec| {
+        let timeval = TimeVal { tv_sec: sec, tv_usec: 0 };
+        let (id, line) = (B(id).as_bstr(), B(line).as_bstr());
+        let (user, host) = (B(user).as_bstr(), B(host).as_bstr());
+        Utmpx::new(ut_type, Pid(pid), line, id, user, host, timeval)
+    };
+    let boot = 1_600_000_000;
+    let run_level = i32::from(b'5') | (i32::from(b'S') << 8);
+    let uts: UtmpxSet = vec![
+        at(BootTime, 0, "~~", "~", "reboot", "6.1.0", boot),
+        at(RunLevel, run_level, "~~", "~", "runlevel", "6.1.0", boot),
+        at(InitProcess, 800, "tty1", "tty1", "", "", boot),
+        at(LoginProcess, 812, "tty1", "tty1", "LOGIN", "", boot),
+        at(UserProcess, 1234, "ts/0", "pts/0", "alice", "10.0.0.1", boot + 60),
+        at(DeadProcess, 1300, "ts/1", "pts/1", "", "", boot + 60),
+        at(UserProcess, 1400, "ts/2", "pts/2", "bob", "", boot + 120),
+    ]
+    .into_iter()
+    .collect();
+    let matches = cli::who_app().get_matches_from(vec!["who", "-a"]);
+    let mut ut_vec = filter_entries(&uts, WhoFlags::from_matches(&matches).unwrap(), None);
+
+    sort_entries(&mut ut_vec);
+
+    // The name, line and PID columns of GNU who, that don't depend on the time zone and on
+    // the terminals of the system. The boot and runlevel lines have no PID
+    let fields = |user: &str, line: &str, pid: &str| format!("{:<8} {:<12} {}", user, line, pid);
+    let lines: Vec<_> = ut_vec
+        .iter()
+        .map(|u| match (u.utype(), u.run_level()) {
+            (BootTime, _) => fields("", "system boot", ""),
+            (RunLevel, Some((current, previous))) => {
+                fields("", &format!("run-level {}", current), &format!("last={}", previous))
+            },
+            _ => {
+                let (user, line) = (u.user().to_string(), u.device_name().to_string());
+                fields(&user, &line, &u.process_id().to_string())
+            },
+        })
+        .collect();
+    let gnu: Vec<_> = GNU_WHO_ALL
+        .lines()
+        .map(|line| {
+            let pid = line[36..].split_whitespace().find(|field| {
+                field.starts_with("last=") || field.bytes().all(|byte| byte.is_ascii_digit())
+            });
+            fields(line[..8].trim_end(), line[11..23].trim_end(), pid.unwrap_or(""))
+        })
+        .collect();
+    assert_eq!(lines, gnu);
+}
+
+#[test]
+fn highlight_me_marks_own_sessions_elsewhere() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "alice", "pts/0", "", 1_030),
+        entry(UserProcess, "alice", "pts/1", "10.0.0.1", 1_040),
+        entry(UserProcess, "bob", "pts/2", "", 1_050),
+    ]
+    .into_iter()
+    .collect();
+    let mut ut_vec: Vec<_> = uts.iter().collect();
+    sort_entries(&mut ut_vec);
+    let mut rows: Vec<_> = ut_vec.into_iter().map(|u| Row::new(u).unwrap()).collect();
+
+    mark_mine(&mut rows, None, Some("pts/0"));
+    assert!(rows.iter().all(|row| !row.mine));
+
+    mark_mine(&mut rows, Some("alice"), Some("pts/0"));
+    let flags = WhoFlags { highlight_me: true, heading: true, ..WhoFlags::default() };
+    let mut out = Vec::new();
+    print_output(&mut out, &rows, flags).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+
+    assert!(lines[0].starts_with(" NAME"), "{:?}", lines[0]);
+    assert!(lines[1].starts_with(" alice        pts/0"), "{:?}", lines[1]);
+    assert!(lines[2].starts_with("*alice        pts/1"), "{:?}", lines[2]);
+    assert!(lines[3].starts_with(" bob          pts/2"), "{:?}", lines[3]);
+
+    // Without a terminal, every session of the user is elsewhere
+    mark_mine(&mut rows, Some("alice"), None);
+    assert_eq!(rows.iter().filter(|row| row.mine).count(), 2);
+}
+
+#[test]
+fn verify_owner_marks_foreign_and_missing_terminals() {
+    let dev = env::temp_dir().join(format!("who_verify_owner_{}", process::id()));
+    fs::create_dir_all(dev.join("pts")).unwrap();
+    fs::write(dev.join("tty1"), "").unwrap();
+    fs::write(dev.join("pts/0"), "").unwrap();
+    let uid = fs::metadata(dev.join("tty1")).unwrap().uid();
+    let passwd = dev.join("passwd");
+    fs::write(&passwd, format!("me:x:{}:{}::/:/bin/sh\nother:x:4242:4242::/:/bin/sh\n", uid, uid))
+        .unwrap();
+
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "me", "tty1", "", 1_030),
+        entry(UserProcess, "other", "pts/0", "", 1_040),
+        entry(UserProcess, "me", "pts/9", "", 1_050),
+    ]
+    .into_iter()
+    .collect();
+    let mut ut_vec: Vec<_> = uts.iter().collect();
+    sort_entries(&mut ut_vec);
+    let mut rows: Vec<_> = ut_vec.into_iter().map(|u| Row::new(u).unwrap()).collect();
+
+    verify_owners(rows.iter_mut(), &dev, &mut NameResolver::from_files(&passwd, "/nonexistent"));
+    let flags = WhoFlags { verify_owner: true, ..WhoFlags::default() };
+    let mut out = Vec::new();
+    print_output(&mut out, &rows, flags).unwrap();
+    fs::remove_dir_all(&dev).unwrap();
+
+    let marks: Vec<_> = rows.iter().map(|row| row.owner).collect();
+    assert_eq!(marks, [None, Some('!'), Some('?')]);
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert!(lines[0].starts_with(" me           tty1"), "{:?}", lines[0]);
+    assert!(lines[1].starts_with("!other        pts/0"), "{:?}", lines[1]);
+    assert!(lines[2].starts_with("?me           pts/9"), "{:?}", lines[2]);
+}
+
+#[test]
+fn no_header_on_empty_only_drops_lone_headers() {
+    let uts = fixture();
+    let rows: Vec<_> =
+        uts.iter().filter(|u| u.is_user_process()).map(|u| Row::new(u).unwrap()).collect();
+    let render = |args: &[&str], groups: &[Vec<Row>]| {
+        let matches = cli::who_app().get_matches_from_safe(args).unwrap();
+        let mut out = Vec::new();
+        print_groups(&mut out, groups, WhoFlags::from_matches(&matches).unwrap()).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let (empty, full) = (vec![Vec::new()], vec![rows.clone()]);
+
+    // Without the flag, -H always displays the header
+    assert!(render(&["who", "-H"], &empty).starts_with("NAME"));
+
+    let flagged = ["who", "-H", "--no-header-on-empty"];
+    assert_eq!(render(&flagged, &empty), "");
+    assert_eq!(render(&["who", "-H", "--no-header-on-empty", "--output=tsv"], &empty), "");
+    let out = render(&flagged, &full);
+    assert_eq!(out.lines().count(), 2);
+    assert!(out.starts_with("NAME"));
+
+    // The header moves to the first group with rows
+    let out = render(&flagged, &[Vec::new(), rows]);
+    let lines: Vec<_> = out.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("NAME") && lines[1].starts_with("alice"));
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn gzipped_wtmp_is_decompressed() {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let path = env::temp_dir().join(format!("who_wtmp_{}", process::id()));
+    let gz_path = env::temp_dir().join(format!("who_wtmp_{}.1.gz", process::id()));
+    for u in fixture().iter() {
+        write_entry(&path, u).unwrap();
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&fs::read(&path).unwrap()).unwrap();
+    fs::write(&gz_path, encoder.finish().unwrap()).unwrap();
+
+    let plain = read_records(&path);
+    let gzipped = read_records(&gz_path);
+    let corrupt = fs::write(&gz_path, b"\x1f\x8bnot gzip").and_then(|_| read_records(&gz_path));
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&gz_path).unwrap();
+
+    let (plain, gzipped) = (plain.unwrap(), gzipped.unwrap());
+    assert_eq!(gzipped.len(), fixture().len());
+    assert_eq!(gzipped, plain);
+    assert!(corrupt.is_err());
+}
+
+#[test]
+fn null_output_layout() {
+    let uts: UtmpxSet = vec![
+        entry(UserProcess, "alice", "nulltty0", "10.0.0.1", 1_030),
+        entry(UserProcess, "bob", "nulltty1", "", 1_040),
+    ]
+    .into_iter()
+    .collect();
+    let mut ut_vec: Vec<_> = uts.iter().collect();
+    sort_entries(&mut ut_vec);
+    let rows: Vec<_> = ut_vec.into_iter().map(|u| Row::new(u).unwrap()).collect();
+
+    let record = |fields: &[&str]| format!("{}\0\0", fields.join("\0"));
+    let expected = record(&["alice", "nulltty0", "1000", "1030", "", "10.0.0.1"])
+        + &record(&["bob", "nulltty1", "1000", "1040", "", ""]);
+
+    for args in &[["who", "-0"], ["who", "--null"]] {
+        let matches = cli::who_app().get_matches_from_safe(args).unwrap();
+        let mut out = Vec::new();
+        print_output(&mut out, &rows, WhoFlags::from_matches(&matches).unwrap()).unwrap();
+
+        assert_eq!(out.as_bstr(), expected.as_str());
+    }
+}
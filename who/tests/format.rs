@@ -0,0 +1,111 @@
+//! Property tests of the output of `who` with fields of any length, run on the built binary
+//! with a utmpx file holding one generated entry.
+#![cfg(not(target_os = "openbsd"))]
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{self, Command},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use coreutils_core::{
+    types::{Pid, TimeVal},
+    utmpx::{write_entry, Utmpx, UtmpxType},
+    ByteSlice,
+};
+
+use proptest::prelude::*;
+
+static CASE: AtomicUsize = AtomicUsize::new(0);
+
+/// Path of the `who` binary, next to the `deps` directory of this test.
+fn who_bin() -> PathBuf {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join("who")
+}
+
+/// Run `who` with `args` on a file with a user process entry of `user`, `line` and `host`,
+/// returning its standard output if it exited successfully.
+fn run_who(user: &str, line: &str, host: &str, args: &[&str]) -> Result<String, String> {
+    let case = CASE.fetch_add(1, Ordering::SeqCst);
+    let path = env::temp_dir().join(format!("who_format_{}_{}", process::id(), case));
+    let timeval = TimeVal { tv_sec: 1_600_000_000, tv_usec: 0 };
+    let (line, user, host) =
+        (line.as_bytes().as_bstr(), user.as_bytes().as_bstr(), host.as_bytes().as_bstr());
+    let entry = Utmpx::new(UtmpxType::UserProcess, Pid(1000), line, line, user, host, timeval);
+    write_entry(&path, &entry).map_err(|err| err.to_string())?;
+
+    let output = Command::new(who_bin()).args(args).arg(&path).output();
+    fs::remove_file(&path).map_err(|err| err.to_string())?;
+
+    let output = output.map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "who {:?} failed with {}: {}",
+            args,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|err| err.to_string())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn default_format_has_every_column(
+        user in "[a-zA-Z0-9_]{1,64}",
+        line in "[a-z0-9]{1,40}",
+        host in "[a-z0-9.-]{0,300}",
+    ) {
+        let out = run_who(&user, &line, &host, &[]).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        prop_assert_eq!(lines.len(), 1);
+
+        // NAME LINE DATE TIME (HOST)
+        let fields: Vec<_> = lines[0].split_whitespace().collect();
+        prop_assert_eq!(fields.len(), 5, "{:?}", lines[0]);
+        prop_assert!(user.starts_with(fields[0]));
+        prop_assert!(line.starts_with(fields[1]));
+        prop_assert!(fields[4].starts_with('(') && fields[4].ends_with(')'));
+    }
+
+    #[test]
+    fn picked_columns_are_all_present(
+        user in "[a-zA-Z0-9_]{1,64}",
+        line in "[a-z0-9]{1,40}",
+        host in "[a-z0-9.-]{0,300}",
+    ) {
+        let columns = "--columns=user,mesg,line,pid,time,idle,comment";
+        let out = run_who(&user, &line, &host, &["-H", columns]).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        prop_assert_eq!(lines.len(), 2);
+
+        prop_assert_eq!(lines[0].split_whitespace().count(), 7, "{:?}", lines[0]);
+        // The time has a date and a time of day
+        let fields: Vec<_> = lines[1].split_whitespace().collect();
+        prop_assert_eq!(fields.len(), 8, "{:?}", lines[1]);
+        prop_assert_eq!(fields[3], "1000");
+        prop_assert!(!lines[1].ends_with(' '));
+    }
+
+    #[test]
+    fn host_width_bounds_the_comment(
+        user in "[a-zA-Z0-9_]{1,64}",
+        host in "[a-z0-9.-]{0,300}",
+        width in 1usize..40,
+    ) {
+        let width_arg = format!("--host-width={}", width);
+        let out = run_who(&user, "pts0", &host, &[&width_arg]).unwrap();
+
+        let fields: Vec<_> = out.split_whitespace().collect();
+        prop_assert_eq!(fields.len(), 5, "{:?}", out);
+        prop_assert!(fields[4].chars().count() <= width + 2, "{:?}", fields[4]);
+    }
+}
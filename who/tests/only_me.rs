@@ -0,0 +1,45 @@
+//! Tests of `who -m` run on the built binary with a pipe as its standard input.
+#![cfg(not(target_os = "openbsd"))]
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{self, Command, Stdio},
+};
+
+use coreutils_core::{
+    types::{Pid, TimeVal},
+    utmpx::{write_entry, Utmpx, UtmpxType},
+    ByteSlice,
+};
+
+/// Path of the `who` binary, next to the `deps` directory of this test.
+fn who_bin() -> PathBuf {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join("who")
+}
+
+#[test]
+fn only_me_with_piped_stdin_is_empty() {
+    let path = env::temp_dir().join(format!("who_only_me_{}", process::id()));
+    let timeval = TimeVal { tv_sec: 1_600_000_000, tv_usec: 0 };
+    let (line, user, host) = (b"pts/0".as_bstr(), b"alice".as_bstr(), b"".as_bstr());
+    let entry = Utmpx::new(UtmpxType::UserProcess, Pid(1000), line, line, user, host, timeval);
+    write_entry(&path, &entry).unwrap();
+
+    let output = Command::new(who_bin())
+        .arg("-m")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .output();
+    fs::remove_file(&path).unwrap();
+
+    let output = output.unwrap();
+    assert!(output.status.success(), "who -m failed with {}", output.status);
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+}